@@ -1,22 +1,45 @@
+use anyhow::{bail, ensure, Result};
+
 use crate::yan85::{
     constants::{Constants, Encodable},
     instruction::Instruction,
 };
 
 /// Assembles the given instructions, converting them into bytes.
-pub fn assemble(instructions: &[Instruction], constants: Constants) -> Vec<u8> {
-    instructions
-        .iter()
-        .flat_map(|i| assemble_instruction(i, constants))
-        .collect()
+///
+/// Fails if a compound instruction can't be encoded; see [`Instruction::Compound`].
+pub fn assemble(instructions: &[Instruction], constants: Constants) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(3 * instructions.len());
+
+    for instruction in instructions {
+        bytes.extend(assemble_instruction(instruction, constants)?);
+    }
+
+    Ok(bytes)
 }
 
 /// Assembles the given instruction, converting it into its three-byte data representation.
-fn assemble_instruction(instruction: &Instruction, c: Constants) -> [u8; 3] {
-    let o = c.opcode;
+fn assemble_instruction(instruction: &Instruction, c: Constants) -> Result<[u8; 3]> {
     let bo = c.byte_order;
 
     let [op, a, b] = match instruction {
+        Instruction::Compound(operations) => assemble_compound(operations, c)?,
+        operation => assemble_operation(operation, c)?,
+    };
+
+    let mut data = [0; 3];
+    data[bo.op as usize] = op;
+    data[bo.a as usize] = a;
+    data[bo.b as usize] = b;
+
+    Ok(data)
+}
+
+/// Encodes a single operation as its opcode and operand bytes.
+fn assemble_operation(operation: &Instruction, c: Constants) -> Result<[u8; 3]> {
+    let o = c.opcode;
+
+    let bytes = match operation {
         Instruction::IMM(register, value) => [o.IMM, register.encode(c), *value],
         Instruction::ADD(dest, operand) => [o.ADD, dest.encode(c), operand.encode(c)],
         Instruction::STK(pop, push) => [o.STK, pop.encode(c), push.encode(c)],
@@ -25,14 +48,54 @@ fn assemble_instruction(instruction: &Instruction, c: Constants) -> [u8; 3] {
         Instruction::CMP(a, b) => [o.CMP, a.encode(c), b.encode(c)],
         Instruction::JMP(condition, register) => [o.JMP, condition.encode(c), register.encode(c)],
         Instruction::SYS(syscall, register) => [o.SYS, *syscall, register.encode(c)],
+        Instruction::Compound(_) => bail!("Compound instructions can't be nested"),
     };
 
-    let mut data = [0; 3];
-    data[bo.op as usize] = op;
-    data[bo.a as usize] = a;
-    data[bo.b as usize] = b;
+    Ok(bytes)
+}
+
+/// Encodes the operations of a compound instruction as their combined opcode and shared operand
+/// bytes.
+///
+/// The operations must have distinct opcodes, follow the constants' opcode order, and encode the
+/// same operand bytes.
+fn assemble_compound(operations: &[Instruction], c: Constants) -> Result<[u8; 3]> {
+    let positions = operations
+        .iter()
+        .map(|operation| {
+            let opcode = operation.opcode();
+            c.opcode.order.iter().position(|o| Some(*o) == opcode)
+        })
+        .collect::<Option<Vec<_>>>();
+
+    ensure!(
+        positions.is_some_and(|positions| positions.windows(2).all(|pair| pair[0] < pair[1])),
+        "The operations of a compound instruction must have distinct opcodes in the order {:?}",
+        c.opcode.order
+    );
 
-    data
+    let mut combined: Option<[u8; 3]> = None;
+
+    for operation in operations {
+        let [op, a, b] = assemble_operation(operation, c)?;
+
+        combined = match combined {
+            None => Some([op, a, b]),
+            Some([ops, a_all, b_all]) => {
+                ensure!(
+                    (a, b) == (a_all, b_all),
+                    "The operations of a compound instruction must share operand bytes, but \
+                     `{operation}` encodes {a:#04x} {b:#04x} instead of {a_all:#04x} {b_all:#04x}"
+                );
+                Some([ops | op, a, b])
+            }
+        };
+    }
+
+    match combined {
+        Some(bytes) if operations.len() >= 2 => Ok(bytes),
+        _ => bail!("A compound instruction must have at least two operations"),
+    }
 }
 
 #[cfg(test)]
@@ -44,7 +107,7 @@ mod tests {
     fn test_assemble_imm() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::IMM(Reg::C, 0x69), consts),
+            assemble_instruction(&Instruction::IMM(Reg::C, 0x69), consts).unwrap(),
             [consts.opcode.IMM, consts.register.C, 0x69]
         )
     }
@@ -53,7 +116,7 @@ mod tests {
     fn test_assemble_add() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::ADD(Reg::B, Reg::S), consts),
+            assemble_instruction(&Instruction::ADD(Reg::B, Reg::S), consts).unwrap(),
             [consts.opcode.ADD, consts.register.B, consts.register.S]
         )
     }
@@ -62,7 +125,7 @@ mod tests {
     fn test_assemble_stk() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::STK(Some(Reg::C), Some(Reg::I)), consts),
+            assemble_instruction(&Instruction::STK(Some(Reg::C), Some(Reg::I)), consts).unwrap(),
             [consts.opcode.STK, consts.register.C, consts.register.I]
         )
     }
@@ -71,7 +134,7 @@ mod tests {
     fn test_assemble_stk_none_operand() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::STK(Some(Reg::C), None), consts),
+            assemble_instruction(&Instruction::STK(Some(Reg::C), None), consts).unwrap(),
             [consts.opcode.STK, consts.register.C, 0]
         )
     }
@@ -80,7 +143,7 @@ mod tests {
     fn test_assemble_stk_none_operands() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::STK(None, None), consts),
+            assemble_instruction(&Instruction::STK(None, None), consts).unwrap(),
             [consts.opcode.STK, 0, 0]
         )
     }
@@ -89,7 +152,7 @@ mod tests {
     fn test_assemble_stm() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::STM(Reg::C, Reg::D), consts),
+            assemble_instruction(&Instruction::STM(Reg::C, Reg::D), consts).unwrap(),
             [consts.opcode.STM, consts.register.C, consts.register.D]
         )
     }
@@ -98,7 +161,7 @@ mod tests {
     fn test_assemble_ldm() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::LDM(Reg::B, Reg::C), consts),
+            assemble_instruction(&Instruction::LDM(Reg::B, Reg::C), consts).unwrap(),
             [consts.opcode.LDM, consts.register.B, consts.register.C]
         );
     }
@@ -107,7 +170,7 @@ mod tests {
     fn test_assemble_cmp() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::CMP(Reg::C, Reg::D), consts),
+            assemble_instruction(&Instruction::CMP(Reg::C, Reg::D), consts).unwrap(),
             [consts.opcode.CMP, consts.register.C, consts.register.D]
         )
     }
@@ -116,7 +179,8 @@ mod tests {
     fn test_assemble_jmp() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::JMP("LG".try_into().unwrap(), Reg::D), consts),
+            assemble_instruction(&Instruction::JMP("LG".try_into().unwrap(), Reg::D), consts)
+                .unwrap(),
             [
                 consts.opcode.JMP,
                 consts.flag.L | consts.flag.G,
@@ -132,7 +196,8 @@ mod tests {
             assemble_instruction(
                 &Instruction::SYS(consts.syscall.WRITE, Some(Reg::D)),
                 consts
-            ),
+            )
+            .unwrap(),
             [consts.opcode.SYS, consts.syscall.WRITE, consts.register.D]
         )
     }
@@ -141,8 +206,60 @@ mod tests {
     fn test_assemble_sys_none_operand() {
         let consts = Constants::default();
         assert_eq!(
-            assemble_instruction(&Instruction::SYS(consts.syscall.EXIT, None), consts),
+            assemble_instruction(&Instruction::SYS(consts.syscall.EXIT, None), consts).unwrap(),
             [consts.opcode.SYS, consts.syscall.EXIT, 0]
         )
     }
+
+    #[test]
+    fn test_assemble_compound() {
+        let consts = Constants::default();
+        assert_eq!(
+            assemble_instruction(
+                &Instruction::Compound(vec![
+                    Instruction::IMM(Reg::A, consts.register.B),
+                    Instruction::ADD(Reg::A, Reg::B),
+                ]),
+                consts
+            )
+            .unwrap(),
+            [
+                consts.opcode.IMM | consts.opcode.ADD,
+                consts.register.A,
+                consts.register.B
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_invalid_compound() {
+        let consts = Constants::default();
+
+        // operands differ
+        assert!(assemble_instruction(
+            &Instruction::Compound(vec![
+                Instruction::IMM(Reg::A, 0x42),
+                Instruction::ADD(Reg::A, Reg::B),
+            ]),
+            consts
+        )
+        .is_err());
+
+        // out of order
+        assert!(assemble_instruction(
+            &Instruction::Compound(vec![
+                Instruction::ADD(Reg::A, Reg::B),
+                Instruction::IMM(Reg::A, consts.register.B),
+            ]),
+            consts
+        )
+        .is_err());
+
+        // too few operations
+        assert!(assemble_instruction(
+            &Instruction::Compound(vec![Instruction::ADD(Reg::A, Reg::B)]),
+            consts
+        )
+        .is_err());
+    }
 }
@@ -4,4 +4,4 @@ mod assemble;
 mod parse;
 
 pub use assemble::assemble;
-pub use parse::parse_asm_file;
+pub use parse::{instruction_lines, parse_asm_file};
@@ -2,9 +2,11 @@ use anyhow::{anyhow, Result};
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{digit1, hex_digit1, line_ending, multispace0, space0, space1},
-    combinator::{all_consuming, eof, value},
-    multi::{many0, many1},
+    character::complete::{
+        digit1, hex_digit1, line_ending, multispace0, not_line_ending, space0, space1,
+    },
+    combinator::{all_consuming, eof, opt, value},
+    multi::{many0, many1, separated_list1},
     sequence::{delimited, preceded, separated_pair, terminated, tuple},
     IResult,
 };
@@ -20,6 +22,16 @@ pub fn parse_asm_file(asm: String) -> Result<Vec<Instruction>> {
     Ok(instructions)
 }
 
+/// The 1-based line number of each instruction in a file of Yan85 assembly, which has one
+/// instruction on every line that isn't blank.
+pub fn instruction_lines(asm: &str) -> Vec<usize> {
+    asm.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
 /// Parses a string with Yan85 assembly instructions with one instruction per line.
 fn parse_asm_instructions(asm: &str) -> IResult<&str, Vec<Instruction>> {
     delimited(
@@ -29,8 +41,36 @@ fn parse_asm_instructions(asm: &str) -> IResult<&str, Vec<Instruction>> {
     )(asm)
 }
 
-/// Parses a single Yan85 assembly instruction.
+/// Parses a single Yan85 assembly instruction, which may be compound and may be followed by a
+/// comment.
+///
+/// Syntax: `<operation> [| <operation>]* [# <comment>]`
+///
+/// Examples:
+///
+/// - `ADD a b`
+/// - `IMM a = 0x02 | ADD a b`
+/// - `JMP d  # never taken`
 fn parse_asm_instruction(asm: &str) -> IResult<&str, Instruction> {
+    let (remaining, mut operations) = terminated(
+        separated_list1(delimited(space0, tag("|"), space0), parse_operation),
+        tuple((
+            space0,
+            opt(preceded(tag("#"), not_line_ending)),
+            alt((line_ending, eof)),
+        )),
+    )(asm)?;
+
+    let instruction = match operations.len() {
+        1 => operations.remove(0),
+        _ => Instruction::Compound(operations),
+    };
+
+    Ok((remaining, instruction))
+}
+
+/// Parses a single Yan85 operation, i.e., an instruction with one opcode.
+fn parse_operation(asm: &str) -> IResult<&str, Instruction> {
     alt((
         parse_imm, parse_add, parse_stk, parse_stm, parse_ldm, parse_cmp, parse_jmp, parse_sys,
     ))(asm)
@@ -45,10 +85,9 @@ fn parse_asm_instruction(asm: &str) -> IResult<&str, Instruction> {
 /// - `IMM i = 0x42`
 /// - `IMM a = 42`
 fn parse_imm(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (reg, imm)) = delimited(
+    let (remaining, (reg, imm)) = preceded(
         tuple((tag("IMM"), space1)),
         separated_pair(parse_register, parse_assignment, parse_int_literal),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::IMM(reg, imm)))
@@ -63,10 +102,9 @@ fn parse_imm(asm: &str) -> IResult<&str, Instruction> {
 /// - `ADD a b`
 /// - `ADD b c`
 fn parse_add(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (a, b)) = delimited(
+    let (remaining, (a, b)) = preceded(
         tuple((tag("ADD"), space1)),
         separated_pair(parse_register, space1, parse_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::ADD(a, b)))
@@ -82,10 +120,9 @@ fn parse_add(asm: &str) -> IResult<&str, Instruction> {
 /// - `STK a NONE`
 /// - `STK NONE b`
 fn parse_stk(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (a, b)) = delimited(
+    let (remaining, (a, b)) = preceded(
         tuple((tag("STK"), space1)),
         separated_pair(parse_optional_register, space1, parse_optional_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::STK(a, b)))
@@ -100,10 +137,9 @@ fn parse_stk(asm: &str) -> IResult<&str, Instruction> {
 /// - `STM *a = b`
 /// - `STM *b = a`
 fn parse_stm(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (a, b)) = delimited(
+    let (remaining, (a, b)) = preceded(
         tuple((tag("STM"), space1)),
         separated_pair(parse_deref_register, parse_assignment, parse_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::STM(a, b)))
@@ -118,10 +154,9 @@ fn parse_stm(asm: &str) -> IResult<&str, Instruction> {
 /// - `LDM b = *a`
 /// - `LDM a = *b`
 fn parse_ldm(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (a, b)) = delimited(
+    let (remaining, (a, b)) = preceded(
         tuple((tag("LDM"), space1)),
         separated_pair(parse_register, parse_assignment, parse_deref_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::LDM(a, b)))
@@ -136,10 +171,9 @@ fn parse_ldm(asm: &str) -> IResult<&str, Instruction> {
 /// - `CMP a b`
 /// - `CMP c d`
 fn parse_cmp(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (a, b)) = delimited(
+    let (remaining, (a, b)) = preceded(
         tuple((tag("CMP"), space1)),
         separated_pair(parse_register, space1, parse_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::CMP(a, b)))
@@ -147,25 +181,29 @@ fn parse_cmp(asm: &str) -> IResult<&str, Instruction> {
 
 /// Parses a `JMP` (jump) instruction.
 ///
-/// Syntax: `JMP [LGENZ]+ <reg>`
+/// Syntax: `JMP [LGENZ]* <reg>`
 ///
 /// Examples:
 ///
 /// - `JMP LE d`
 /// - `JMP Z d`
+/// - `JMP d` (empty condition: taken unconditionally only under `jump: reference`, never taken by
+///   default)
 fn parse_jmp(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (condition, reg)) = delimited(
+    let (remaining, (condition, reg)) = preceded(
         tuple((tag("JMP"), space1)),
-        separated_pair(
+        tuple((
             // note that this allows repeated flag letters, e.g. "LZL"
-            many1(alt((tag("L"), tag("G"), tag("E"), tag("N"), tag("Z")))),
-            space1,
+            opt(terminated(
+                many1(alt((tag("L"), tag("G"), tag("E"), tag("N"), tag("Z")))),
+                space1,
+            )),
             parse_register,
-        ),
-        tuple((space0, alt((line_ending, eof)))),
+        )),
     )(asm)?;
 
     let condition = condition
+        .unwrap_or_default()
         .join("")
         .as_str()
         .try_into()
@@ -183,10 +221,9 @@ fn parse_jmp(asm: &str) -> IResult<&str, Instruction> {
 /// - `SYS 0x20 d`
 /// - `SYS 0x2 d`
 fn parse_sys(asm: &str) -> IResult<&str, Instruction> {
-    let (remaining, (syscall, reg)) = delimited(
+    let (remaining, (syscall, reg)) = preceded(
         tuple((tag("SYS"), space1)),
         separated_pair(parse_int_literal, space1, parse_optional_register),
-        tuple((space0, alt((line_ending, eof)))),
     )(asm)?;
 
     Ok((remaining, Instruction::SYS(syscall, reg)))
@@ -415,6 +452,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jmp_empty_condition() {
+        let (_, instruction) = parse_asm_instruction("JMP d").unwrap();
+        assert_eq!(instruction, Instruction::JMP(Flags::default(), Register::D));
+    }
+
+    #[test]
+    fn test_comment() {
+        let (_, instruction) = parse_asm_instruction("JMP d  # never taken").unwrap();
+        assert_eq!(instruction, Instruction::JMP(Flags::default(), Register::D));
+    }
+
+    #[test]
+    fn test_compound() {
+        let (_, instruction) = parse_asm_instruction("IMM a = 0x02 | ADD a b").unwrap();
+        assert_eq!(
+            instruction,
+            Instruction::Compound(vec![
+                Instruction::IMM(Register::A, 0x02),
+                Instruction::ADD(Register::A, Register::B),
+            ])
+        );
+    }
+
     #[test]
     fn test_jmp_misordered_operands() {
         assert!(parse_asm_instruction("JMP d 8").is_err());
@@ -437,6 +498,14 @@ mod tests {
         assert!(parse_asm_instruction("SYS d 0x20").is_err());
     }
 
+    #[test]
+    fn test_instruction_lines() {
+        let asm = "\nIMM a = 0x1\n  \r\nADD a b\r\nSYS 0x20 a\n\n";
+
+        assert_eq!(parse_asm_file(asm.to_string()).unwrap().len(), 3);
+        assert_eq!(instruction_lines(asm), vec![2, 4, 5]);
+    }
+
     #[test]
     fn test_int_literal_decimal() {
         let (_, literal) = parse_int_literal("5").unwrap();
@@ -0,0 +1,126 @@
+use std::{thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// How the `SLEEP` syscall passes time.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SleepMode {
+    /// Sleep for the requested time.
+    #[default]
+    Real,
+    /// Return immediately, only advancing the simulated time.
+    Virtual,
+    /// Sleep for the requested time multiplied by `factor`, and for at most `max`.
+    Scaled {
+        /// The factor that requested times are multiplied by.
+        factor: f64,
+        /// The longest time to sleep for, if any.
+        max: Option<Duration>,
+    },
+}
+
+impl SleepMode {
+    /// The real time to sleep for when `requested` is asked for.
+    pub fn real_duration(&self, requested: Duration) -> Duration {
+        match *self {
+            SleepMode::Real => requested,
+            SleepMode::Virtual => Duration::ZERO,
+            SleepMode::Scaled { factor, max } => {
+                let scaled = requested.mul_f64(factor);
+                max.map_or(scaled, |max| scaled.min(max))
+            }
+        }
+    }
+}
+
+/// The time that has passed for an emulated program, which is advanced by the `SLEEP` syscall.
+///
+/// Simulated time always advances by the requested amount, however much real time passes.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clock {
+    /// How sleeping passes time.
+    #[serde(skip)]
+    mode: SleepMode,
+    /// The total time requested by `SLEEP` syscalls.
+    elapsed: Duration,
+    /// The number of `SLEEP` syscalls performed.
+    sleeps: u64,
+}
+
+impl Clock {
+    /// Constructs a clock at time zero that sleeps according to `mode`.
+    pub fn new(mode: SleepMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// How sleeping passes time.
+    pub fn mode(&self) -> SleepMode {
+        self.mode
+    }
+
+    /// Changes how sleeping passes time, keeping the simulated time.
+    pub fn set_mode(&mut self, mode: SleepMode) {
+        self.mode = mode;
+    }
+
+    /// The total time requested by `SLEEP` syscalls.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The number of `SLEEP` syscalls performed.
+    pub fn sleeps(&self) -> u64 {
+        self.sleeps
+    }
+
+    /// Sleeps for `requested` simulated time.
+    pub fn sleep(&mut self, requested: Duration) {
+        self.elapsed += requested;
+        self.sleeps += 1;
+
+        let duration = self.mode.real_duration(requested);
+        if !duration.is_zero() {
+            thread::sleep(duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_virtual_sleep() {
+        let mut clock = Clock::new(SleepMode::Virtual);
+
+        let start = Instant::now();
+        clock.sleep(Duration::from_secs(100));
+        clock.sleep(Duration::from_secs(20));
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(clock.elapsed(), Duration::from_secs(120));
+        assert_eq!(clock.sleeps(), 2);
+    }
+
+    #[test]
+    fn test_scaled_duration() {
+        let mode = SleepMode::Scaled {
+            factor: 0.5,
+            max: Some(Duration::from_secs(3)),
+        };
+
+        assert_eq!(
+            mode.real_duration(Duration::from_secs(4)),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            mode.real_duration(Duration::from_secs(10)),
+            Duration::from_secs(3)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    disasm::disassemble_instruction,
+    emulator::Effect,
+    yan85::{code::Code, constants::Constants, instruction::Instruction},
+};
+
+/// How often a branching instruction did and didn't jump.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BranchCounts {
+    /// The number of executions that jumped.
+    pub taken: u64,
+    /// The number of executions that fell through to the next instruction.
+    pub not_taken: u64,
+}
+
+/// Per-instruction execution counts collected over one or more runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// The number of times each instruction index was executed.
+    hits: [u64; 256],
+    /// Branch counts for each instruction index at which a `JMP` was executed.
+    branches: BTreeMap<u8, BranchCounts>,
+    /// The instruction most recently executed at each index, which may differ from the original
+    /// code if the program modified itself.
+    instructions: BTreeMap<u8, Instruction>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            hits: [0; 256],
+            branches: BTreeMap::new(),
+            instructions: BTreeMap::new(),
+        }
+    }
+}
+
+impl Coverage {
+    /// Records that `instruction` was executed at `index` with the side effects `effects`.
+    ///
+    /// Instructions containing a `JMP` count as taken if any of their jumps were taken, even if
+    /// the target was the following index.
+    pub fn record(&mut self, index: u8, instruction: Option<&Instruction>, effects: &[Effect]) {
+        self.hits[index as usize] += 1;
+
+        let Some(instruction) = instruction else {
+            return;
+        };
+
+        if instruction
+            .operations()
+            .iter()
+            .any(|operation| matches!(operation, Instruction::JMP(..)))
+        {
+            let counts = self.branches.entry(index).or_default();
+            if effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::Jump { taken: true }))
+            {
+                counts.taken += 1;
+            } else {
+                counts.not_taken += 1;
+            }
+        }
+
+        self.instructions.insert(index, instruction.clone());
+    }
+
+    /// The number of times the instruction at `index` was executed.
+    pub fn hits(&self, index: u8) -> u64 {
+        self.hits[index as usize]
+    }
+
+    /// The branch counts for the instruction at `index`, if a `JMP` was executed there.
+    pub fn branch(&self, index: u8) -> Option<BranchCounts> {
+        self.branches.get(&index).copied()
+    }
+
+    /// The number of distinct instruction indices that were executed.
+    pub fn covered(&self) -> usize {
+        self.hits.iter().filter(|&&hits| hits > 0).count()
+    }
+
+    /// Writes a disassembly of `code` annotated with execution and branch counts.
+    ///
+    /// The listing runs up to the last instruction that is either nonzero or was executed.
+    /// Instructions are shown as they were last executed, and unexecuted ones are marked with
+    /// `#####`.
+    pub fn write_listing(
+        &self,
+        code: &Code,
+        constants: Constants,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        let Some(last) = (0..=u8::MAX)
+            .rev()
+            .find(|&index| self.hits(index) > 0 || code.get_instruction(index) != [0; 3])
+        else {
+            return Ok(());
+        };
+
+        for index in 0..=last {
+            let hits = match self.hits(index) {
+                0 => "#####".to_string(),
+                hits => hits.to_string(),
+            };
+
+            let jump = constants.semantics.jump;
+            let instruction = match self.instructions.get(&index) {
+                Some(instruction) => instruction.listing(jump).to_string(),
+                None => match disassemble_instruction(code.get_instruction(index), constants) {
+                    Ok(instruction) => instruction.listing(jump).to_string(),
+                    Err(_) => format!("(invalid: {:02x?})", code.get_instruction(index)),
+                },
+            };
+
+            write!(writer, "{hits:>9}  {index:#04x}  {instruction}")?;
+
+            if let Some(BranchCounts { taken, not_taken }) = self.branch(index) {
+                write!(writer, "  [taken {taken}, not taken {not_taken}]")?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the counts as an lcov tracefile for the assembly source at `source_path`, where
+    /// instruction `i` is on line `lines[i]`.
+    ///
+    /// Each `JMP` is reported as a branch block with a taken and a not-taken branch.
+    pub fn write_lcov(
+        &self,
+        source_path: &Path,
+        lines: &[usize],
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        writeln!(writer, "TN:")?;
+        writeln!(writer, "SF:{}", source_path.display())?;
+
+        let instructions = lines.iter().zip(0..=u8::MAX);
+
+        let (mut found, mut hit) = (0, 0);
+        for (line, index) in instructions.clone() {
+            let Some(BranchCounts { taken, not_taken }) = self.branch(index) else {
+                continue;
+            };
+
+            writeln!(writer, "BRDA:{line},0,0,{taken}")?;
+            writeln!(writer, "BRDA:{line},0,1,{not_taken}")?;
+            found += 2;
+            hit += u64::from(taken > 0) + u64::from(not_taken > 0);
+        }
+        writeln!(writer, "BRF:{found}")?;
+        writeln!(writer, "BRH:{hit}")?;
+
+        let (mut found, mut hit) = (0, 0);
+        for (line, index) in instructions {
+            let hits = self.hits(index);
+            writeln!(writer, "DA:{line},{hits}")?;
+            found += 1;
+            hit += u64::from(hits > 0);
+        }
+        writeln!(writer, "LF:{found}")?;
+        writeln!(writer, "LH:{hit}")?;
+
+        writeln!(writer, "end_of_record")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yan85::{flags::Flags, register::Register};
+
+    #[test]
+    fn test_record_branches() {
+        let jump = Instruction::JMP(Flags::default(), Register::D);
+        let mut coverage = Coverage::default();
+
+        let taken = [Effect::Jump { taken: true }];
+        let not_taken = [Effect::Jump { taken: false }];
+
+        coverage.record(0, Some(&Instruction::IMM(Register::D, 5)), &[]);
+        coverage.record(1, Some(&jump), &taken);
+        coverage.record(1, Some(&jump), &not_taken);
+        coverage.record(1, Some(&jump), &taken);
+
+        assert_eq!(coverage.hits(0), 1);
+        assert_eq!(coverage.hits(1), 3);
+        assert_eq!(coverage.hits(2), 0);
+        assert_eq!(coverage.covered(), 2);
+
+        assert_eq!(coverage.branch(0), None);
+        assert_eq!(
+            coverage.branch(1),
+            Some(BranchCounts {
+                taken: 2,
+                not_taken: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_lcov() {
+        let mut coverage = Coverage::default();
+        coverage.record(
+            1,
+            Some(&Instruction::JMP(Flags::default(), Register::D)),
+            &[Effect::Jump { taken: false }],
+        );
+
+        let mut lcov = vec![];
+        coverage
+            .write_lcov(Path::new("check.asm"), &[1, 3], &mut lcov)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(lcov).unwrap(),
+            "TN:\nSF:check.asm\nBRDA:3,0,0,0\nBRDA:3,0,1,1\nBRF:2\nBRH:1\n\
+             DA:1,0\nDA:3,1\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    emulator::{Effect, Emulator, StepOutcome},
+    solve::{default_byte, preferred_bytes},
+    syscall_handler::{VirtualSyscallHandler, STDIN},
+    yan85::syscall::Syscall,
+};
+
+/// How far a run with a candidate input got. Runs compare by matching comparisons first and then
+/// by steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Progress {
+    /// The number of `CMP` operations whose operands were equal.
+    pub matches: u64,
+    /// The number of instructions executed.
+    pub steps: u64,
+}
+
+/// Limits on a brute-force search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrackOptions {
+    /// The number of input bytes to search for, or `None` to use as many as the program reads.
+    pub length: Option<usize>,
+    /// The number of instructions that each run may execute.
+    pub max_steps: u64,
+    /// The number of passes over the input bytes.
+    pub max_rounds: usize,
+}
+
+impl Default for CrackOptions {
+    fn default() -> Self {
+        Self {
+            length: None,
+            max_steps: 100_000,
+            max_rounds: 8,
+        }
+    }
+}
+
+/// The outcome of a brute-force search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrackReport {
+    /// The best input found.
+    pub key: Vec<u8>,
+    /// How far a run with the key got.
+    pub progress: Progress,
+    /// The exit code of a run with the key, if it exited.
+    pub exit_code: Option<u8>,
+    /// Everything a run with the key wrote to standard output.
+    pub output: Vec<u8>,
+    /// The number of runs performed.
+    pub runs: u64,
+}
+
+/// The result of running the program once.
+struct Run {
+    /// How far it got.
+    progress: Progress,
+    /// The number of bytes it read from standard input.
+    input_read: usize,
+    /// Its exit code, if it exited.
+    exit_code: Option<u8>,
+    /// The emulator after the run.
+    emulator: Emulator<VirtualSyscallHandler>,
+}
+
+/// Recovers the input that the program loaded in `emulator` checks for, one byte at a time.
+///
+/// Every `CMP` is logged, and changes to a single input byte are kept if they make more
+/// comparisons find equal operands or, failing that, make the program run further. This cracks
+/// programs that compare their input one character at a time in a few thousand runs, but can't
+/// get past checks that combine many input bytes before comparing.
+///
+/// Each run starts from a clone of `emulator` with the candidate appended to its standard input.
+pub fn crack(emulator: &Emulator<VirtualSyscallHandler>, options: CrackOptions) -> CrackReport {
+    let mut runs = 0;
+    let mut run = |input: &[u8]| {
+        runs += 1;
+        run_once(emulator, input, options.max_steps)
+    };
+
+    let length = options
+        .length
+        .unwrap_or_else(|| run(&[default_byte(); 256]).input_read);
+    let mut key = vec![default_byte(); length];
+    let mut best = run(&key).progress;
+
+    for _ in 0..options.max_rounds {
+        let mut improved = false;
+
+        for offset in 0..length {
+            let mut candidate = key.clone();
+
+            for &byte in preferred_bytes() {
+                candidate[offset] = byte;
+
+                let progress = run(&candidate).progress;
+                if progress > best {
+                    best = progress;
+                    key[offset] = byte;
+                    improved = true;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    let last = run(&key);
+    CrackReport {
+        key,
+        progress: last.progress,
+        exit_code: last.exit_code,
+        output: last.emulator.handler().stdout().to_vec(),
+        runs,
+    }
+}
+
+/// Runs a clone of `emulator` with `input` on standard input for up to `max_steps` instructions.
+/// Errors end the run like exits do.
+fn run_once(emulator: &Emulator<VirtualSyscallHandler>, input: &[u8], max_steps: u64) -> Run {
+    let mut emulator = emulator.clone();
+    emulator.handler_mut().feed_stdin(input);
+
+    let mut progress = Progress::default();
+    let mut input_read = 0;
+    let mut exit_code = None;
+
+    while progress.steps < max_steps {
+        let outcome = emulator.step();
+        progress.steps += 1;
+
+        for effect in emulator.effects() {
+            match *effect {
+                Effect::Comparison { a, b } if a == b => progress.matches += 1,
+                Effect::Syscall {
+                    syscall: Syscall::ReadCode | Syscall::ReadMemory,
+                    arguments: [STDIN, ..],
+                    return_value: Some(read),
+                } => input_read += read as usize,
+                _ => {}
+            }
+        }
+
+        match outcome {
+            Ok(StepOutcome::Continued(_)) => {}
+            Ok(StepOutcome::Exited(code)) => {
+                exit_code = Some(code);
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+
+    Run {
+        progress,
+        input_read,
+        exit_code,
+        emulator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yan85::{
+        constants::Constants, flags::Flags, instruction::Instruction, memory::Memory,
+        register::Register,
+    };
+
+    #[test]
+    fn test_crack() {
+        let consts = Constants::default();
+        let not_equal = Flags {
+            not_equal: true,
+            ..Default::default()
+        };
+
+        // Checks "key" one character at a time, each transformed, exiting with 1 on a mismatch.
+        let mut instructions = vec![
+            Instruction::IMM(Register::A, 0),
+            Instruction::IMM(Register::B, 0x10),
+            Instruction::IMM(Register::C, 3),
+            Instruction::SYS(consts.syscall.READ_MEMORY, None),
+            Instruction::IMM(Register::D, 0x40),
+        ];
+        for (address, &expected) in (0x10..).zip(b"key") {
+            instructions.extend([
+                Instruction::IMM(Register::B, address),
+                Instruction::LDM(Register::A, Register::B),
+                Instruction::ADD(Register::A, Register::A),
+                Instruction::IMM(Register::C, expected.wrapping_mul(2)),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::JMP(not_equal, Register::D),
+            ]);
+        }
+        instructions.extend([
+            Instruction::IMM(Register::A, 0),
+            Instruction::SYS(consts.syscall.EXIT, None),
+        ]);
+        instructions.resize(0x40, Instruction::IMM(Register::A, 0));
+        instructions.extend([
+            Instruction::IMM(Register::A, 1),
+            Instruction::SYS(consts.syscall.EXIT, None),
+        ]);
+
+        let emulator = Emulator::from_instructions_with_handler(
+            consts,
+            instructions,
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        let report = crack(&emulator, CrackOptions::default());
+        assert_eq!(report.key, b"key");
+        assert_eq!(report.exit_code, Some(0));
+        assert_eq!(report.progress.matches, 3);
+    }
+}
@@ -0,0 +1,781 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use colored::Colorize;
+
+use crate::{
+    disasm::disassemble_instruction,
+    emulator::{Emulator, StepOutcome},
+    syscall_handler::SyscallHandler,
+    watch::{WatchHit, Watchpoint},
+    yan85::{
+        constants::StackLayout, instruction::Instruction, register::Register, registers::Registers,
+    },
+};
+
+/// The prompt printed before reading each command.
+const PROMPT: &str = "(gyan) ";
+
+/// Help text listing the supported commands.
+const HELP: &str = "\
+break <index>              set a breakpoint at an instruction index (alias: b)
+delete <index>             remove a breakpoint (alias: d)
+watch <spec>               stop when a watchpoint is triggered (alias: w); <spec> is one of
+                             memory:<address>[-<end>][:r|w|rw]
+                             stack:<address>
+                             register:<register>=<value>
+unwatch <number>           remove a watchpoint
+step [count]               execute one or more instructions (alias: s)
+next                       execute until the following instruction, stepping over loops (alias: n)
+continue                   execute until a breakpoint, watchpoint, exit, or the step limit
+                           (alias: c)
+reverse-step [count]       undo one or more instructions or sets (alias: rs)
+reverse-continue           undo instructions until a breakpoint, watchpoint, or the start
+                           (alias: rc)
+info registers             show register values (alias: i r)
+info breakpoints           list breakpoints (alias: i b)
+info watchpoints           list watchpoints (alias: i w)
+list [index] [count]       disassemble instructions (alias: l)
+memory [address] [length]  hexdump memory (alias: x)
+stack [address] [length]   hexdump the stack; shows up to the stack pointer by default
+set <register> <value>     assign a register
+set memory <address> <value>...
+                           write bytes to memory
+set stack <address> <value>...
+                           write bytes to the stack
+help                       show this message (alias: h)
+quit                       exit the debugger (alias: q)";
+
+/// A debugger command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Set a breakpoint at an instruction index.
+    Break(u8),
+    /// Remove the breakpoint at an instruction index.
+    Delete(u8),
+    /// Add a watchpoint.
+    Watch(Watchpoint),
+    /// Remove the watchpoint with the given number.
+    Unwatch(usize),
+    /// Execute a number of instructions.
+    Step(u32),
+    /// Execute until the instruction after the current one is reached.
+    Next,
+    /// Execute until a breakpoint or watchpoint is hit or the program exits.
+    Continue,
+    /// Undo a number of instructions.
+    ReverseStep(u32),
+    /// Undo instructions until a breakpoint or watchpoint is reached or there is nothing left to
+    /// undo.
+    ReverseContinue,
+    /// Show register values.
+    InfoRegisters,
+    /// List breakpoints.
+    InfoBreakpoints,
+    /// List watchpoints.
+    InfoWatchpoints,
+    /// Disassemble `count` instructions starting at an index, defaulting to the current one.
+    List(Option<u8>, u8),
+    /// Hexdump `length` bytes of memory starting at an address.
+    Memory(u8, u16),
+    /// Hexdump `length` bytes of the stack starting at an address. Defaults to everything below
+    /// the stack pointer.
+    Stack(u8, Option<u16>),
+    /// Assign a value to a register.
+    SetRegister(Register, u8),
+    /// Write bytes to memory starting at an address.
+    SetMemory(u8, Vec<u8>),
+    /// Write bytes to the stack starting at an address.
+    SetStack(u8, Vec<u8>),
+    /// Show the help text.
+    Help,
+    /// Exit the debugger.
+    Quit,
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer.
+fn parse_int<T: TryFrom<u32>>(word: &str) -> Result<T> {
+    let value = match word.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => word.parse(),
+    }
+    .with_context(|| format!("Invalid number: {word:?}"))?;
+
+    T::try_from(value).map_err(|_| anyhow!("Number out of range: {word}"))
+}
+
+/// Parses an optional integer argument, falling back to `default`.
+fn parse_int_or<T: TryFrom<u32>>(word: Option<&&str>, default: T) -> Result<T> {
+    word.map_or(Ok(default), |word| parse_int(word))
+}
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        let command = match words.as_slice() {
+            ["break" | "b", index] => Command::Break(parse_int(index)?),
+            ["delete" | "d", index] => Command::Delete(parse_int(index)?),
+            ["watch" | "w", spec] => Command::Watch(spec.parse()?),
+            ["unwatch", number] => Command::Unwatch(parse_int(number)?),
+            ["step" | "s", rest @ ..] if rest.len() <= 1 => {
+                Command::Step(parse_int_or(rest.first(), 1)?)
+            }
+            ["next" | "n"] => Command::Next,
+            ["continue" | "c"] => Command::Continue,
+            ["reverse-step" | "rs", rest @ ..] if rest.len() <= 1 => {
+                Command::ReverseStep(parse_int_or(rest.first(), 1)?)
+            }
+            ["reverse-continue" | "rc"] => Command::ReverseContinue,
+            ["info" | "i", "registers" | "r"] => Command::InfoRegisters,
+            ["info" | "i", "breakpoints" | "b"] => Command::InfoBreakpoints,
+            ["info" | "i", "watchpoints" | "w"] => Command::InfoWatchpoints,
+            ["list" | "l", rest @ ..] if rest.len() <= 2 => Command::List(
+                rest.first().map(|index| parse_int(index)).transpose()?,
+                parse_int_or(rest.get(1), 8)?,
+            ),
+            ["memory" | "x", rest @ ..] if rest.len() <= 2 => Command::Memory(
+                parse_int_or(rest.first(), 0)?,
+                parse_int_or(rest.get(1), 256)?,
+            ),
+            ["stack", rest @ ..] if rest.len() <= 2 => Command::Stack(
+                parse_int_or(rest.first(), 0)?,
+                rest.get(1).map(|length| parse_int(length)).transpose()?,
+            ),
+            ["set", "memory", address, values @ ..] if !values.is_empty() => Command::SetMemory(
+                parse_int(address)?,
+                values.iter().map(|v| parse_int(v)).collect::<Result<_>>()?,
+            ),
+            ["set", "stack", address, values @ ..] if !values.is_empty() => Command::SetStack(
+                parse_int(address)?,
+                values.iter().map(|v| parse_int(v)).collect::<Result<_>>()?,
+            ),
+            ["set", register, value] => {
+                Command::SetRegister(Register::try_from(*register)?, parse_int(value)?)
+            }
+            ["help" | "h"] => Command::Help,
+            ["quit" | "q"] => Command::Quit,
+            _ => bail!("Unknown command: {line:?} (try \"help\")"),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Formats `bytes` as a hexdump with 16 bytes per line, labelling lines with addresses starting
+/// at `start`.
+fn hexdump(bytes: &[u8], start: u8) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let address = start as usize + 16 * i;
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            format!(
+                "{}: {:<47}  |{ascii}|\n",
+                format!("{address:#04x}").blue(),
+                hex.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// Returns the `length` bytes of `bytes` starting at `start`, truncated at the end.
+fn window(bytes: &[u8], start: u8, length: u16) -> &[u8] {
+    let end = (start as usize + length as usize).min(bytes.len());
+    &bytes[start as usize..end]
+}
+
+/// Limits on a debugging session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebuggerOptions {
+    /// The number of most recent steps, and `set` commands, that can be undone.
+    pub history_limit: usize,
+    /// The number of instructions that `continue` and `next` execute before stopping, so that an
+    /// infinite loop doesn't hang the session.
+    pub max_steps: u64,
+}
+
+impl Default for DebuggerOptions {
+    fn default() -> Self {
+        Self {
+            history_limit: 100_000,
+            max_steps: 10_000_000,
+        }
+    }
+}
+
+/// An interactive, gdb-like debugger for Yan85 programs.
+///
+/// Execution history is recorded so that instructions, and `set` commands, can be undone.
+/// Undoing doesn't affect the syscall handler, so input that was consumed stays consumed.
+pub struct Debugger<H: SyscallHandler> {
+    /// The emulator being debugged.
+    emulator: Emulator<H>,
+    /// Limits on the session.
+    options: DebuggerOptions,
+    /// Instruction indices at which execution stops.
+    breakpoints: BTreeSet<u8>,
+    /// Watchpoints that stop execution, by number.
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    /// The number to give the next watchpoint.
+    next_watchpoint: usize,
+}
+
+impl<H: SyscallHandler> Debugger<H> {
+    /// Constructs a debugger for the program loaded in `emulator`.
+    pub fn new(mut emulator: Emulator<H>, options: DebuggerOptions) -> Self {
+        emulator.record_history(Some(options.history_limit));
+
+        Self {
+            emulator,
+            options,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            next_watchpoint: 0,
+        }
+    }
+
+    /// The emulator being debugged.
+    pub fn emulator(&self) -> &Emulator<H> {
+        &self.emulator
+    }
+
+    /// Reads commands from `input` and executes them until `quit` or the end of input.
+    ///
+    /// An empty line repeats the previous command. Errors are reported to `output` and don't end
+    /// the session.
+    pub fn repl(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+        let mut previous = None;
+
+        self.show_location(&mut output)?;
+
+        loop {
+            write!(output, "{PROMPT}")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            let command = match (line.trim(), previous.take()) {
+                ("", Some(command)) => Ok(command),
+                ("", None) => continue,
+                (line, _) => line.parse::<Command>(),
+            };
+
+            match command {
+                Ok(Command::Quit) => return Ok(()),
+                Ok(command) => {
+                    if let Err(e) = self.execute(&command, &mut output) {
+                        writeln!(output, "{} {e}", "error:".red())?;
+                    }
+                    previous = Some(command);
+                }
+                Err(e) => writeln!(output, "{} {e}", "error:".red())?,
+            }
+        }
+    }
+
+    /// Executes a single command, writing any results to `output`.
+    pub fn execute(&mut self, command: &Command, mut output: impl Write) -> Result<()> {
+        match command {
+            Command::Break(index) => {
+                self.breakpoints.insert(*index);
+                writeln!(output, "Breakpoint set at {index:#04x}")?;
+            }
+            Command::Delete(index) => {
+                if !self.breakpoints.remove(index) {
+                    bail!("No breakpoint at {index:#04x}");
+                }
+            }
+            Command::Watch(watchpoint) => {
+                let number = self.next_watchpoint;
+                self.next_watchpoint += 1;
+                writeln!(output, "Watchpoint {number}: {watchpoint}")?;
+                self.watchpoints.insert(number, watchpoint.clone());
+            }
+            Command::Unwatch(number) => {
+                if self.watchpoints.remove(number).is_none() {
+                    bail!("No watchpoint {number}");
+                }
+            }
+            Command::Step(count) => {
+                for _ in 0..*count {
+                    if self.step(&mut output)? {
+                        break;
+                    }
+                }
+                self.show_location(&mut output)?;
+            }
+            Command::Next => {
+                let target = self.current_index().wrapping_add(1);
+                self.run_until(&mut output, |debugger| {
+                    debugger.current_index() == target || debugger.at_breakpoint()
+                })?;
+                self.show_location(&mut output)?;
+            }
+            Command::Continue => {
+                if self.run_until(&mut output, Self::at_breakpoint)? {
+                    writeln!(output, "Breakpoint at {:#04x}", self.current_index())?;
+                }
+                self.show_location(&mut output)?;
+            }
+            Command::ReverseStep(count) => {
+                for _ in 0..*count {
+                    if !self.emulator.step_back() {
+                        writeln!(output, "Reached the start of the recorded history")?;
+                        break;
+                    }
+                }
+                self.show_location(&mut output)?;
+            }
+            Command::ReverseContinue => {
+                loop {
+                    let after = *self.emulator.registers();
+                    if !self.emulator.step_back() {
+                        writeln!(output, "Reached the start of the recorded history")?;
+                        break;
+                    }
+
+                    let before = *self.emulator.registers();
+                    let hits =
+                        self.watch_hits(&before, &after, self.emulator.next_instruction().ok());
+                    if !hits.is_empty() {
+                        for hit in hits {
+                            writeln!(output, "Stopped before {hit}")?;
+                        }
+                        break;
+                    }
+
+                    if self.at_breakpoint() {
+                        writeln!(output, "Breakpoint at {:#04x}", self.current_index())?;
+                        break;
+                    }
+                }
+                self.show_location(&mut output)?;
+            }
+            Command::InfoRegisters => {
+                for register in Register::ALL {
+                    let value = self.emulator.registers()[register];
+                    writeln!(output, "{register}  {value:#04x}  {value}")?;
+                }
+            }
+            Command::InfoBreakpoints => {
+                if self.breakpoints.is_empty() {
+                    writeln!(output, "No breakpoints")?;
+                }
+                for index in &self.breakpoints {
+                    writeln!(output, "{index:#04x}")?;
+                }
+            }
+            Command::InfoWatchpoints => {
+                if self.watchpoints.is_empty() {
+                    writeln!(output, "No watchpoints")?;
+                }
+                for (number, watchpoint) in &self.watchpoints {
+                    writeln!(output, "{number}: {watchpoint}")?;
+                }
+            }
+            Command::List(start, count) => {
+                let start = start.unwrap_or_else(|| self.current_index());
+                for index in (start..=u8::MAX).take(*count as usize) {
+                    self.show_instruction(index, &mut output)?;
+                }
+            }
+            Command::Memory(start, length) => {
+                let bytes = window(&self.emulator.memory()[0..], *start, *length);
+                write!(output, "{}", hexdump(bytes, *start))?;
+            }
+            Command::Stack(start, length) => {
+                // The stack pointer points past the top of a separate stack, but at the top of
+                // one in memory
+                let stack_pointer = u16::from(self.emulator.registers()[Register::S]);
+                let (stack, top) = match self.emulator.constants().semantics.stack {
+                    StackLayout::Separate => (&self.emulator.stack()[0..], stack_pointer),
+                    StackLayout::Memory => (&self.emulator.memory()[0..], stack_pointer + 1),
+                };
+
+                let length = length.unwrap_or_else(|| top.saturating_sub(u16::from(*start)));
+                let bytes = window(stack, *start, length);
+                write!(output, "{}", hexdump(bytes, *start))?;
+            }
+            Command::SetRegister(register, value) => {
+                self.emulator
+                    .edit(|registers, _, _| registers[*register] = *value);
+            }
+            Command::SetMemory(start, values) => {
+                self.emulator.edit(|_, memory, _| {
+                    for (address, value) in (*start..=u8::MAX).zip(values) {
+                        memory[address] = *value;
+                    }
+                });
+            }
+            Command::SetStack(start, values) => {
+                let layout = self.emulator.constants().semantics.stack;
+                self.emulator.edit(|_, memory, stack| {
+                    for (address, value) in (*start..=u8::MAX).zip(values) {
+                        match layout {
+                            StackLayout::Separate => stack[address] = *value,
+                            StackLayout::Memory => memory[address] = *value,
+                        }
+                    }
+                });
+            }
+            Command::Help => writeln!(output, "{HELP}")?,
+            Command::Quit => {}
+        }
+
+        Ok(())
+    }
+
+    /// The index of the next instruction to execute.
+    fn current_index(&self) -> u8 {
+        self.emulator.registers()[Register::I]
+    }
+
+    /// Checks whether the next instruction to execute has a breakpoint.
+    fn at_breakpoint(&self) -> bool {
+        self.breakpoints.contains(&self.current_index())
+    }
+
+    /// Steps until the program exits, a watchpoint is triggered, `stop` holds, or the step limit
+    /// is reached, returning whether `stop` held.
+    fn run_until(&mut self, mut output: impl Write, stop: impl Fn(&Self) -> bool) -> Result<bool> {
+        for _ in 0..self.options.max_steps {
+            if self.step(&mut output)? {
+                return Ok(false);
+            }
+            if stop(self) {
+                return Ok(true);
+            }
+        }
+
+        writeln!(output, "Stopped after {} steps", self.options.max_steps)?;
+        Ok(false)
+    }
+
+    /// Steps through one instruction, returning whether the program exited or a watchpoint was
+    /// triggered.
+    fn step(&mut self, mut output: impl Write) -> Result<bool> {
+        let before = *self.emulator.registers();
+        let instruction = self.emulator.next_instruction().ok();
+
+        let outcome = self.emulator.step()?;
+
+        let hits = self.watch_hits(&before, self.emulator.registers(), instruction);
+        for hit in &hits {
+            writeln!(output, "Stopped after {hit}")?;
+        }
+
+        match outcome {
+            StepOutcome::Continued(_) => Ok(!hits.is_empty()),
+            StepOutcome::Exited(exit_code) => {
+                writeln!(output, "Program exited with code {exit_code}")?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Checks the watchpoints against the most recently executed or undone instruction, given the
+    /// registers around it.
+    fn watch_hits(
+        &self,
+        before: &Registers,
+        after: &Registers,
+        instruction: Option<Instruction>,
+    ) -> Vec<WatchHit> {
+        self.watchpoints
+            .iter()
+            .filter_map(|(&number, watchpoint)| {
+                let event = watchpoint.check(before, after, self.emulator.effects())?;
+                Some(WatchHit {
+                    watchpoint: number,
+                    index: before[Register::I],
+                    instruction: instruction.clone(),
+                    event,
+                })
+            })
+            .collect()
+    }
+
+    /// Shows the next instruction to execute, unless the program has exited.
+    fn show_location(&self, output: impl Write) -> Result<()> {
+        if self.emulator.exit_code().is_none() {
+            self.show_instruction(self.current_index(), output)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows the disassembly of the instruction at `index`, marking the current instruction and
+    /// breakpoints.
+    fn show_instruction(&self, index: u8, mut output: impl Write) -> Result<()> {
+        let marker = match (
+            index == self.current_index(),
+            self.breakpoints.contains(&index),
+        ) {
+            (true, _) => "=>".green(),
+            (false, true) => " *".red(),
+            (false, false) => "  ".normal(),
+        };
+
+        let bytes = self.emulator.code().get_instruction(index);
+        match disassemble_instruction(bytes, self.emulator.constants()) {
+            Ok(instruction) => {
+                let instruction = instruction.listing(self.emulator.constants().semantics.jump);
+                writeln!(output, "{marker} {index:#04x}: {instruction}")?
+            }
+            Err(e) => writeln!(output, "{marker} {index:#04x}: {} ({e})", "??".red())?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, instruction::Instruction, memory::Memory},
+    };
+
+    /// Constructs a debugger for a program that counts `a` up from 0 and then exits.
+    fn counting_debugger() -> Debugger<VirtualSyscallHandler> {
+        let consts = Constants::default();
+        let emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::B, 1),
+                Instruction::IMM(Register::C, 3),
+                Instruction::ADD(Register::A, Register::B),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::IMM(Register::D, 2),
+                Instruction::JMP("L".try_into().unwrap(), Register::D),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        Debugger::new(emulator, DebuggerOptions::default())
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("b 0x10".parse::<Command>().unwrap(), Command::Break(0x10));
+        assert_eq!("step".parse::<Command>().unwrap(), Command::Step(1));
+        assert_eq!("s 5".parse::<Command>().unwrap(), Command::Step(5));
+        assert_eq!("i r".parse::<Command>().unwrap(), Command::InfoRegisters);
+        assert_eq!(
+            "w stack:3".parse::<Command>().unwrap(),
+            Command::Watch(Watchpoint::Stack { address: 3 })
+        );
+        assert_eq!(
+            "x 0x20 16".parse::<Command>().unwrap(),
+            Command::Memory(0x20, 16)
+        );
+        assert_eq!(
+            "set a 0x42".parse::<Command>().unwrap(),
+            Command::SetRegister(Register::A, 0x42)
+        );
+        assert_eq!(
+            "set memory 0x10 1 2".parse::<Command>().unwrap(),
+            Command::SetMemory(0x10, vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_commands() {
+        assert!("break".parse::<Command>().is_err());
+        assert!("break 256".parse::<Command>().is_err());
+        assert!("set z 1".parse::<Command>().is_err());
+        assert!("frobnicate".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_continue_to_breakpoint() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger.execute(&Command::Break(3), &mut output).unwrap();
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert_eq!(debugger.emulator().registers()[Register::A], 1);
+
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+    }
+
+    #[test]
+    fn test_next_steps_over_loop() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger.execute(&Command::Step(5), &mut output).unwrap();
+        assert_eq!(debugger.current_index(), 5);
+
+        debugger.execute(&Command::Next, &mut output).unwrap();
+        assert_eq!(debugger.current_index(), 6);
+        assert_eq!(debugger.emulator().registers()[Register::A], 3);
+    }
+
+    #[test]
+    fn test_continue_to_exit() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert_eq!(debugger.emulator().exit_code(), Some(3));
+        assert!(debugger.execute(&Command::Step(1), &mut output).is_err());
+    }
+
+    #[test]
+    fn test_reverse_continue_to_breakpoint() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert_eq!(debugger.emulator().exit_code(), Some(3));
+
+        debugger.execute(&Command::Break(3), &mut output).unwrap();
+        debugger
+            .execute(&Command::ReverseContinue, &mut output)
+            .unwrap();
+        assert_eq!(debugger.current_index(), 3);
+        assert_eq!(debugger.emulator().registers()[Register::A], 3);
+
+        debugger
+            .execute(&Command::ReverseStep(2), &mut output)
+            .unwrap();
+        assert_eq!(debugger.current_index(), 5);
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        let watchpoint = "reg:a=2".parse().unwrap();
+        debugger
+            .execute(&Command::Watch(watchpoint), &mut output)
+            .unwrap();
+
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert_eq!(debugger.current_index(), 3);
+        assert_eq!(debugger.emulator().registers()[Register::A], 2);
+
+        debugger.execute(&Command::Step(4), &mut output).unwrap();
+        debugger
+            .execute(&Command::ReverseContinue, &mut output)
+            .unwrap();
+        assert_eq!(debugger.current_index(), 2);
+        assert_eq!(debugger.emulator().registers()[Register::A], 1);
+
+        debugger.execute(&Command::Unwatch(0), &mut output).unwrap();
+        assert!(debugger.execute(&Command::Unwatch(0), &mut output).is_err());
+    }
+
+    #[test]
+    fn test_set_and_examine_memory() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger
+            .execute(&Command::SetMemory(0x10, b"hi".to_vec()), &mut output)
+            .unwrap();
+        debugger
+            .execute(&Command::Memory(0x10, 2), &mut output)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("68 69"));
+        assert!(output.contains("|hi|"));
+    }
+
+    #[test]
+    fn test_continue_step_limit() {
+        let consts = Constants::default();
+        let emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::I, 0),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        let options = DebuggerOptions {
+            history_limit: 4,
+            max_steps: 100,
+        };
+        let mut debugger = Debugger::new(emulator, options);
+        let mut output = vec![];
+
+        debugger.execute(&Command::Continue, &mut output).unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("Stopped after 100 steps"));
+        assert_eq!(debugger.emulator().history_len(), 4);
+
+        debugger
+            .execute(&Command::ReverseStep(5), &mut output)
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("Reached the start"));
+    }
+
+    #[test]
+    fn test_reverse_step_undoes_set() {
+        let mut debugger = counting_debugger();
+        let mut output = vec![];
+
+        debugger.execute(&Command::Step(1), &mut output).unwrap();
+        debugger
+            .execute(&Command::SetRegister(Register::B, 7), &mut output)
+            .unwrap();
+        debugger
+            .execute(&Command::SetMemory(0x10, b"hi".to_vec()), &mut output)
+            .unwrap();
+
+        debugger
+            .execute(&Command::ReverseStep(1), &mut output)
+            .unwrap();
+        assert_eq!(debugger.emulator().memory()[0x10], 0);
+        assert_eq!(debugger.emulator().registers()[Register::B], 7);
+
+        debugger
+            .execute(&Command::ReverseStep(1), &mut output)
+            .unwrap();
+        assert_eq!(debugger.emulator().registers()[Register::B], 1);
+        assert_eq!(debugger.current_index(), 1);
+    }
+
+    #[test]
+    fn test_repl_repeats_previous_command() {
+        let mut debugger = counting_debugger();
+
+        debugger
+            .repl("step\n\n\nquit\n".as_bytes(), vec![])
+            .unwrap();
+        assert_eq!(debugger.current_index(), 3);
+    }
+}
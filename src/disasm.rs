@@ -1,9 +1,10 @@
 use anyhow::{bail, Result};
 
 use crate::yan85::{
-    constants::{Constants, Decodable},
+    constants::{Constants, Decodable, Encodable},
     flags::Flags,
     instruction::Instruction,
+    opcode::Opcode,
     register::Register,
 };
 
@@ -22,33 +23,71 @@ pub fn disassemble(bytes: Vec<u8>, constants: Constants) -> Result<Vec<Instructi
 }
 
 /// Attempts to convert the given byte 3-tuple to a Yan85 instruction.
+///
+/// An opcode byte that matches no opcode exactly but combines several opcode bits decodes to an
+/// [`Instruction::Compound`] whose operations follow the constants' opcode order.
 pub fn disassemble_instruction(bytes: [u8; 3], constants: Constants) -> Result<Instruction> {
     let bo = constants.byte_order;
-    let o = constants.opcode;
 
     let op = bytes[bo.op as usize];
     let a = bytes[bo.a as usize];
     let b = bytes[bo.b as usize];
 
-    let a_register = Register::decode(a, constants);
-    let b_register = Register::decode(b, constants);
+    if let Some(opcode) = Opcode::ALL
+        .into_iter()
+        .find(|opcode| opcode.encode(constants) == op)
+    {
+        return disassemble_operation(opcode, a, b, constants);
+    }
+
+    let opcodes: Vec<Opcode> = constants
+        .opcode
+        .order
+        .into_iter()
+        .filter(|opcode| op & opcode.encode(constants) != 0)
+        .collect();
+
+    let covered = opcodes
+        .iter()
+        .fold(0, |covered, opcode| covered | opcode.encode(constants));
+    if opcodes.len() < 2 || covered != op {
+        bail!("Invalid opcode: {op:#02x}");
+    }
+
+    let operations = opcodes
+        .into_iter()
+        .map(|opcode| disassemble_operation(opcode, a, b, constants))
+        .collect::<Result<_>>()?;
+
+    Ok(Instruction::Compound(operations))
+}
 
-    match op {
-        _ if op == o.IMM => Ok(Instruction::IMM(a_register?, b)),
-        _ if op == o.ADD => Ok(Instruction::ADD(a_register?, b_register?)),
-        _ if op == o.STK => Ok(Instruction::STK(
+/// Decodes the operands `a` and `b` for a single `opcode`.
+fn disassemble_operation(
+    opcode: Opcode,
+    a: u8,
+    b: u8,
+    constants: Constants,
+) -> Result<Instruction> {
+    // Decoded lazily, since failing to decode is slow when errors capture backtraces.
+    let a_register = || Register::decode(a, constants);
+    let b_register = || Register::decode(b, constants);
+
+    match opcode {
+        Opcode::Imm => Ok(Instruction::IMM(a_register()?, b)),
+        Opcode::Add => Ok(Instruction::ADD(a_register()?, b_register()?)),
+        Opcode::Stk => Ok(Instruction::STK(
             Option::<Register>::decode(a, constants)?,
             Option::<Register>::decode(b, constants)?,
         )),
-        _ if op == o.STM => Ok(Instruction::STM(a_register?, b_register?)),
-        _ if op == o.LDM => Ok(Instruction::LDM(a_register?, b_register?)),
-        _ if op == o.CMP => Ok(Instruction::CMP(a_register?, b_register?)),
-        _ if op == o.JMP => Ok(Instruction::JMP(Flags::decode(a, constants)?, b_register?)),
-        _ if op == o.SYS => Ok(Instruction::SYS(
+        Opcode::Stm => Ok(Instruction::STM(a_register()?, b_register()?)),
+        Opcode::Ldm => Ok(Instruction::LDM(a_register()?, b_register()?)),
+        Opcode::Cmp => Ok(Instruction::CMP(a_register()?, b_register()?)),
+        Opcode::Jmp => Ok(Instruction::JMP(Flags::decode(a, constants)?, b_register()?)),
+        Opcode::Sys => Ok(Instruction::SYS(
             a,
             Option::<Register>::decode(b, constants)?,
         )),
-        _ => bail!("Invalid opcode: {op:#02x}"),
     }
 }
 
@@ -187,4 +226,41 @@ mod tests {
             Instruction::SYS(consts.syscall.EXIT, None),
         );
     }
+
+    #[test]
+    fn test_disassemble_compound() {
+        let mut consts = Constants::default();
+        consts.opcode.order.swap(0, 1);
+
+        assert_eq!(
+            disassemble_instruction(
+                [
+                    consts.opcode.IMM | consts.opcode.ADD,
+                    consts.register.A,
+                    consts.register.B
+                ],
+                consts
+            )
+            .unwrap(),
+            Instruction::Compound(vec![
+                Instruction::ADD(Reg::A, Reg::B),
+                Instruction::IMM(Reg::A, consts.register.B),
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_disassemble_invalid_opcode() {
+        let consts = Constants::default();
+        assert!(disassemble_instruction([0, consts.register.A, 0], consts).is_err());
+        assert!(disassemble_instruction(
+            [
+                consts.opcode.ADD | consts.opcode.CMP,
+                consts.register.A,
+                0x03
+            ],
+            consts
+        )
+        .is_err());
+    }
 }
@@ -1,14 +1,91 @@
+use std::io::Write;
+
 use anyhow::Result;
+use colored::Colorize;
+
+use crate::{
+    emulator::{Emulator, StepOutcome},
+    run::{RunMonitor, RunOptions, StopReason},
+    syscall_handler::SyscallHandler,
+    trace::Tracer,
+    yan85::register::Register,
+};
+
+/// Emulates the Yan85 program loaded in `emulator` until it exits or one of the limits in
+/// `options` is reached, returning why it stopped.
+///
+/// When showing disassembly, instructions that were written at runtime (e.g. via `READ_CODE`) are
+/// marked with a `*`. If a tracer is supplied, every executed instruction is recorded with it.
+/// Hits of watchpoints that only log and comparisons of values derived from input are reported on
+/// standard error.
+pub fn emulate<H: SyscallHandler>(
+    emulator: &mut Emulator<H>,
+    show_disassembly: bool,
+    tracer: Option<&mut Tracer<impl Write>>,
+    options: RunOptions,
+) -> Result<StopReason> {
+    let mut monitor = RunMonitor::new(options);
+    let stop_reason = emulate_until(emulator, show_disassembly, tracer, &mut monitor, |_| false)?;
+
+    Ok(stop_reason.expect("emulation only stops early if asked to"))
+}
+
+/// Emulates the Yan85 program loaded in `emulator` until `stop` holds before an instruction, the
+/// program exits, or `monitor` reports that a limit was reached. Returns why the program stopped,
+/// unless it was because of `stop`.
+///
+/// See [`emulate`] for the meaning of the other arguments.
+pub fn emulate_until<H: SyscallHandler>(
+    emulator: &mut Emulator<H>,
+    show_disassembly: bool,
+    mut tracer: Option<&mut Tracer<impl Write>>,
+    monitor: &mut RunMonitor,
+    mut stop: impl FnMut(&Emulator<H>) -> bool,
+) -> Result<Option<StopReason>> {
+    let stop_reason = loop {
+        if stop(emulator) {
+            break None;
+        }
+
+        if let Some(reason) = monitor.before_step(emulator) {
+            break Some(reason);
+        }
 
-use crate::emulator::Emulator;
+        let index = emulator.registers()[Register::I];
+        let modified = emulator.code().is_instruction_modified(index);
 
-/// Emulates the Yan85 program loaded in `emulator`.
-pub fn emulate(emulator: &mut Emulator, show_disassembly: bool) -> Result<()> {
-    loop {
-        let instruction = emulator.step()?;
+        let outcome = match tracer.as_mut() {
+            Some(tracer) => tracer.step(emulator)?,
+            None => emulator.step()?,
+        };
 
-        if show_disassembly {
-            println!("{instruction}");
+        if let (true, StepOutcome::Continued(instruction)) = (show_disassembly, &outcome) {
+            let instruction = instruction.listing(emulator.constants().semantics.jump);
+            if modified {
+                println!("{} {instruction}", "*".yellow());
+            } else {
+                println!("{instruction}");
+            }
         }
+
+        let stop_reason = monitor.record(emulator, outcome);
+
+        for hit in monitor.take_logged() {
+            eprintln!("{} {hit}", "watch:".cyan());
+        }
+
+        for comparison in monitor.take_comparisons() {
+            eprintln!("{} {comparison}", "taint:".magenta());
+        }
+
+        if stop_reason.is_some() {
+            break stop_reason;
+        }
+    };
+
+    if let Some(tracer) = tracer {
+        tracer.flush()?;
     }
+
+    Ok(stop_reason)
 }
@@ -1,23 +1,29 @@
 use std::{
-    cmp,
-    ffi::CString,
-    fs::File,
+    collections::VecDeque,
     io::{Read, Write},
     mem,
-    os::fd::{AsRawFd, FromRawFd},
-    process::exit,
-    thread,
     time::Duration,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     asm::assemble,
+    clock::Clock,
+    coverage::Coverage,
     disasm::disassemble_instruction,
+    hook::{Hook, Hooks, Verdict},
+    profile::Profile,
+    run::{RunMonitor, RunOptions, StopReason},
+    syscall_handler::{HostSyscallHandler, SyscallHandler},
+    taint::Comparison,
+    watch::WatchHit,
     yan85::{
-        code::Code,
-        constants::{Constants, Decodable, Encodable},
+        code::{Code, CODE_SIZE},
+        constants::{
+            Constants, Decodable, Encodable, FlagUpdate, JumpSemantics, Layout, StackLayout,
+        },
         flags::Flags,
         instruction::Instruction,
         memory::Memory,
@@ -28,8 +34,193 @@ use crate::{
     },
 };
 
+/// The offset of memory in the reference VM's flat state struct, which starts with the code.
+const FLAT_MEMORY: usize = CODE_SIZE;
+/// The offset of the registers in the reference VM's flat state struct, which follow memory.
+const FLAT_REGISTERS: usize = FLAT_MEMORY + 256;
+/// The size of the reference VM's flat state struct.
+const FLAT_SIZE: usize = FLAT_REGISTERS + Register::ALL.len();
+
+/// The result of stepping through a single instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The instruction was emulated and execution can continue.
+    Continued(Instruction),
+    /// The program terminated via the `EXIT` syscall with the given exit code.
+    Exited(u8),
+}
+
+/// An observable side effect of executing an instruction.
+///
+/// Memory, stack, and code accesses are recorded byte by byte, so syscalls that transfer data
+/// produce one effect per byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Effect {
+    /// A byte of memory was read.
+    MemoryRead {
+        /// The memory address.
+        address: u8,
+        /// The value read.
+        value: u8,
+    },
+    /// A byte of memory was written.
+    MemoryWrite {
+        /// The memory address.
+        address: u8,
+        /// The value before the write.
+        old: u8,
+        /// The value after the write.
+        new: u8,
+    },
+    /// A stack slot was read.
+    StackRead {
+        /// The stack address.
+        address: u8,
+        /// The value read.
+        value: u8,
+    },
+    /// A stack slot was written.
+    StackWrite {
+        /// The stack address.
+        address: u8,
+        /// The value before the write.
+        old: u8,
+        /// The value after the write.
+        new: u8,
+    },
+    /// A register was read as data by a system call that overran memory.
+    RegisterRead {
+        /// The register.
+        register: Register,
+        /// The value read.
+        value: u8,
+    },
+    /// A register was written as data by a system call that overran memory.
+    RegisterWrite {
+        /// The register.
+        register: Register,
+        /// The value before the write.
+        old: u8,
+        /// The value after the write.
+        new: u8,
+    },
+    /// A byte of code was written.
+    CodeWrite {
+        /// The byte offset into the code storage.
+        offset: usize,
+        /// The value before the write.
+        old: u8,
+        /// The value after the write.
+        new: u8,
+        /// Whether the byte had already been written at runtime before this write.
+        was_modified: bool,
+    },
+    /// A system call was performed.
+    Syscall {
+        /// The system call.
+        syscall: Syscall,
+        /// The values of registers A, B, and C when the call was made.
+        arguments: [u8; 3],
+        /// The value returned, unless the call doesn't return.
+        return_value: Option<u8>,
+    },
+    /// Two values were compared by a `CMP` operation.
+    Comparison {
+        /// The value of the first register.
+        a: u8,
+        /// The value of the second register.
+        b: u8,
+    },
+    /// A `JMP` operation was executed.
+    Jump {
+        /// Whether it jumped.
+        taken: bool,
+    },
+}
+
+/// A summary of a program run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunResult {
+    /// Why the run stopped.
+    pub stop_reason: StopReason,
+    /// The number of instructions executed, including the final `SYS` instruction if the program
+    /// exited.
+    pub steps: u64,
+    /// Standard output captured by the syscall handler, if it captures output.
+    pub output: Option<Vec<u8>>,
+    /// Hits of watchpoints with [`WatchAction::Log`](crate::watch::WatchAction::Log), in the order
+    /// they occurred.
+    pub watch_log: Vec<WatchHit>,
+    /// The total simulated time that the program has slept for.
+    pub simulated_time: Duration,
+    /// Execution and branch counts, if [`RunOptions::coverage`] was enabled.
+    pub coverage: Option<Coverage>,
+    /// Execution statistics, if [`RunOptions::profile`] was enabled.
+    pub profile: Option<Profile>,
+    /// Comparisons of values derived from input, if [`RunOptions::taint`] was enabled.
+    pub comparisons: Vec<Comparison>,
+}
+
+impl RunResult {
+    /// The code that the program passed to the `EXIT` syscall, if it exited.
+    pub fn exit_code(&self) -> Option<u8> {
+        match self.stop_reason {
+            StopReason::Exited(exit_code) => Some(exit_code),
+            _ => None,
+        }
+    }
+}
+
+/// The information needed to undo a single step.
+#[derive(Debug, Clone)]
+struct UndoRecord {
+    /// Register values before the step.
+    registers: Registers,
+    /// The side effects of the step.
+    effects: Vec<Effect>,
+}
+
+/// Undo information for the most recent steps.
+#[derive(Debug, Clone)]
+struct History {
+    /// The records, oldest first.
+    records: VecDeque<UndoRecord>,
+    /// The number of records to keep, beyond which the oldest are dropped.
+    limit: usize,
+}
+
+impl History {
+    /// Records a step, dropping the oldest record if the history is full.
+    fn push(&mut self, record: UndoRecord) {
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
+
+/// A saved copy of an emulator's complete state. See [`Emulator::snapshot`].
+#[derive(Clone)]
+pub struct Snapshot<H: SyscallHandler>(Emulator<H>);
+
 /// A Yan85 emulator.
-pub struct Emulator {
+///
+/// File-related system calls are serviced by the handler `H`, which defaults to the host's
+/// standard streams and filesystem.
+///
+/// The complete machine state—including the handler's, if it supports it—can be saved to a file
+/// with [`Emulator::save_state`] and resumed with [`Emulator::load_state`].
+///
+/// [`Hook`]s added with [`Emulator::add_hook`] are called around instructions, memory accesses, and
+/// system calls.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "H: Deserialize<'de>"))]
+pub struct Emulator<H: SyscallHandler = HostSyscallHandler> {
     /// Encoding constants.
     constants: Constants,
     /// The Yan85 machine code to emulate.
@@ -40,54 +231,461 @@ pub struct Emulator {
     stack: Stack,
     /// The Yan85 memory.
     memory: Memory,
+    /// The backend that services file-related system calls.
+    handler: H,
+    /// The exit code, once the program has terminated.
+    exit_code: Option<u8>,
+    /// The time passed by the `SLEEP` syscall.
+    #[serde(default)]
+    clock: Clock,
+    /// The side effects of the most recently executed instruction.
+    #[serde(skip)]
+    effects: Vec<Effect>,
+    /// Undo information for the most recent steps, if history recording is enabled.
+    #[serde(skip)]
+    history: Option<History>,
+    /// Callbacks run as the program executes.
+    #[serde(skip)]
+    hooks: Hooks<H>,
 }
 
 impl Emulator {
-    /// Constructs a new emulator instance.
+    /// Constructs a new emulator instance that performs system calls on the host.
     pub fn new(constants: Constants, code: Code, memory: Memory) -> Self {
+        Self::with_handler(constants, code, memory, HostSyscallHandler::default())
+    }
+
+    /// Attempts to construct a new emulator instance from (disassembled) instructions that
+    /// performs system calls on the host.
+    pub fn from_instructions(
+        constants: Constants,
+        instructions: Vec<Instruction>,
+        memory: Memory,
+    ) -> Result<Self> {
+        Self::from_instructions_with_handler(
+            constants,
+            instructions,
+            memory,
+            HostSyscallHandler::default(),
+        )
+    }
+}
+
+impl<H: SyscallHandler> Emulator<H> {
+    /// Constructs a new emulator instance whose system calls are serviced by `handler`.
+    pub fn with_handler(constants: Constants, code: Code, memory: Memory, handler: H) -> Self {
         Self {
             constants,
             code,
             registers: Registers::default(),
             stack: Stack::default(),
             memory,
+            handler,
+            exit_code: None,
+            clock: Clock::default(),
+            effects: vec![],
+            history: None,
+            hooks: Hooks::default(),
         }
     }
 
-    /// Attempts to construct a new emulator instance from (disassembled) instructions.
-    pub fn from_instructions(
+    /// Attempts to construct a new emulator instance from (disassembled) instructions whose system
+    /// calls are serviced by `handler`.
+    pub fn from_instructions_with_handler(
         constants: Constants,
         instructions: Vec<Instruction>,
         memory: Memory,
+        handler: H,
     ) -> Result<Self> {
-        let code = assemble(&instructions, constants);
+        let code = assemble(&instructions, constants)?;
 
-        Ok(Self {
+        Ok(Self::with_handler(
             constants,
-            code: code.try_into()?,
-            registers: Registers::default(),
-            stack: Stack::default(),
+            code.try_into()?,
             memory,
-        })
+            handler,
+        ))
     }
 
-    /// Steps through the next instruction.
-    pub fn step(&mut self) -> Result<Instruction> {
-        let instruction = disassemble_instruction(
+    /// The backend that services file-related system calls.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// The backend that services file-related system calls, mutably.
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Replaces the syscall handler with one derived from it, keeping the rest of the state. Hooks
+    /// are specific to the handler type, so they're removed.
+    pub fn map_handler<H2: SyscallHandler>(self, f: impl FnOnce(H) -> H2) -> Emulator<H2> {
+        Emulator {
+            constants: self.constants,
+            code: self.code,
+            registers: self.registers,
+            stack: self.stack,
+            memory: self.memory,
+            handler: f(self.handler),
+            exit_code: self.exit_code,
+            clock: self.clock,
+            effects: self.effects,
+            history: self.history,
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Adds a hook to be called as the program executes, after any hooks added before it.
+    ///
+    /// Hooks aren't part of the machine state: they aren't saved, cloned, or replaced by
+    /// [`Emulator::restore`].
+    pub fn add_hook(&mut self, hook: impl Hook<H> + Send + 'static) {
+        self.hooks.0.push(Box::new(hook));
+    }
+
+    /// Removes all hooks.
+    pub fn clear_hooks(&mut self) {
+        self.hooks.0.clear();
+    }
+
+    /// The encoding constants.
+    pub fn constants(&self) -> Constants {
+        self.constants
+    }
+
+    /// The Yan85 machine code being emulated.
+    pub fn code(&self) -> &Code {
+        &self.code
+    }
+
+    /// The Yan85 code, mutably. Writes through [`Code::write`] are marked as modified at runtime.
+    pub fn code_mut(&mut self) -> &mut Code {
+        &mut self.code
+    }
+
+    /// The Yan85 registers.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// The Yan85 registers, mutably.
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    /// The Yan85 memory.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// The Yan85 memory, mutably.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// The Yan85 stack. Unused if the constants place the stack in memory; see [`StackLayout`].
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// The Yan85 stack, mutably.
+    pub fn stack_mut(&mut self) -> &mut Stack {
+        &mut self.stack
+    }
+
+    /// The clock that the `SLEEP` syscall advances.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// The clock that the `SLEEP` syscall advances, mutably, e.g. to change its
+    /// [`SleepMode`](crate::clock::SleepMode).
+    pub fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    /// The exit code, if the program has terminated.
+    pub fn exit_code(&self) -> Option<u8> {
+        self.exit_code
+    }
+
+    /// The side effects of the most recently executed instruction, in the order they occurred.
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Decodes the next instruction to execute without executing it.
+    pub fn next_instruction(&self) -> Result<Instruction> {
+        disassemble_instruction(
             self.code.get_instruction(self.registers[Register::I]),
             self.constants,
-        )?;
+        )
+    }
 
-        self.registers[Register::I] += 1;
+    /// Steps through the next instruction.
+    ///
+    /// Fails if the program has already exited. If the instruction fails, the machine state is
+    /// restored to what it was before the step, and no history is recorded for it.
+    /// [`Emulator::effects`] still returns the side effects of the failed attempt.
+    pub fn step(&mut self) -> Result<StepOutcome> {
+        if self.exit_code.is_some() {
+            bail!("The program has already exited");
+        }
+
+        self.effects.clear();
+        let instruction = self.next_instruction()?;
+        let index = self.registers[Register::I];
+
+        let verdict = self
+            .call_hooks(|hook, emulator| hook.before_instruction(emulator, index, &instruction));
+        let registers = self.registers;
+
+        self.registers[Register::I] = self.registers[Register::I].wrapping_add(1);
+
+        let result = match verdict {
+            Verdict::Proceed => self.emulate_instruction(&instruction),
+            Verdict::Veto(()) => Ok(()),
+        };
+
+        if let Err(error) = result {
+            let effects = mem::take(&mut self.effects);
+            self.undo(registers, &effects);
+            self.effects = effects;
+
+            return Err(error);
+        }
+
+        if let Some(history) = &mut self.history {
+            history.push(UndoRecord {
+                registers,
+                effects: self.effects.clone(),
+            });
+        }
+
+        if verdict == Verdict::Proceed {
+            self.call_hooks(|hook, emulator| {
+                hook.after_instruction(emulator, index, &instruction);
+                Verdict::<()>::Proceed
+            });
+        }
+
+        match self.exit_code {
+            Some(exit_code) => Ok(StepOutcome::Exited(exit_code)),
+            None => Ok(StepOutcome::Continued(instruction)),
+        }
+    }
+
+    /// Enables recording the undo information that [`Emulator::step_back`] needs for up to
+    /// `limit` of the most recent steps, or disables it if `limit` is `None`. Changing the
+    /// recording discards the existing history.
+    ///
+    /// Only the machine state is recorded. Side effects on the syscall handler, such as consumed
+    /// input or written output, are not undone.
+    pub fn record_history(&mut self, limit: Option<usize>) {
+        self.history = limit.map(|limit| History {
+            records: VecDeque::new(),
+            limit,
+        });
+    }
+
+    /// The number of steps that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.records.len())
+    }
 
-        self.emulate_instruction(instruction)?;
+    /// Changes registers, memory, or the stack with `edit` outside of execution, as a debugger's
+    /// `set` command does. If history is being recorded, the change is recorded like a step, so
+    /// [`Emulator::step_back`] undoes it.
+    pub fn edit(&mut self, edit: impl FnOnce(&mut Registers, &mut Memory, &mut Stack)) {
+        let registers = self.registers;
+        let memory = self.memory.clone();
+        let stack = self.stack.clone();
+
+        edit(&mut self.registers, &mut self.memory, &mut self.stack);
+
+        if let Some(history) = &mut self.history {
+            let mut effects = vec![];
+            for address in 0..=u8::MAX {
+                let (old, new) = (memory[address], self.memory[address]);
+                if old != new {
+                    effects.push(Effect::MemoryWrite { address, old, new });
+                }
+
+                let (old, new) = (stack[address], self.stack[address]);
+                if old != new {
+                    effects.push(Effect::StackWrite { address, old, new });
+                }
+            }
 
-        Ok(instruction)
+            history.push(UndoRecord { registers, effects });
+        }
+    }
+
+    /// Undoes the most recently executed step, restoring registers, memory, the stack, and code.
+    /// Returns `false` if there is no step to undo.
+    ///
+    /// Undoing the step that exited the program allows execution to continue. Afterwards,
+    /// [`Emulator::effects`] returns the side effects of the undone step.
+    pub fn step_back(&mut self) -> bool {
+        let Some(record) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.records.pop_back())
+        else {
+            return false;
+        };
+
+        self.undo(record.registers, &record.effects);
+        self.effects = record.effects;
+
+        true
+    }
+
+    /// Reverts the writes among `effects`, latest first, and restores `registers` and the running
+    /// state that preceded them.
+    fn undo(&mut self, registers: Registers, effects: &[Effect]) {
+        for effect in effects.iter().rev() {
+            match *effect {
+                Effect::MemoryWrite { address, old, .. } => self.memory[address] = old,
+                Effect::StackWrite { address, old, .. } => self.stack[address] = old,
+                Effect::CodeWrite {
+                    offset,
+                    old,
+                    was_modified,
+                    ..
+                } => self.code.restore(offset, old, was_modified),
+                // registers are restored wholesale below
+                Effect::MemoryRead { .. }
+                | Effect::StackRead { .. }
+                | Effect::RegisterRead { .. }
+                | Effect::RegisterWrite { .. }
+                | Effect::Syscall { .. }
+                | Effect::Comparison { .. }
+                | Effect::Jump { .. } => {}
+            }
+        }
+
+        self.registers = registers;
+        self.exit_code = None;
+    }
+
+    /// Steps backwards until `stop` holds or the history is exhausted, returning the number of
+    /// steps undone. At least one step is undone if possible.
+    pub fn reverse_continue(&mut self, mut stop: impl FnMut(&Self) -> bool) -> u64 {
+        let mut steps = 0;
+
+        while self.step_back() {
+            steps += 1;
+
+            if stop(self) {
+                break;
+            }
+        }
+
+        steps
+    }
+
+    /// Steps through instructions until the program exits.
+    pub fn run(&mut self) -> Result<RunResult> {
+        self.run_with(RunOptions::default())
+    }
+
+    /// Steps through instructions until the program exits or one of the limits in `options` is
+    /// reached.
+    pub fn run_with(&mut self, options: RunOptions) -> Result<RunResult> {
+        let mut monitor = RunMonitor::new(options);
+
+        let stop_reason = loop {
+            if let Some(reason) = monitor.before_step(self) {
+                break reason;
+            }
+
+            let outcome = self.step()?;
+
+            if let Some(reason) = monitor.record(self, outcome) {
+                break reason;
+            }
+        };
+
+        Ok(RunResult {
+            stop_reason,
+            steps: monitor.steps(),
+            output: self.handler.captured_output().map(<[u8]>::to_vec),
+            watch_log: monitor.take_logged(),
+            simulated_time: self.clock.elapsed(),
+            coverage: monitor.take_coverage(),
+            profile: monitor.take_profile(),
+            comparisons: monitor.take_comparisons(),
+        })
+    }
+
+    /// Captures the complete machine state, including the syscall handler's.
+    pub fn snapshot(&self) -> Snapshot<H>
+    where
+        H: Clone,
+    {
+        Snapshot(self.clone())
+    }
+
+    /// Restores the machine state captured by [`Emulator::snapshot`].
+    pub fn restore(&mut self, snapshot: &Snapshot<H>)
+    where
+        H: Clone,
+    {
+        let hooks = mem::take(&mut self.hooks);
+        *self = snapshot.0.clone();
+        self.hooks = hooks;
+    }
+
+    /// Writes the complete machine state to `writer` as JSON.
+    pub fn save_state(&self, writer: impl Write) -> Result<()>
+    where
+        H: Serialize,
+    {
+        serde_json::to_writer(writer, self).context("Unable to save emulator state")
+    }
+
+    /// Reads a machine state written by [`Emulator::save_state`] from `reader`.
+    pub fn load_state(reader: impl Read) -> Result<Self>
+    where
+        H: DeserializeOwned,
+    {
+        serde_json::from_reader(reader).context("Unable to load emulator state")
+    }
+
+    /// Calls `f` with each hook in turn until one vetoes, returning the veto if there was one.
+    fn call_hooks<T>(
+        &mut self,
+        mut f: impl FnMut(&mut dyn Hook<H>, &mut Self) -> Verdict<T>,
+    ) -> Verdict<T> {
+        if self.hooks.0.is_empty() {
+            return Verdict::Proceed;
+        }
+
+        // Hooks receive the emulator, so they're moved out while they run.
+        let mut hooks = mem::take(&mut self.hooks);
+
+        let mut verdict = Verdict::Proceed;
+        for hook in &mut hooks.0 {
+            verdict = f(hook.as_mut(), self);
+
+            if let Verdict::Veto(_) = verdict {
+                break;
+            }
+        }
+
+        // Keep any hooks that were added while the others ran.
+        hooks.0.append(&mut self.hooks.0);
+        self.hooks = hooks;
+
+        verdict
     }
 
     /// Emulates a Yan85 instruction.
-    fn emulate_instruction(&mut self, instruction: Instruction) -> Result<()> {
-        match instruction {
+    fn emulate_instruction(&mut self, instruction: &Instruction) -> Result<()> {
+        match *instruction {
             Instruction::IMM(register, value) => self.emulate_imm(register, value),
             Instruction::ADD(a, b) => self.emulate_add(a, b),
             Instruction::STK(pop, push) => self.emulate_stk(pop, push),
@@ -96,6 +694,17 @@ impl Emulator {
             Instruction::CMP(a, b) => self.emulate_cmp(a, b),
             Instruction::JMP(condition, register) => self.emulate_jmp(condition, register),
             Instruction::SYS(syscall, register) => self.emulate_sys(syscall, register),
+            Instruction::Compound(ref operations) => {
+                for operation in operations {
+                    self.emulate_instruction(operation)?;
+
+                    if self.exit_code.is_some() {
+                        break;
+                    }
+                }
+
+                Ok(())
+            }
         }
     }
 
@@ -114,15 +723,32 @@ impl Emulator {
 
     /// Emulates a `STK` instruction, pushing `push`, and popping into `pop` unless either
     /// [`Register::None`].
+    ///
+    /// Where the stack lives is determined by [`StackLayout`].
     fn emulate_stk(&mut self, pop: Option<Register>, push: Option<Register>) -> Result<()> {
-        if let Some(src) = push {
-            self.stack[self.registers[Register::S]] = self.registers[src];
-            self.registers[Register::S] = self.registers[Register::S].wrapping_add(1);
-        }
-
-        if let Some(dest) = pop {
-            self.registers[Register::S] = self.registers[Register::S].wrapping_sub(1);
-            self.registers[dest] = self.stack[self.registers[Register::S]];
+        match self.constants.semantics.stack {
+            StackLayout::Separate => {
+                if let Some(src) = push {
+                    self.write_stack(self.registers[Register::S], self.registers[src]);
+                    self.registers[Register::S] = self.registers[Register::S].wrapping_add(1);
+                }
+
+                if let Some(dest) = pop {
+                    self.registers[Register::S] = self.registers[Register::S].wrapping_sub(1);
+                    self.registers[dest] = self.read_stack(self.registers[Register::S]);
+                }
+            }
+            StackLayout::Memory => {
+                if let Some(src) = push {
+                    self.registers[Register::S] = self.registers[Register::S].wrapping_add(1);
+                    self.write_memory(self.registers[Register::S], self.registers[src]);
+                }
+
+                if let Some(dest) = pop {
+                    self.registers[dest] = self.read_memory(self.registers[Register::S]);
+                    self.registers[Register::S] = self.registers[Register::S].wrapping_sub(1);
+                }
+            }
         }
 
         Ok(())
@@ -131,154 +757,279 @@ impl Emulator {
     /// Emulates a `STM` instruction, assigning the value of `b` to the location referenced by `a`.
     /// In other words, it performs `*a = b`.
     fn emulate_stm(&mut self, a: Register, b: Register) -> Result<()> {
-        self.memory[self.registers[a]] = self.registers[b];
+        self.write_memory(self.registers[a], self.registers[b]);
         Ok(())
     }
 
     /// Emulates a `LDM` instruction, assigning the value at the location referenced by `b` to `a`.
     /// In other words, it performs `a = *b`.
     fn emulate_ldm(&mut self, a: Register, b: Register) -> Result<()> {
-        self.registers[a] = self.memory[self.registers[b]];
+        self.registers[a] = self.read_memory(self.registers[b]);
         Ok(())
     }
 
     /// Emulates a `CMP` instruction, comparing `a` and `b` and assigning a representation of their
     /// relationship to register F.
     fn emulate_cmp(&mut self, a: Register, b: Register) -> Result<()> {
+        let semantics = self.constants.semantics.cmp;
         let a = self.registers[a];
         let b = self.registers[b];
 
-        let mut flags = Flags::default();
-
-        match a.cmp(&b) {
-            cmp::Ordering::Less => {
-                flags.less_than = true;
-                flags.not_equal = true;
-            }
-            cmp::Ordering::Greater => {
-                flags.greater_than = true;
-                flags.not_equal = true;
-            }
-            cmp::Ordering::Equal => {
-                flags.equal = true;
-            }
-        }
-
-        if (a == 0) && (b == 0) {
-            flags.zeroes = true;
-        }
+        let mut flags = match semantics.flags {
+            FlagUpdate::Replace => Flags::default(),
+            FlagUpdate::Accumulate => Flags::decode(self.registers[Register::F], self.constants)?,
+        };
 
+        flags.record_comparison(a, b, semantics.zero_flag);
         self.registers[Register::F] = flags.encode(self.constants);
+        self.effects.push(Effect::Comparison { a, b });
         Ok(())
     }
 
     /// Emulates a `JMP` instruction, comparing the conditions encoded in `condition` to those in
     /// register F, jumping to the instruction referenced by `register` if any of the conditions
-    /// match.
+    /// match. Whether an empty condition always jumps depends on [`JumpSemantics`].
     fn emulate_jmp(&mut self, condition: Flags, register: Register) -> Result<()> {
         let comparison_result = Flags::decode(self.registers[Register::F], self.constants)?;
 
-        if comparison_result.does_match(&condition) {
+        let unconditional = match self.constants.semantics.jump {
+            JumpSemantics::Reference => condition.is_empty(),
+            JumpSemantics::Strict => false,
+        };
+
+        let taken = unconditional || comparison_result.does_match(&condition);
+        if taken {
             self.registers[Register::I] = self.registers[register];
         }
+        self.effects.push(Effect::Jump { taken });
 
         Ok(())
     }
 
-    /// Emulates a `SYS` instruction, performing Yan85 system calls and placing the return value in
-    /// `retval_register`.
+    /// Emulates a `SYS` instruction, performing Yan85 system calls in the constants' syscall order
+    /// and placing each return value in `retval_register`. Return values are discarded if it's
+    /// [`Register::None`].
     fn emulate_sys(&mut self, syscalls: u8, retval_register: Option<Register>) -> Result<()> {
         let syscalls = Vec::<Syscall>::decode(syscalls, self.constants)?;
 
         for syscall in syscalls {
+            let verdict = self.call_hooks(|hook, emulator| hook.on_syscall(emulator, syscall));
+
             let a = self.registers[Register::A];
             let b = self.registers[Register::B];
             let c = self.registers[Register::C];
 
-            let return_value = match syscall {
-                Syscall::Open => self.syscall_open(a),
-                Syscall::ReadCode => self.syscall_read_code(a, b, c),
-                Syscall::ReadMemory => self.syscall_read_memory(a, b, c),
-                Syscall::Write => self.syscall_write(a, b, c),
-                Syscall::Sleep => self.syscall_sleep(a),
-                Syscall::Exit => self.syscall_exit(a),
-            };
+            let return_value = match (verdict, syscall) {
+                (Verdict::Veto(return_value), _) => Ok(return_value),
+                (Verdict::Proceed, Syscall::Open) => self.syscall_open(a),
+                (Verdict::Proceed, Syscall::ReadCode) => self.syscall_read_code(a, b, c),
+                (Verdict::Proceed, Syscall::ReadMemory) => self.syscall_read_memory(a, b, c),
+                (Verdict::Proceed, Syscall::Write) => self.syscall_write(a, b, c),
+                (Verdict::Proceed, Syscall::Sleep) => self.syscall_sleep(a),
+                (Verdict::Proceed, Syscall::Exit) => {
+                    self.syscall_exit(a);
+                    self.effects.push(Effect::Syscall {
+                        syscall,
+                        arguments: [a, b, c],
+                        return_value: None,
+                    });
+                    self.call_hooks(|hook, emulator| {
+                        hook.after_syscall(emulator, syscall, [a, b, c], None);
+                        Verdict::<()>::Proceed
+                    });
+                    return Ok(());
+                }
+            }?;
+
+            self.effects.push(Effect::Syscall {
+                syscall,
+                arguments: [a, b, c],
+                return_value: Some(return_value),
+            });
+            self.call_hooks(|hook, emulator| {
+                hook.after_syscall(emulator, syscall, [a, b, c], Some(return_value));
+                Verdict::<()>::Proceed
+            });
 
             if let Some(reg) = retval_register {
-                self.registers[reg] = return_value?;
-            } else {
-                bail!("the \"NONE\" argument is supported only for syscalls that don't return")
+                self.registers[reg] = return_value;
             }
         }
 
         Ok(())
     }
 
-    /// Opens the file on the host system with the path pointed to by `path_address`.
+    /// Reads the memory at `address`, recording the access, unless a hook supplies the value.
+    fn read_memory(&mut self, address: u8) -> u8 {
+        let value = match self.call_hooks(|hook, emulator| hook.on_memory_read(emulator, address)) {
+            Verdict::Proceed => self.memory[address],
+            Verdict::Veto(value) => value,
+        };
+        self.effects.push(Effect::MemoryRead { address, value });
+
+        value
+    }
+
+    /// Writes `new` to the memory at `address`, recording the access, unless a hook vetoes it.
+    fn write_memory(&mut self, address: u8, new: u8) {
+        if let Verdict::Veto(()) =
+            self.call_hooks(|hook, emulator| hook.on_memory_write(emulator, address, new))
+        {
+            return;
+        }
+
+        let old = self.memory[address];
+        self.memory[address] = new;
+        self.effects.push(Effect::MemoryWrite { address, old, new });
+    }
+
+    /// Reads the stack slot at `address`, recording the access.
+    fn read_stack(&mut self, address: u8) -> u8 {
+        let value = self.stack[address];
+        self.effects.push(Effect::StackRead { address, value });
+
+        value
+    }
+
+    /// Writes `new` to the stack slot at `address`, recording the access.
+    fn write_stack(&mut self, address: u8, new: u8) {
+        let old = self.stack[address];
+        self.stack[address] = new;
+        self.effects.push(Effect::StackWrite { address, old, new });
+    }
+
+    /// Opens the file with the path pointed to by `path_address`.
     fn syscall_open(&mut self, path_address: u8) -> Result<u8> {
-        let path_bytes: Vec<u8> = self.memory[path_address..]
-            .iter()
-            .take_while(|&&b| b != 0)
-            .copied()
-            .collect();
-        let path = &CString::new(path_bytes).expect("we don't have any null bytes by construction");
+        let start = FLAT_MEMORY + path_address as usize;
 
-        let file = File::open(path.to_str()?)?;
-        let fd = file.as_raw_fd();
-        mem::forget(file); // don't close the fd upon dropping `file`
+        let mut path = vec![];
+        for offset in start..start + self.transfer_len(start, FLAT_SIZE) {
+            match self.read_flat(offset) {
+                0 => break,
+                byte => path.push(byte),
+            }
+        }
 
-        Ok(u8::try_from(fd)?)
+        self.handler.open(&path)
     }
 
     /// Reads up to `num_bytes` bytes from the file with file descriptor `fd` into Yan85
     /// instructions, starting at instruction index `start`.
+    ///
+    /// The read is truncated as described by [`Layout`]. Bytes written to code this way are
+    /// marked as modified; see [`Code::is_instruction_modified`].
     fn syscall_read_code(&mut self, fd: u8, start: u8, num_bytes: u8) -> Result<u8> {
-        todo!("syscall read_code({fd}, {start:#02x}, {num_bytes:#02x})");
+        self.read_into(fd, 3 * start as usize, num_bytes)
     }
 
     /// Reads up to `num_bytes` bytes from the file with file descriptor `fd` into memory, starting
     /// at the memory location `start`.
+    ///
+    /// The read is truncated as described by [`Layout`].
     fn syscall_read_memory(&mut self, fd: u8, start: u8, num_bytes: u8) -> Result<u8> {
-        let mut buffer = vec![0u8; num_bytes as usize];
-
-        let mut file = unsafe { File::from_raw_fd(fd.into()) };
-        let bytes_read = file.read(&mut buffer)?;
-        let bytes_read = u8::try_from(bytes_read).expect("the buffer size is a u8");
-
-        self.memory[start..start + bytes_read].copy_from_slice(&buffer[..bytes_read as usize]);
-
-        Ok(bytes_read)
+        self.read_into(fd, FLAT_MEMORY + start as usize, num_bytes)
     }
 
     /// Writes up to `size` bytes from memory starting at the memory location `start` to the file
     /// with file descriptor `fd`.
+    ///
+    /// The write is truncated as described by [`Layout`].
     fn syscall_write(&mut self, fd: u8, start: u8, size: u8) -> Result<u8> {
-        let bytes_written = unsafe {
-            let mut file = File::from_raw_fd(fd.into());
-            let n = file.write(&self.memory[start..start + size])?;
-            mem::forget(file);
+        let start = FLAT_MEMORY + start as usize;
+        let end = start + self.transfer_len(start, size.into());
 
-            n
-        };
+        let buffer: Vec<u8> = (start..end).map(|offset| self.read_flat(offset)).collect();
+        let bytes_written = self.handler.write(fd, &buffer)?;
 
         Ok(u8::try_from(bytes_written).expect("the range size is at most 255"))
     }
 
-    /// Sleeps for `duration_secs` seconds.
+    /// Reads up to `num_bytes` bytes from the file with file descriptor `fd` to `start` in the
+    /// flat layout, returning the number of bytes read.
+    fn read_into(&mut self, fd: u8, start: usize, num_bytes: u8) -> Result<u8> {
+        let mut buffer = vec![0u8; self.transfer_len(start, num_bytes.into())];
+
+        let bytes_read = self.handler.read(fd, &mut buffer)?;
+        for (offset, &value) in (start..).zip(&buffer[..bytes_read]) {
+            self.write_flat(offset, value);
+        }
+
+        Ok(u8::try_from(bytes_read).expect("the buffer size is a u8"))
+    }
+
+    /// Limits a transfer of `length` bytes starting at `offset` in the flat layout to the bytes
+    /// that the [`Layout`] allows it to reach.
+    fn transfer_len(&self, offset: usize, length: usize) -> usize {
+        let end = match self.constants.semantics.layout {
+            Layout::Flat => FLAT_SIZE,
+            Layout::Separate if offset < FLAT_MEMORY => FLAT_MEMORY,
+            Layout::Separate => FLAT_REGISTERS,
+        };
+
+        length.min(end - offset)
+    }
+
+    /// Reads the byte at `offset` in the reference VM's flat state struct, recording the access.
+    fn read_flat(&mut self, offset: usize) -> u8 {
+        match offset {
+            ..FLAT_MEMORY => self.code[offset],
+            FLAT_MEMORY..FLAT_REGISTERS => self.read_memory((offset - FLAT_MEMORY) as u8),
+            _ => {
+                let register = Register::ALL[offset - FLAT_REGISTERS];
+                let value = self.registers[register];
+                self.effects.push(Effect::RegisterRead { register, value });
+
+                value
+            }
+        }
+    }
+
+    /// Writes `new` to the byte at `offset` in the reference VM's flat state struct, recording the
+    /// access.
+    fn write_flat(&mut self, offset: usize, new: u8) {
+        match offset {
+            ..FLAT_MEMORY => {
+                self.effects.push(Effect::CodeWrite {
+                    offset,
+                    old: self.code[offset],
+                    new,
+                    was_modified: self.code.is_byte_modified(offset),
+                });
+                self.code.write(offset, &[new]);
+            }
+            FLAT_MEMORY..FLAT_REGISTERS => self.write_memory((offset - FLAT_MEMORY) as u8, new),
+            _ => {
+                let register = Register::ALL[offset - FLAT_REGISTERS];
+                let old = self.registers[register];
+                self.registers[register] = new;
+                self.effects
+                    .push(Effect::RegisterWrite { register, old, new });
+            }
+        }
+    }
+
+    /// Sleeps for `duration_secs` seconds of simulated time. How much real time passes depends on
+    /// the clock's [`SleepMode`](crate::clock::SleepMode).
     fn syscall_sleep(&mut self, duration_secs: u8) -> Result<u8> {
-        thread::sleep(Duration::from_secs(duration_secs.into()));
+        self.clock.sleep(Duration::from_secs(duration_secs.into()));
         Ok(0)
     }
 
-    /// Terminates the Yan85 virtual machine.
-    fn syscall_exit(&mut self, exit_code: u8) -> ! {
-        exit(exit_code as i32);
+    /// Terminates the Yan85 virtual machine. Any syscalls after `EXIT` in the same `SYS`
+    /// instruction are not performed.
+    fn syscall_exit(&mut self, exit_code: u8) {
+        self.exit_code = Some(exit_code);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        clock::SleepMode,
+        syscall_handler::{ScriptedSyscallHandler, VirtualSyscallHandler},
+        yan85::{constants::ZeroFlag, opcode::Opcode},
+    };
 
     #[test]
     fn test_imm() {
@@ -370,6 +1121,37 @@ mod tests {
         assert_eq!(emulator.registers[Register::C], 42);
     }
 
+    #[test]
+    fn test_stk_memory_layout() {
+        let mut consts = Constants::default();
+        consts.semantics.stack = StackLayout::Memory;
+
+        let mut emulator = Emulator::from_instructions(
+            consts,
+            vec![
+                Instruction::STK(None, Some(Register::C)),
+                Instruction::LDM(Register::D, Register::S),
+                Instruction::STK(Some(Register::B), None),
+            ],
+            Memory::default(),
+        )
+        .unwrap();
+
+        emulator.registers[Register::C] = 42;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::S], 1);
+        assert_eq!(emulator.memory[1], 42);
+        assert_eq!(emulator.stack, Stack::default());
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::D], 42);
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::S], 0);
+        assert_eq!(emulator.registers[Register::B], 42);
+    }
+
     #[test]
     fn test_stm() {
         let mut emulator = Emulator::from_instructions(
@@ -425,6 +1207,8 @@ mod tests {
         assert_eq!(emulator.registers[Register::F] & f.G, 0);
         assert_eq!(emulator.registers[Register::F] & f.E, 0);
         assert_eq!(emulator.registers[Register::F] & f.Z, 0);
+
+        assert_eq!(emulator.effects(), [Effect::Comparison { a: 1, b: 2 }]);
     }
 
     #[test]
@@ -542,6 +1326,7 @@ mod tests {
 
         emulator.step().unwrap();
         assert_eq!(emulator.registers[Register::I], 2);
+        assert_eq!(emulator.effects(), [Effect::Jump { taken: true }]);
     }
 
     #[test]
@@ -567,7 +1352,623 @@ mod tests {
 
         assert_eq!(emulator.registers[Register::I], 1);
         assert_ne!(emulator.registers[Register::I], 2);
+        assert_eq!(emulator.effects(), [Effect::Jump { taken: false }]);
+    }
+
+    #[test]
+    fn test_compound() {
+        let mut consts = Constants::default();
+        let imm = Instruction::IMM(Register::A, consts.register.B);
+        let add = Instruction::ADD(Register::A, Register::B);
+
+        for (operations, expected) in [(vec![imm.clone(), add.clone()], 7), (vec![add, imm], 2)] {
+            if operations[0].opcode() == Some(Opcode::Add) {
+                consts.opcode.order.swap(0, 1);
+            }
+
+            let mut emulator = Emulator::from_instructions(
+                consts,
+                vec![Instruction::Compound(operations)],
+                Memory::default(),
+            )
+            .unwrap();
+
+            emulator.registers[Register::B] = 5;
+            emulator.step().unwrap();
+
+            assert_eq!(emulator.registers[Register::A], expected);
+        }
+    }
+
+    #[test]
+    fn test_jmp_unconditional() {
+        let mut consts = Constants::default();
+
+        for (semantics, expected) in [(JumpSemantics::Reference, 2), (JumpSemantics::Strict, 1)] {
+            consts.semantics.jump = semantics;
+
+            let mut emulator = Emulator::from_instructions(
+                consts,
+                vec![
+                    Instruction::JMP(Flags::default(), Register::A),
+                    Instruction::ADD(Register::C, Register::C),
+                    Instruction::ADD(Register::C, Register::C),
+                ],
+                Memory::default(),
+            )
+            .unwrap();
+
+            emulator.registers[Register::A] = 2;
+            emulator.step().unwrap();
+
+            assert_eq!(emulator.registers[Register::I], expected);
+        }
+    }
+
+    #[test]
+    fn test_cmp_semantics() {
+        let mut consts = Constants::default();
+        consts.semantics.cmp.zero_flag = ZeroFlag::Either;
+        consts.semantics.cmp.flags = FlagUpdate::Accumulate;
+        let f = consts.flag;
+
+        let mut emulator = Emulator::from_instructions(
+            consts,
+            vec![
+                Instruction::CMP(Register::A, Register::B),
+                Instruction::CMP(Register::B, Register::A),
+            ],
+            Memory::default(),
+        )
+        .unwrap();
+
+        emulator.registers[Register::B] = 1;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::F], f.L | f.N | f.Z);
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::F], f.L | f.G | f.N | f.Z);
+    }
+
+    #[test]
+    fn test_sys_read_code() {
+        let consts = Constants::default();
+
+        let mut handler = VirtualSyscallHandler::default();
+        handler.add_file(
+            "code",
+            assemble(&[Instruction::IMM(Register::D, 42)], consts).unwrap(),
+        );
+        let fd = handler.open(b"code").unwrap();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![Instruction::SYS(
+                consts.syscall.READ_CODE,
+                Some(Register::C),
+            )],
+            Memory::default(),
+            handler,
+        )
+        .unwrap();
+
+        emulator.registers[Register::A] = fd;
+        emulator.registers[Register::B] = 1;
+        emulator.registers[Register::C] = 3;
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.registers[Register::C], 3);
+        assert!(emulator.code.is_instruction_modified(1));
+
+        assert_eq!(
+            emulator.step().unwrap(),
+            StepOutcome::Continued(Instruction::IMM(Register::D, 42))
+        );
+        assert_eq!(emulator.registers[Register::D], 42);
+    }
+
+    #[test]
+    fn test_sys_open_read_write() {
+        let consts = Constants::default();
+
+        let mut handler = VirtualSyscallHandler::default();
+        handler.add_file("/flag", "pwn.college{fake}");
+
+        let mut memory = Memory::default();
+        memory[0..6].copy_from_slice(b"/flag\0");
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // a = open("/flag")
+                Instruction::IMM(Register::A, 0),
+                Instruction::SYS(consts.syscall.OPEN, Some(Register::A)),
+                // c = read_memory(a, 0x10, 0x20)
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 0x20),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+                // write(1, 0x10, c)
+                Instruction::IMM(Register::A, 1),
+                Instruction::SYS(consts.syscall.WRITE, Some(Register::D)),
+            ],
+            memory,
+            handler,
+        )
+        .unwrap();
+
+        for _ in 0..7 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(emulator.registers[Register::D], 17);
+        assert_eq!(emulator.handler().stdout(), b"pwn.college{fake}");
+    }
+
+    #[test]
+    fn test_sys_scripted_input() {
+        let consts = Constants::default();
+
+        let mut memory = Memory::default();
+        memory[0..2].copy_from_slice(b"? ");
+
+        let script = "expect \"? \"\nsend \"yes\"".parse().unwrap();
+        let handler = ScriptedSyscallHandler::new(VirtualSyscallHandler::default(), script);
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // read_memory(0, 0x10, 3)
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+            ],
+            memory.clone(),
+            handler.clone(),
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+        assert!(emulator.step().is_err());
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // write(1, 0, 2)
+                Instruction::IMM(Register::A, 1),
+                Instruction::IMM(Register::B, 0),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.WRITE, None),
+                // read_memory(0, 0x10, 3)
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+            ],
+            memory,
+            handler,
+        )
+        .unwrap();
+
+        for _ in 0..8 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(emulator.registers[Register::C], 3);
+        assert_eq!(&emulator.memory[0x10..0x13], b"yes");
+        assert!(emulator.handler().script().is_finished());
+    }
+
+    #[test]
+    fn test_sys_exit() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 3),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        emulator.step().unwrap();
+        assert_eq!(emulator.step().unwrap(), StepOutcome::Exited(3));
+        assert!(emulator.step().is_err());
+    }
+
+    #[test]
+    fn test_sys_order() {
+        let mut consts = Constants::default();
+
+        let mut memory = Memory::default();
+        memory[0..2].copy_from_slice(b"hi");
+
+        for (exit_first, output) in [(false, &b"hi"[..]), (true, &b""[..])] {
+            if exit_first {
+                consts.syscall.order.reverse();
+            }
+
+            let mut emulator = Emulator::from_instructions_with_handler(
+                consts,
+                vec![
+                    Instruction::IMM(Register::A, 1),
+                    Instruction::IMM(Register::C, 2),
+                    Instruction::SYS(consts.syscall.WRITE | consts.syscall.EXIT, None),
+                ],
+                memory.clone(),
+                VirtualSyscallHandler::default(),
+            )
+            .unwrap();
+
+            let result = emulator.run().unwrap();
+            assert_eq!(result.exit_code(), Some(1));
+            assert_eq!(result.output.unwrap(), output);
+        }
+    }
+
+    #[test]
+    fn test_sys_overflow() {
+        let mut consts = Constants::default();
+
+        for (layout, bytes_read) in [(Layout::Separate, 2), (Layout::Flat, 4)] {
+            consts.semantics.layout = layout;
+
+            let handler = VirtualSyscallHandler::new(b"wxyz".to_vec());
+            let mut emulator = Emulator::from_instructions_with_handler(
+                consts,
+                vec![
+                    Instruction::IMM(Register::B, 0xfe),
+                    Instruction::IMM(Register::C, 4),
+                    Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::D)),
+                    Instruction::IMM(Register::A, 1),
+                    Instruction::IMM(Register::B, 0xff),
+                    Instruction::IMM(Register::C, 3),
+                    Instruction::SYS(consts.syscall.WRITE, None),
+                ],
+                Memory::default(),
+                handler,
+            )
+            .unwrap();
+
+            for _ in 0..3 {
+                emulator.step().unwrap();
+            }
+            assert_eq!(emulator.memory[0xfe..], *b"wx");
+            assert_eq!(emulator.registers[Register::D], bytes_read);
+
+            for _ in 0..4 {
+                emulator.step().unwrap();
+            }
+            match layout {
+                Layout::Separate => assert_eq!(emulator.handler().stdout(), b"x"),
+                Layout::Flat => assert_eq!(emulator.handler().stdout(), b"x\x01\xff"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sys_read_code_into_memory() {
+        let mut consts = Constants::default();
+        consts.semantics.layout = Layout::Flat;
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::B, 0xff),
+                Instruction::IMM(Register::C, 5),
+                Instruction::SYS(consts.syscall.READ_CODE, Some(Register::D)),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::new(b"abcde".to_vec()),
+        )
+        .unwrap();
+
+        emulator
+            .run_with(RunOptions {
+                max_steps: Some(3),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert_eq!(emulator.code.get_instruction(0xff), *b"abc");
+        assert_eq!(emulator.memory[0..2], *b"de");
+    }
+
+    #[test]
+    fn test_sys_sleep_virtual() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 200),
+                Instruction::SYS(consts.syscall.SLEEP, None),
+                Instruction::SYS(consts.syscall.SLEEP | consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        emulator.clock_mut().set_mode(SleepMode::Virtual);
+
+        let result = emulator.run().unwrap();
+        assert_eq!(result.simulated_time, Duration::from_secs(400));
+        assert_eq!(emulator.clock().sleeps(), 2);
+    }
+
+    #[test]
+    fn test_run() {
+        let consts = Constants::default();
+
+        let mut memory = Memory::default();
+        memory[0..2].copy_from_slice(b"hi");
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 1),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.WRITE, Some(Register::D)),
+                Instruction::IMM(Register::A, 0),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            memory,
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            emulator.run().unwrap(),
+            RunResult {
+                stop_reason: StopReason::Exited(0),
+                steps: 5,
+                output: Some(b"hi".to_vec()),
+                watch_log: vec![],
+                simulated_time: Duration::ZERO,
+                coverage: None,
+                profile: None,
+                comparisons: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 1),
+                Instruction::STM(Register::A, Register::A),
+                Instruction::STK(None, Some(Register::A)),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        emulator.step().unwrap();
+        let snapshot = emulator.snapshot();
+
+        emulator.step().unwrap();
+        emulator.step().unwrap();
+        assert_eq!(emulator.memory[1], 1);
+
+        emulator.restore(&snapshot);
+        assert_eq!(emulator.registers[Register::I], 1);
+        assert_eq!(emulator.memory[1], 0);
+        assert_eq!(emulator.stack[0], 0);
+    }
+
+    #[test]
+    fn test_save_load_state() {
+        let consts = Constants::default();
+
+        let mut handler = VirtualSyscallHandler::new("input");
+        handler.add_file("/flag", "pwn.college{fake}");
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // read_memory(0, 0x10, 2)
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::D)),
+                // read_memory(0, 0x12, 3)
+                Instruction::IMM(Register::B, 0x12),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::D)),
+            ],
+            Memory::default(),
+            handler,
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+
+        let mut state = vec![];
+        emulator.save_state(&mut state).unwrap();
+        let mut restored = Emulator::<VirtualSyscallHandler>::load_state(state.as_slice()).unwrap();
+
+        assert_eq!(restored.registers, emulator.registers);
+        assert_eq!(restored.memory, emulator.memory);
+        assert_eq!(restored.code, emulator.code);
+
+        for _ in 0..3 {
+            restored.step().unwrap();
+        }
+        assert_eq!(&restored.memory[0x10..0x15], b"input");
+    }
+
+    #[test]
+    fn test_step_back() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0x10),
+                Instruction::STM(Register::A, Register::A),
+                Instruction::STK(None, Some(Register::A)),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        emulator.record_history(Some(100));
+
+        emulator.run().unwrap();
+        assert_eq!(emulator.history_len(), 4);
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.exit_code(), None);
+        assert_eq!(emulator.registers[Register::I], 3);
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.stack[0], 0);
+        assert_eq!(emulator.registers[Register::S], 0);
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.memory[0x10], 0);
+        assert_eq!(
+            emulator.effects(),
+            [Effect::MemoryWrite {
+                address: 0x10,
+                old: 0,
+                new: 0x10
+            }]
+        );
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.registers, Registers::default());
+        assert!(!emulator.step_back());
+    }
+
+    #[test]
+    fn test_step_back_read_code() {
+        let consts = Constants::default();
+
+        let handler = VirtualSyscallHandler::new(vec![0xff; 3]);
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::B, 2),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.READ_CODE, Some(Register::C)),
+                Instruction::IMM(Register::A, 1),
+            ],
+            Memory::default(),
+            handler,
+        )
+        .unwrap();
+        emulator.record_history(Some(100));
+
+        let original = emulator.code.clone();
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+        assert!(emulator.code.is_instruction_modified(2));
+
+        emulator.step_back();
+        assert_eq!(emulator.code, original);
+    }
+
+    /// Constructs an emulator whose fourth instruction reads "abc" from standard input into memory
+    /// and then fails to write to standard input.
+    fn failing_emulator() -> Emulator<VirtualSyscallHandler> {
+        let consts = Constants::default();
+
+        Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(
+                    consts.syscall.READ_MEMORY | consts.syscall.WRITE,
+                    Some(Register::C),
+                ),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::new("abc"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_failed_step_restores_state() {
+        let mut emulator = failing_emulator();
+
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+        let registers = emulator.registers;
+
+        assert!(emulator.step().is_err());
+        assert_eq!(emulator.registers, registers);
+        assert_eq!(emulator.memory[0x10..0x13], [0; 3]);
+        assert!(emulator.effects().contains(&Effect::MemoryWrite {
+            address: 0x10,
+            old: 0,
+            new: b'a'
+        }));
+    }
+
+    #[test]
+    fn test_failed_step_not_recorded() {
+        let mut emulator = failing_emulator();
+        emulator.record_history(Some(100));
+
+        for _ in 0..3 {
+            emulator.step().unwrap();
+        }
+
+        assert!(emulator.step().is_err());
+        assert_eq!(emulator.history_len(), 3);
+
+        assert!(emulator.step_back());
+        assert_eq!(emulator.registers[Register::I], 2);
+        assert_eq!(emulator.registers[Register::C], 0);
+        assert_eq!(emulator.memory[0x10..0x13], [0; 3]);
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        let consts = Constants::default();
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::B, 1),
+                Instruction::ADD(Register::A, Register::B),
+                Instruction::IMM(Register::I, 1),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        emulator.record_history(Some(100));
+
+        for _ in 0..9 {
+            emulator.step().unwrap();
+        }
+        assert_eq!(emulator.registers[Register::A], 4);
+
+        let steps = emulator.reverse_continue(|e| e.registers[Register::A] == 2);
+        assert_eq!(steps, 4);
+        assert_eq!(emulator.registers[Register::A], 2);
     }
 
-    // TODO: write syscall tests
+    // TODO: write more syscall tests
 }
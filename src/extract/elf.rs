@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+
+/// The section type of sections that occupy no space in the file, such as `.bss`.
+const SHT_NOBITS: u32 = 8;
+/// The section type of the full symbol table.
+const SHT_SYMTAB: u32 = 2;
+/// The section type of the dynamic symbol table.
+const SHT_DYNSYM: u32 = 11;
+/// The section flag of sections that are loaded into memory.
+const SHF_ALLOC: u64 = 0x2;
+/// The section flag of sections that contain machine code.
+const SHF_EXECINSTR: u64 = 0x4;
+/// The symbol type of functions.
+const STT_FUNC: u8 = 2;
+
+/// A section of an ELF file.
+#[derive(Debug, Clone)]
+struct Section {
+    /// The section type.
+    kind: u32,
+    /// The section flags.
+    flags: u64,
+    /// The address the section is loaded at.
+    address: u64,
+    /// The offset of the section's contents in the file.
+    offset: u64,
+    /// The size of the section.
+    size: u64,
+    /// The index of the associated section, such as a symbol table's string table.
+    link: u32,
+}
+
+/// A symbol of an ELF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The symbol's name.
+    pub name: String,
+    /// The symbol's address.
+    pub address: u64,
+    /// The size of the object or function the symbol names.
+    pub size: u64,
+    /// Whether the symbol names a function.
+    pub is_function: bool,
+}
+
+/// A 64-bit little-endian ELF file, parsed just enough to read symbols and loaded sections.
+#[derive(Debug)]
+pub struct Elf<'a> {
+    /// The file's contents.
+    bytes: &'a [u8],
+    /// The file's sections.
+    sections: Vec<Section>,
+    /// The symbols of the full and dynamic symbol tables.
+    symbols: Vec<Symbol>,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses the ELF file `bytes`.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            bail!("Not an ELF file");
+        }
+        if bytes.get(4..6) != Some(&[2, 1]) {
+            bail!("Only 64-bit little-endian ELF files are supported");
+        }
+
+        let section_offset = read_u64(bytes, 0x28)?;
+        let section_size = read_u16(bytes, 0x3a)? as u64;
+        let section_count = read_u16(bytes, 0x3c)? as u64;
+
+        let sections = (0..section_count)
+            .map(|index| {
+                let header = section_offset.saturating_add(index * section_size);
+                let header = bytes.get(header as usize..).unwrap_or_default();
+
+                Ok(Section {
+                    kind: read_u32(header, 0x4)?,
+                    flags: read_u64(header, 0x8)?,
+                    address: read_u64(header, 0x10)?,
+                    offset: read_u64(header, 0x18)?,
+                    size: read_u64(header, 0x20)?,
+                    link: read_u32(header, 0x28)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("Truncated ELF section headers")?;
+
+        let mut elf = Self {
+            bytes,
+            sections,
+            symbols: vec![],
+        };
+        elf.symbols = elf.read_symbols().context("Truncated ELF symbol table")?;
+
+        Ok(elf)
+    }
+
+    /// Reads the symbols of every symbol table.
+    fn read_symbols(&self) -> Result<Vec<Symbol>> {
+        let mut symbols = vec![];
+
+        for table in &self.sections {
+            if table.kind != SHT_SYMTAB && table.kind != SHT_DYNSYM {
+                continue;
+            }
+
+            let strings = self
+                .sections
+                .get(table.link as usize)
+                .context("Missing string table")?;
+            let strings = self.contents(strings)?;
+
+            for entry in self.contents(table)?.chunks_exact(24) {
+                let name = strings
+                    .get(read_u32(entry, 0)? as usize..)
+                    .context("Symbol name out of bounds")?;
+                let name = &name[..name.iter().position(|&byte| byte == 0).unwrap_or(0)];
+
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    address: read_u64(entry, 8)?,
+                    size: read_u64(entry, 16)?,
+                    is_function: entry[4] & 0xf == STT_FUNC,
+                });
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// The contents of `section` in the file.
+    fn contents(&self, section: &Section) -> Result<&'a [u8]> {
+        let end = section.offset.saturating_add(section.size);
+        self.bytes
+            .get(section.offset as usize..end as usize)
+            .context("Section extends past the end of the file")
+    }
+
+    /// Finds the symbol named `name`.
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Finds the function that starts at `address`.
+    pub fn function_at(&self, address: u64) -> Option<&Symbol> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.is_function && symbol.address == address)
+    }
+
+    /// The loaded sections that contain machine code, with the addresses they're loaded at.
+    pub fn code(&self) -> impl Iterator<Item = (u64, &'a [u8])> + '_ {
+        self.sections
+            .iter()
+            .filter(|section| section.flags & SHF_EXECINSTR != 0 && section.kind != SHT_NOBITS)
+            .filter_map(|section| Some((section.address, self.contents(section).ok()?)))
+    }
+
+    /// The loaded section that contains `address`.
+    fn section_at(&self, address: u64) -> Option<&Section> {
+        self.sections.iter().find(|section| {
+            section.flags & SHF_ALLOC != 0
+                && section.address <= address
+                && address - section.address < section.size
+        })
+    }
+
+    /// Reads `length` bytes from memory at `address` once the file is loaded, or `None` if they
+    /// aren't all in one loaded section.
+    pub fn read(&self, address: u64, length: u64) -> Option<Vec<u8>> {
+        let section = self.section_at(address)?;
+        let start = address - section.address;
+        if start.checked_add(length)? > section.size {
+            return None;
+        }
+
+        if section.kind == SHT_NOBITS {
+            return Some(vec![0; length as usize]);
+        }
+
+        self.contents(section)
+            .ok()?
+            .get(start as usize..(start + length) as usize)
+            .map(<[u8]>::to_vec)
+    }
+
+    /// Reads the NUL-terminated string at `address`, if it's at most `max_length` bytes long.
+    pub fn string(&self, address: u64, max_length: usize) -> Option<String> {
+        let section = self.section_at(address)?;
+        if section.kind == SHT_NOBITS {
+            return None;
+        }
+
+        let contents = &self.contents(section).ok()?[(address - section.address) as usize..];
+        let length = contents
+            .iter()
+            .take(max_length + 1)
+            .position(|&byte| byte == 0)?;
+
+        String::from_utf8(contents[..length].to_vec()).ok()
+    }
+
+    /// Reads the value of the integer variable named `name`.
+    pub fn integer(&self, name: &str) -> Option<u64> {
+        let symbol = self.symbol(name)?;
+        let bytes = self.read(symbol.address, symbol.size.min(8))?;
+
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| value << 8 | byte as u64),
+        )
+    }
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(read_array(bytes, offset)?))
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(bytes, offset)?))
+}
+
+/// Reads a little-endian `u64` at `offset`.
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_array(bytes, offset)?))
+}
+
+/// Reads `N` bytes at `offset`.
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N]> {
+    offset
+        .checked_add(N)
+        .and_then(|end| bytes.get(offset..end))
+        .and_then(|bytes| bytes.try_into().ok())
+        .context("Truncated ELF file")
+}
@@ -0,0 +1,624 @@
+/// Parsing of the ELF files that levels ship as.
+mod elf;
+/// Just enough x86-64 decoding to find the values an interpreter checks its operands against.
+mod x86;
+
+use std::{collections::BTreeSet, fmt};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    disasm::disassemble_instruction,
+    infer::{infer_constants, InferOptions},
+    yan85::{
+        code::CODE_SIZE,
+        constants::{
+            ByteOrderConstants, Constants, FlagConstants, JumpSemantics, OpcodeConstants,
+            RegisterConstants, SemanticsConstants, SyscallConstants,
+        },
+        memory::Memory,
+        opcode::Opcode,
+        syscall::Syscall,
+    },
+};
+
+use elf::Elf;
+use x86::Label;
+
+/// The strings the reference VM describes registers with, in the order of
+/// [`Register::ALL`](crate::yan85::register::Register::ALL).
+const REGISTER_NAMES: [&str; 7] = ["a", "b", "c", "d", "s", "i", "f"];
+
+/// The characters the reference VM describes the L, G, E, N, and Z flags with.
+const FLAG_NAMES: [u8; 5] = *b"LGENZ";
+
+/// The longest string worth reading as a label.
+const MAX_STRING_LENGTH: usize = 64;
+
+/// A group of constants in a constants file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+    /// The opcodes.
+    Opcode,
+    /// The registers.
+    Register,
+    /// The syscalls.
+    Syscall,
+    /// The flags.
+    Flag,
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Group::Opcode => "opcode",
+            Group::Register => "register",
+            Group::Syscall => "syscall",
+            Group::Flag => "flag",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// What a level binary contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extraction {
+    /// The Yan85 machine code.
+    pub code: Vec<u8>,
+    /// The initial memory, if the binary has any.
+    pub memory: Option<Memory>,
+    /// The constants the code is encoded with.
+    pub constants: Constants,
+    /// The groups of constants that couldn't be recovered from the interpreter, and were inferred
+    /// from the code instead.
+    pub inferred: Vec<Group>,
+}
+
+/// A constant that a check in the interpreter was found to test for, identified by its index in
+/// its group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Constant {
+    /// An opcode, in the order of [`Opcode::ALL`].
+    Opcode(usize),
+    /// A register, in the order of [`REGISTER_NAMES`].
+    Register(usize),
+    /// A syscall, in the order of [`Syscall::ALL`].
+    Syscall(usize),
+    /// A flag, in the order of [`FLAG_NAMES`].
+    Flag(usize),
+}
+
+/// Extracts the Yan85 code, initial memory, and constants from the level binary `elf`.
+///
+/// The code and memory are read from the `vm_code` and `vm_mem` symbols, so the binary must not
+/// be stripped. Constants are recovered from the interpreter: every test of an operand bit, or
+/// comparison of an operand with a value, is matched with what the code it guards does first,
+/// such as calling `interpret_imm`, loading the string `"a"`, loading a string ending in `open`,
+/// or storing the character `'L'`. Groups of constants that can't be recovered consistently and
+/// completely are inferred from the code with [`infer_constants`], and the byte order is the one
+/// under which the most instructions decode.
+pub fn extract(elf: &[u8]) -> Result<Extraction> {
+    let elf = Elf::parse(elf)?;
+
+    let code_symbol = elf
+        .symbol("vm_code")
+        .context("No vm_code symbol; the binary may be stripped")?;
+    let length = elf
+        .integer("vm_code_length")
+        .map_or(code_symbol.size, |length| length.min(code_symbol.size));
+    // vm_code may be in .bss, whose size isn't limited by the size of the file.
+    if length > CODE_SIZE as u64 {
+        bail!("vm_code is {length:#x} bytes, more than the {CODE_SIZE:#x} bytes of code storage");
+    }
+    let code = elf
+        .read(code_symbol.address, length)
+        .context("vm_code isn't in a loaded section")?;
+
+    let memory = match elf.symbol("vm_mem") {
+        Some(symbol) => {
+            let bytes = elf
+                .read(symbol.address, symbol.size.min(256))
+                .context("vm_mem isn't in a loaded section")?;
+
+            let mut image = [0; 256];
+            image[..bytes.len()].copy_from_slice(&bytes);
+            Some(Memory::from(image))
+        }
+        None => None,
+    };
+
+    let found = find_constants(&elf);
+    let opcodes = resolve::<8>(&found, Constant::Opcode);
+    let registers = resolve::<7>(&found, Constant::Register);
+    let syscalls = resolve::<6>(&found, Constant::Syscall);
+    let flags = resolve::<5>(&found, Constant::Flag);
+
+    let inferred: Vec<Group> = [
+        (Group::Opcode, opcodes.is_none()),
+        (Group::Register, registers.is_none()),
+        (Group::Syscall, syscalls.is_none()),
+        (Group::Flag, flags.is_none()),
+    ]
+    .into_iter()
+    .filter_map(|(group, missing)| missing.then_some(group))
+    .collect();
+
+    let mut constants = match inferred.is_empty() {
+        true => Constants {
+            semantics: SemanticsConstants {
+                jump: JumpSemantics::Reference,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        false => infer_constants(&code, InferOptions::default())
+            .first()
+            .map(|candidate| candidate.constants)
+            .unwrap_or_default(),
+    };
+    apply(&mut constants, opcodes, registers, syscalls, flags);
+    constants.byte_order = best_byte_order(&code, constants);
+
+    Ok(Extraction {
+        code,
+        memory,
+        constants,
+        inferred,
+    })
+}
+
+/// Finds the constants that the interpreter's checks test for, with the values they test.
+fn find_constants(elf: &Elf) -> BTreeSet<(Constant, u8)> {
+    let mut found = BTreeSet::new();
+
+    for (address, code) in elf.code() {
+        for check in x86::checks(code) {
+            let constant =
+                x86::labels(code, address, check.body).find_map(|label| identify(elf, label));
+
+            // Interpreters compare register operands, but test the bits of the others.
+            let consistent =
+                |constant: &Constant| matches!(constant, Constant::Register(_)) == check.compares;
+            if let Some(constant) = constant.filter(consistent) {
+                found.insert((constant, check.value));
+            }
+        }
+    }
+
+    found
+}
+
+/// Identifies the constant that guarded code starting with `label` handles.
+fn identify(elf: &Elf, label: Label) -> Option<Constant> {
+    match label {
+        Label::Call(target) => {
+            let name = elf.function_at(target)?.name.strip_prefix("interpret_")?;
+            Opcode::ALL
+                .iter()
+                .position(|opcode| opcode.to_string().eq_ignore_ascii_case(name))
+                .map(Constant::Opcode)
+        }
+        Label::Load(target) => {
+            let string = elf.string(target, MAX_STRING_LENGTH)?;
+            if let Some(index) = REGISTER_NAMES.iter().position(|&name| name == string) {
+                return Some(Constant::Register(index));
+            }
+
+            // Operations are announced with messages like "[s] IMM %s = %#hhx", and syscalls with
+            // messages like "[s] ... open".
+            let words: Vec<&str> = string.split_whitespace().collect();
+            let opcode = match words[..] {
+                [tag, mnemonic, ..] if tag.starts_with('[') => Opcode::ALL
+                    .iter()
+                    .position(|opcode| opcode.to_string() == mnemonic),
+                _ => None,
+            };
+            let syscall = words.last().and_then(|&name| {
+                Syscall::ALL
+                    .iter()
+                    .position(|syscall| syscall.to_string().eq_ignore_ascii_case(name))
+            });
+
+            opcode
+                .map(Constant::Opcode)
+                .or(syscall.map(Constant::Syscall))
+        }
+        Label::Store(byte) => FLAG_NAMES
+            .iter()
+            .position(|&name| name == byte)
+            .map(Constant::Flag),
+    }
+}
+
+/// Resolves the values of the `N` constants of a group from what was `found`, or returns `None`
+/// if any are missing or inconsistent.
+fn resolve<const N: usize>(
+    found: &BTreeSet<(Constant, u8)>,
+    constant: fn(usize) -> Constant,
+) -> Option<[u8; N]> {
+    let mut values = [0; N];
+    let mut missing = vec![];
+
+    for (index, value) in values.iter_mut().enumerate() {
+        let candidates: Vec<u8> = found
+            .iter()
+            .filter(|(found, _)| *found == constant(index))
+            .map(|&(_, value)| value)
+            .collect();
+
+        match candidates[..] {
+            [candidate] => *value = candidate,
+            [] => missing.push(index),
+            _ => return None,
+        }
+    }
+
+    let known: BTreeSet<u8> = values.iter().copied().filter(|&value| value != 0).collect();
+    if known.len() != N - missing.len() {
+        return None;
+    }
+
+    // Eight constants take all eight bits, so the one whose check wasn't recognized takes the
+    // bit that's left.
+    match missing[..] {
+        [] => {}
+        [index] if N == 8 => {
+            values[index] = (0..8)
+                .map(|bit| 1 << bit)
+                .find(|bit| !known.contains(bit))?;
+        }
+        _ => return None,
+    }
+
+    Some(values)
+}
+
+/// Sets the groups of `constants` that were recovered.
+#[allow(non_snake_case)]
+fn apply(
+    constants: &mut Constants,
+    opcodes: Option<[u8; 8]>,
+    registers: Option<[u8; 7]>,
+    syscalls: Option<[u8; 6]>,
+    flags: Option<[u8; 5]>,
+) {
+    if let Some([IMM, ADD, STK, STM, LDM, CMP, JMP, SYS]) = opcodes {
+        constants.opcode = OpcodeConstants {
+            IMM,
+            ADD,
+            STK,
+            STM,
+            LDM,
+            CMP,
+            JMP,
+            SYS,
+            ..constants.opcode
+        };
+    }
+    if let Some([A, B, C, D, S, I, F]) = registers {
+        constants.register = RegisterConstants {
+            A,
+            B,
+            C,
+            D,
+            S,
+            I,
+            F,
+        };
+    }
+    if let Some([OPEN, READ_CODE, READ_MEMORY, WRITE, SLEEP, EXIT]) = syscalls {
+        constants.syscall = SyscallConstants {
+            OPEN,
+            READ_CODE,
+            READ_MEMORY,
+            WRITE,
+            SLEEP,
+            EXIT,
+            ..constants.syscall
+        };
+    }
+    if let Some([L, G, E, N, Z]) = flags {
+        constants.flag = FlagConstants { L, G, E, N, Z };
+    }
+}
+
+/// The byte order under which the most instructions of `code` decode with `constants`,
+/// preferring the current one on ties.
+fn best_byte_order(code: &[u8], constants: Constants) -> ByteOrderConstants {
+    let decoded = |byte_order: ByteOrderConstants| {
+        code.chunks_exact(3)
+            .filter(|bytes| {
+                let bytes = [bytes[0], bytes[1], bytes[2]];
+                disassemble_instruction(
+                    bytes,
+                    Constants {
+                        byte_order,
+                        ..constants
+                    },
+                )
+                .is_ok()
+            })
+            .count()
+    };
+
+    let mut best = (constants.byte_order, decoded(constants.byte_order));
+    for [op, a, b] in [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ] {
+        let byte_order = ByteOrderConstants { op, a, b };
+        let count = decoded(byte_order);
+        if count > best.1 {
+            best = (byte_order, count);
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::{assemble, parse_asm_file},
+        yan85::{constants::Encodable, register::Register},
+    };
+
+    /// Where the builder places code.
+    const TEXT: u64 = 0x1000;
+    /// Where the builder places functions, after the code that calls them.
+    const FUNCTIONS: u64 = 0x1800;
+    /// Where the builder places strings.
+    const RODATA: u64 = 0x2000;
+    /// Where the builder places variables.
+    const DATA: u64 = 0x3000;
+
+    /// How a check treats its operand.
+    enum Condition {
+        /// Tests for a bit.
+        Test(u8),
+        /// Compares with a value.
+        Compare(u8),
+    }
+
+    /// A level binary under construction.
+    #[derive(Default)]
+    struct Level {
+        /// The contents of `.text`.
+        text: Vec<u8>,
+        /// The number of functions.
+        functions: u64,
+        /// The contents of `.rodata`.
+        rodata: Vec<u8>,
+        /// The contents of `.data`.
+        data: Vec<u8>,
+        /// The name, address, size, and whether it's a function, of each symbol.
+        symbols: Vec<(String, u64, u64, bool)>,
+    }
+
+    impl Level {
+        /// Adds a function that just returns, returning its address.
+        fn function(&mut self, name: &str) -> u64 {
+            let address = FUNCTIONS + self.functions * 0x10;
+            self.functions += 1;
+            self.symbols.push((name.to_string(), address, 1, true));
+            address
+        }
+
+        /// Adds a string, returning its address.
+        fn string(&mut self, string: &str) -> u64 {
+            let address = RODATA + self.rodata.len() as u64;
+            self.rodata.extend(string.bytes().chain([0]));
+            address
+        }
+
+        /// Adds a variable.
+        fn variable(&mut self, name: &str, value: &[u8]) {
+            let address = DATA + self.data.len() as u64;
+            self.data.extend(value);
+            self.symbols
+                .push((name.to_string(), address, value.len() as u64, false));
+        }
+
+        /// Adds a check like unoptimized code does, guarding the code that `body` returns given
+        /// its address.
+        fn check(&mut self, condition: Condition, body: impl FnOnce(u64) -> Vec<u8>) {
+            let (mut code, jump) = match condition {
+                // movzx eax, byte [rbp-0xc]; test al, al; jns
+                Condition::Test(0x80) => (vec![0x0f, 0xb6, 0x45, 0xf4, 0x84, 0xc0], 0x79),
+                // movzx eax, byte [rbp-0xc]; and eax, bit; test eax, eax; je
+                Condition::Test(bit) => (
+                    vec![0x0f, 0xb6, 0x45, 0xf4, 0x83, 0xe0, bit, 0x85, 0xc0],
+                    0x74,
+                ),
+                // cmp byte [rbp-0x4], value; jne
+                Condition::Compare(value) => (vec![0x80, 0x7d, 0xfc, value], 0x75),
+            };
+
+            let body = body(TEXT + (self.text.len() + code.len() + 2) as u64);
+            code.extend([jump, body.len() as u8]);
+            code.extend(body);
+            self.text.extend(code);
+        }
+
+        /// Lays out the ELF file.
+        fn build(mut self) -> Vec<u8> {
+            self.text.resize((FUNCTIONS - TEXT) as usize, 0x90);
+            self.text
+                .resize(self.text.len() + self.functions as usize * 0x10, 0xc3);
+
+            let mut symtab = vec![0; 24];
+            let mut strtab = vec![0];
+            for (name, address, size, is_function) in &self.symbols {
+                symtab.extend((strtab.len() as u32).to_le_bytes());
+                symtab.extend([if *is_function { 0x12 } else { 0x11 }, 0]);
+                symtab.extend(1u16.to_le_bytes());
+                symtab.extend(address.to_le_bytes());
+                symtab.extend(size.to_le_bytes());
+                strtab.extend(name.bytes().chain([0]));
+            }
+
+            // The type, flags, address, contents, and link of each section after the null one.
+            let sections = [
+                (1, 0x6, TEXT, self.text, 0),
+                (1, 0x2, RODATA, self.rodata, 0),
+                (1, 0x3, DATA, self.data, 0),
+                (2, 0x0, 0, symtab, 5),
+                (3, 0x0, 0, strtab, 0),
+            ];
+
+            let mut elf = vec![0; 0x40];
+            let mut headers = vec![0; 0x40];
+            for (kind, flags, address, contents, link) in sections {
+                headers.extend([0; 4]);
+                headers.extend(u32::to_le_bytes(kind));
+                headers.extend(u64::to_le_bytes(flags));
+                headers.extend(address.to_le_bytes());
+                headers.extend((elf.len() as u64).to_le_bytes());
+                headers.extend((contents.len() as u64).to_le_bytes());
+                headers.extend(u32::to_le_bytes(link));
+                headers.extend([0; 20]);
+                elf.extend(contents);
+            }
+
+            elf[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+            let header_offset = elf.len() as u64;
+            elf[0x28..0x30].copy_from_slice(&header_offset.to_le_bytes());
+            elf[0x3a..0x3c].copy_from_slice(&0x40u16.to_le_bytes());
+            elf[0x3c..0x3e].copy_from_slice(&6u16.to_le_bytes());
+            elf.extend(headers);
+
+            elf
+        }
+    }
+
+    /// Encodes a call from `address` to `target`.
+    fn call(address: u64, target: u64) -> Vec<u8> {
+        let displacement = target.wrapping_sub(address + 5) as u32;
+        [0xe8]
+            .into_iter()
+            .chain(displacement.to_le_bytes())
+            .collect()
+    }
+
+    /// Encodes a load of `target`'s address into `rdi` at `address`.
+    fn lea(address: u64, target: u64) -> Vec<u8> {
+        let displacement = target.wrapping_sub(address + 7) as u32;
+        [0x48, 0x8d, 0x3d]
+            .into_iter()
+            .chain(displacement.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_extract() {
+        let consts = Constants {
+            byte_order: ByteOrderConstants { op: 1, a: 2, b: 0 },
+            opcode: OpcodeConstants {
+                IMM: 0x8,
+                ADD: 0x20,
+                STK: 0x1,
+                STM: 0x80,
+                LDM: 0x4,
+                CMP: 0x40,
+                JMP: 0x2,
+                SYS: 0x10,
+                ..Default::default()
+            },
+            register: RegisterConstants {
+                A: 0x10,
+                B: 0x1,
+                C: 0x40,
+                D: 0x8,
+                S: 0x2,
+                I: 0x80,
+                F: 0x20,
+            },
+            syscall: SyscallConstants {
+                OPEN: 0x8,
+                READ_CODE: 0x2,
+                READ_MEMORY: 0x80,
+                WRITE: 0x1,
+                SLEEP: 0x20,
+                EXIT: 0x4,
+                ..Default::default()
+            },
+            flag: FlagConstants {
+                L: 0x2,
+                G: 0x80,
+                E: 0x8,
+                N: 0x20,
+                Z: 0x1,
+            },
+            semantics: SemanticsConstants {
+                jump: JumpSemantics::Reference,
+                ..Default::default()
+            },
+        };
+
+        let mut level = Level::default();
+        for opcode in Opcode::ALL {
+            let name = format!("interpret_{}", opcode.to_string().to_lowercase());
+            let target = level.function(&name);
+            level.check(Condition::Test(opcode.encode(consts)), |address| {
+                call(address, target)
+            });
+        }
+        for (register, name) in Register::ALL.into_iter().zip(REGISTER_NAMES) {
+            let string = level.string(name);
+            level.check(Condition::Compare(register.encode(consts)), |address| {
+                lea(address, string)
+            });
+        }
+        for syscall in Syscall::ALL {
+            let message = format!("[s] ... {}", syscall.to_string().to_lowercase());
+            let string = level.string(&message);
+            level.check(Condition::Test(syscall.encode(consts)), |address| {
+                lea(address, string)
+            });
+        }
+        let flag = consts.flag;
+        for (bit, name) in [flag.L, flag.G, flag.E, flag.N, flag.Z]
+            .into_iter()
+            .zip(FLAG_NAMES)
+        {
+            // mov byte [rax], name
+            level.check(Condition::Test(bit), |_| vec![0xc6, 0x00, name]);
+        }
+
+        let program = "
+            IMM a = 0x30
+            IMM b = 0x8
+            STM *a = b
+            LDM c = *a
+            ADD c b
+            CMP c a
+            JMP N d
+            STK NONE c
+            SYS 0x1 d
+            SYS 0x4 d
+        ";
+        let code = assemble(&parse_asm_file(program.to_string()).unwrap(), consts).unwrap();
+        level.variable("vm_code", &code);
+        level.variable("vm_mem", b"hello");
+
+        let extraction = extract(&level.build()).unwrap();
+        assert_eq!(extraction.code, code);
+        assert_eq!(extraction.memory.unwrap()[0..5], *b"hello");
+        assert_eq!(extraction.constants, consts);
+        assert!(extraction.inferred.is_empty());
+    }
+
+    #[test]
+    fn test_extract_oversized_code() {
+        let mut level = Level::default();
+        level.variable("vm_code", &[0; CODE_SIZE + 3]);
+
+        assert!(extract(&level.build()).is_err());
+    }
+}
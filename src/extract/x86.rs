@@ -0,0 +1,340 @@
+/// How many bytes of code after a check to search for the first label.
+const LABEL_WINDOW: usize = 48;
+
+/// The opcodes of `RET` and unconditional `JMP`s, after which code is unrelated.
+const ENDS: [u8; 3] = [0xc3, 0xe9, 0xeb];
+
+/// A test of operand bits, or a comparison of an operand with a constant, that guards code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Check {
+    /// The bit tested, or the value compared with.
+    pub value: u8,
+    /// Whether the operand is compared with the value, rather than tested for the bit.
+    pub compares: bool,
+    /// The offset of the code that runs when the bit is set or the values are equal.
+    pub body: usize,
+}
+
+/// Something in guarded code that identifies what the guarded code handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    /// A call of the function at an address, such as `interpret_imm`.
+    Call(u64),
+    /// A load of the address of data, such as the string `"a"`.
+    Load(u64),
+    /// A store of a byte, such as the character `'L'`.
+    Store(u8),
+}
+
+/// What a check does with its operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Tests whether a bit is set.
+    Test,
+    /// Compares with a value.
+    Compare,
+    /// Tests whether the sign bit is set, which compilers do instead of testing for 0x80.
+    Sign,
+}
+
+/// Finds the checks in `code`.
+///
+/// Code isn't disassembled from the start of each function. Instead, every offset is tried as the
+/// start of a bit test or comparison followed by a conditional jump, which is how the reference
+/// VM's interpreter dispatches on opcode, register, syscall, and flag bits whether or not it was
+/// compiled with optimizations.
+pub fn checks(code: &[u8]) -> Vec<Check> {
+    (0..code.len())
+        .filter_map(|offset| check_at(code, offset))
+        .collect()
+}
+
+/// Finds labels in the code at `offset` of `code`, which is loaded at `address`, in order.
+///
+/// The search stops at the first byte that could be a `RET` or an unconditional `JMP`, so that it
+/// doesn't run on into unrelated code.
+pub fn labels(code: &[u8], address: u64, offset: usize) -> impl Iterator<Item = Label> + '_ {
+    let end = code.len().min(offset.saturating_add(LABEL_WINDOW));
+    let end = code[offset..end]
+        .iter()
+        .position(|byte| ENDS.contains(byte))
+        .map_or(end, |length| offset + length);
+
+    (offset..end).filter_map(move |offset| label_at(code, address, offset))
+}
+
+/// Decodes a check at `offset`.
+fn check_at(code: &[u8], offset: usize) -> Option<Check> {
+    let (kind, value, mut next) = condition_at(code, offset)?;
+    if kind == Kind::Test && value.count_ones() != 1 {
+        return None;
+    }
+
+    // Unoptimized code tests the result of an AND again before jumping.
+    if let Some(length) = self_test_at(code, next) {
+        next += length;
+    }
+
+    let (condition, length, target) = jump_at(code, next)?;
+    let taken = match (kind, condition) {
+        (Kind::Test, 0x5) | (Kind::Compare, 0x4) | (Kind::Sign, 0x8) => true,
+        (Kind::Test, 0x4) | (Kind::Compare, 0x5) | (Kind::Sign, 0x9) => false,
+        _ => return None,
+    };
+
+    let body = match taken {
+        true => usize::try_from(target)
+            .ok()
+            .filter(|&body| body < code.len())?,
+        false => next + length,
+    };
+
+    Some(Check {
+        value,
+        body,
+        compares: kind == Kind::Compare,
+    })
+}
+
+/// Decodes an instruction at `offset` that tests or compares a byte with a constant, returning
+/// what it does, the constant, and the offset of the next instruction.
+fn condition_at(code: &[u8], offset: usize) -> Option<(Kind, u8, usize)> {
+    let mut next = offset;
+    if (0x40..=0x4f).contains(code.get(next)?) {
+        next += 1;
+    }
+
+    let opcode = *code.get(next)?;
+    next += 1;
+
+    let byte = |at: usize| code.get(at).copied();
+    let dword = |at: usize| -> Option<u8> {
+        let value = u32::from_le_bytes(code.get(at..at + 4)?.try_into().ok()?);
+        u8::try_from(value).ok()
+    };
+
+    match opcode {
+        // TEST AL, imm8; AND AL, imm8; CMP AL, imm8
+        0xa8 | 0x24 => Some((Kind::Test, byte(next)?, next + 1)),
+        0x3c => Some((Kind::Compare, byte(next)?, next + 1)),
+        // TEST EAX, imm32; AND EAX, imm32; CMP EAX, imm32
+        0xa9 | 0x25 => Some((Kind::Test, dword(next)?, next + 4)),
+        0x3d => Some((Kind::Compare, dword(next)?, next + 4)),
+        // TEST r/m8, imm8; AND or CMP r/m8, imm8; AND or CMP r/m32, imm8 or imm32
+        0xf6 | 0x80 | 0x83 | 0x81 => {
+            let modrm = byte(next)?;
+            let kind = match (opcode, modrm >> 3 & 0x7) {
+                (0xf6, 0) | (0x80 | 0x83 | 0x81, 4) => Kind::Test,
+                (0x80 | 0x83 | 0x81, 7) => Kind::Compare,
+                _ => return None,
+            };
+
+            let immediate = next + modrm_length(code, next)?;
+            match opcode {
+                0x81 => Some((kind, dword(immediate)?, immediate + 4)),
+                // The immediate is sign-extended.
+                0x83 if byte(immediate)? >= 0x80 => None,
+                _ => Some((kind, byte(immediate)?, immediate + 1)),
+            }
+        }
+        // TEST r8, r8
+        0x84 => {
+            let modrm = byte(next)?;
+            is_same_register(modrm).then_some((Kind::Sign, 0x80, next + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes `TEST r, r` at `offset`, returning its length.
+fn self_test_at(code: &[u8], offset: usize) -> Option<usize> {
+    let rex = usize::from((0x40..=0x4f).contains(code.get(offset)?));
+    let opcode = *code.get(offset + rex)?;
+    let modrm = *code.get(offset + rex + 1)?;
+
+    (matches!(opcode, 0x84 | 0x85) && is_same_register(modrm)).then_some(rex + 2)
+}
+
+/// Decodes a conditional jump at `offset`, returning its condition code, its length, and the
+/// offset it jumps to.
+fn jump_at(code: &[u8], offset: usize) -> Option<(u8, usize, isize)> {
+    match *code.get(offset)? {
+        opcode @ 0x70..=0x7f => {
+            let displacement = *code.get(offset + 1)? as i8;
+            Some((
+                opcode & 0xf,
+                2,
+                (offset + 2) as isize + displacement as isize,
+            ))
+        }
+        0x0f => {
+            let opcode = *code.get(offset + 1)?;
+            if !(0x80..=0x8f).contains(&opcode) {
+                return None;
+            }
+
+            let displacement =
+                i32::from_le_bytes(code.get(offset + 2..offset + 6)?.try_into().ok()?);
+            Some((
+                opcode & 0xf,
+                6,
+                (offset + 6) as isize + displacement as isize,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a call, a RIP-relative `LEA`, or a store of an immediate byte at `offset`.
+fn label_at(code: &[u8], address: u64, offset: usize) -> Option<Label> {
+    let displacement =
+        |at: usize| Some(i32::from_le_bytes(code.get(at..at + 4)?.try_into().ok()?) as i64);
+    let address_of = |at: usize| address.wrapping_add(at as u64);
+
+    match *code.get(offset)? {
+        // CALL rel32
+        0xe8 => {
+            let target = address_of(offset + 5).wrapping_add_signed(displacement(offset + 1)?);
+            Some(Label::Call(target))
+        }
+        // LEA r64, [RIP + disp32]
+        0x48 | 0x4c if code.get(offset + 1) == Some(&0x8d) => {
+            let modrm = *code.get(offset + 2)?;
+            if modrm & 0xc7 != 0x05 {
+                return None;
+            }
+
+            let target = address_of(offset + 7).wrapping_add_signed(displacement(offset + 3)?);
+            Some(Label::Load(target))
+        }
+        // MOV r/m8, imm8
+        0xc6 => {
+            let modrm = *code.get(offset + 1)?;
+            if modrm >> 3 & 0x7 != 0 {
+                return None;
+            }
+
+            let immediate = offset + 1 + modrm_length(code, offset + 1)?;
+            Some(Label::Store(*code.get(immediate)?))
+        }
+        _ => None,
+    }
+}
+
+/// The length of the ModRM byte at `offset` and the SIB byte and displacement that follow it.
+fn modrm_length(code: &[u8], offset: usize) -> Option<usize> {
+    let modrm = *code.get(offset)?;
+    let mode = modrm >> 6;
+    let rm = modrm & 0x7;
+
+    if mode == 0b11 {
+        return Some(1);
+    }
+
+    let mut length = 1;
+    if rm == 0b100 {
+        let sib = *code.get(offset + 1)?;
+        length += 1;
+        if mode == 0b00 && sib & 0x7 == 0b101 {
+            length += 4;
+        }
+    } else if mode == 0b00 && rm == 0b101 {
+        length += 4;
+    }
+
+    length += match mode {
+        0b01 => 1,
+        0b10 => 4,
+        _ => 0,
+    };
+
+    Some(length)
+}
+
+/// Whether the ModRM byte `modrm` names the same register as both operands.
+fn is_same_register(modrm: u8) -> bool {
+    modrm >> 6 == 0b11 && modrm >> 3 & 0x7 == modrm & 0x7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unoptimized_check() {
+        // movzx eax, byte [rbp-0x14]; and eax, 0x10; test eax, eax; je +0x16;
+        // mov eax, [rbp-0x4]; movsxd rdx, eax; mov byte [rax], 'L'
+        let code = [
+            0x0f, 0xb6, 0x45, 0xec, 0x83, 0xe0, 0x10, 0x85, 0xc0, 0x74, 0x16, 0x8b, 0x45, 0xfc,
+            0x48, 0x63, 0xd0, 0xc6, 0x00, 0x4c,
+        ];
+
+        assert_eq!(
+            checks(&code),
+            [Check {
+                value: 0x10,
+                body: 11,
+                compares: false,
+            }]
+        );
+        assert_eq!(
+            labels(&code, 0x1000, 11).collect::<Vec<_>>(),
+            [Label::Store(b'L')]
+        );
+    }
+
+    #[test]
+    fn test_optimized_checks() {
+        // test bl, 0x20; jne +0x4; test bl, bl; js +0x7; call 0x1000; ret; ret;
+        // lea rdi, [rip+0x10]
+        let code = [
+            0xf6, 0xc3, 0x20, 0x75, 0x04, 0x84, 0xdb, 0x78, 0x07, 0xe8, 0xf2, 0xff, 0xff, 0xff,
+            0xc3, 0xc3, 0x48, 0x8d, 0x3d, 0x10, 0x00, 0x00, 0x00,
+        ];
+
+        assert_eq!(
+            checks(&code),
+            [
+                Check {
+                    value: 0x20,
+                    body: 9,
+                    compares: false,
+                },
+                Check {
+                    value: 0x80,
+                    body: 16,
+                    compares: false,
+                },
+            ]
+        );
+        assert_eq!(
+            labels(&code, 0x1000, 9).collect::<Vec<_>>(),
+            [Label::Call(0x1000)]
+        );
+        assert_eq!(
+            labels(&code, 0x1000, 16).collect::<Vec<_>>(),
+            [Label::Load(0x1027)]
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        // cmp byte [rbp-0x4], 0x8; jne +0x9; lea rax, [rip+0xe39]
+        let code = [
+            0x80, 0x7d, 0xfc, 0x08, 0x75, 0x09, 0x48, 0x8d, 0x05, 0x39, 0x0e, 0x00, 0x00,
+        ];
+
+        assert_eq!(
+            checks(&code),
+            [Check {
+                value: 0x8,
+                body: 6,
+                compares: true,
+            }]
+        );
+        assert_eq!(
+            labels(&code, 0x11c2, 6).collect::<Vec<_>>(),
+            [Label::Load(0x2008)]
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    coverage::Coverage,
+    emulator::{Effect, Emulator, StepOutcome},
+    hook::{Hook, Verdict},
+    syscall_handler::{SyscallHandler, VirtualSyscallHandler},
+    yan85::{register::Register, syscall::Syscall},
+};
+
+/// Bytes that often sit on the boundaries of checks.
+const INTERESTING: [u8; 10] = [0, 1, b'\n', b' ', b'0', b'A', b'a', 0x7f, 0x80, 0xff];
+
+/// Limits on each run of the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzOptions {
+    /// The number of instructions that each run may execute before it counts as hanging.
+    pub max_steps: u64,
+    /// The maximum length of generated inputs.
+    pub max_len: usize,
+    /// The seed for generating inputs.
+    pub seed: u64,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            max_steps: 100_000,
+            max_len: 256,
+            seed: 0,
+        }
+    }
+}
+
+/// Something unusual that a run did for the first time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The emulator failed with the given error.
+    Crash(String),
+    /// The program exited with the given exit code.
+    Exit(u8),
+    /// The program attempted the given system call.
+    Syscall(Syscall),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Crash(message) => write!(f, "crash: {message}"),
+            Finding::Exit(exit_code) => write!(f, "exit with code {exit_code}"),
+            Finding::Syscall(syscall) => write!(f, "first {syscall} syscall"),
+        }
+    }
+}
+
+/// What a single run of the program discovered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Execution {
+    /// Whether the run covered something new, so its input was added to the corpus.
+    pub new_coverage: bool,
+    /// Things the run did that no earlier run did.
+    pub findings: Vec<Finding>,
+}
+
+/// How a run ended.
+enum Outcome {
+    /// The program exited with the given exit code.
+    Exited(u8),
+    /// The emulator failed with the given error.
+    Crashed(String),
+    /// The run reached [`FuzzOptions::max_steps`].
+    Hung,
+}
+
+/// Records every system call that's attempted, including ones that fail.
+struct SyscallLog(Arc<Mutex<Vec<Syscall>>>);
+
+impl<H: SyscallHandler> Hook<H> for SyscallLog {
+    fn on_syscall(&mut self, _emulator: &mut Emulator<H>, syscall: Syscall) -> Verdict<u8> {
+        let mut syscalls = self.0.lock().expect("the log is never poisoned");
+        if !syscalls.contains(&syscall) {
+            syscalls.push(syscall);
+        }
+
+        Verdict::Proceed
+    }
+}
+
+/// A small xorshift generator, so that random searches are reproducible from a seed.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Seeds a generator.
+    pub(crate) fn new(seed: u64) -> Self {
+        // The state must never be zero, or every number generated would be zero too.
+        match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => Self(0x9e37_79b9_7f4a_7c15),
+            state => Self(state),
+        }
+    }
+
+    /// Generates a number.
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Generates a number below `n`, which must be positive.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Generates a byte.
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+}
+
+/// An in-process, coverage-guided mutational fuzzer over standard input.
+///
+/// Each run starts from a clone of the emulator, with a mutated corpus entry as standard input.
+/// Inputs that execute a new instruction, take a new branch direction, or execute an instruction
+/// a new order of magnitude of times are added to the corpus. Values that `CMP` operations
+/// compare are collected into a dictionary that mutations draw bytes from.
+pub struct Fuzzer {
+    /// The program, in its initial state.
+    emulator: Emulator<VirtualSyscallHandler>,
+    /// Limits on each run.
+    options: FuzzOptions,
+    /// The inputs that covered something new.
+    corpus: Vec<Vec<u8>>,
+    /// Pairs of an instruction index and a hit count bucket or branch direction seen so far.
+    features: BTreeSet<(u8, u8)>,
+    /// Bytes that were compared.
+    dictionary: Vec<u8>,
+    /// The crash messages, exit codes, and system calls seen so far.
+    seen: Vec<Finding>,
+    /// The number of runs that hit the step limit.
+    hangs: u64,
+    /// The number of runs performed.
+    runs: u64,
+    /// The input generator.
+    rng: Rng,
+}
+
+impl Fuzzer {
+    /// Constructs a fuzzer for the program loaded in `emulator`, with an empty corpus.
+    pub fn new(emulator: Emulator<VirtualSyscallHandler>, options: FuzzOptions) -> Self {
+        Self {
+            emulator,
+            options,
+            corpus: vec![],
+            features: BTreeSet::new(),
+            dictionary: vec![],
+            seen: vec![],
+            hangs: 0,
+            runs: 0,
+            rng: Rng::new(options.seed),
+        }
+    }
+
+    /// The inputs that covered something new, in the order they were found.
+    pub fn corpus(&self) -> &[Vec<u8>] {
+        &self.corpus
+    }
+
+    /// The number of distinct coverage features seen so far.
+    pub fn features(&self) -> usize {
+        self.features.len()
+    }
+
+    /// The number of runs that hit the step limit.
+    pub fn hangs(&self) -> u64 {
+        self.hangs
+    }
+
+    /// The number of runs performed.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// Runs the program with `input` on standard input, adding the input to the corpus if it
+    /// covered something new.
+    pub fn execute(&mut self, input: Vec<u8>) -> Execution {
+        self.runs += 1;
+
+        let mut emulator = self.emulator.clone();
+        emulator.handler_mut().feed_stdin(&input);
+
+        let syscalls = Arc::new(Mutex::new(vec![]));
+        emulator.add_hook(SyscallLog(syscalls.clone()));
+
+        let mut coverage = Coverage::default();
+        let mut steps = 0;
+
+        let outcome = loop {
+            if steps >= self.options.max_steps {
+                break Outcome::Hung;
+            }
+
+            let index = emulator.registers()[Register::I];
+            let result = emulator.step();
+            steps += 1;
+
+            match result {
+                Ok(StepOutcome::Continued(instruction)) => {
+                    coverage.record(index, Some(&instruction), emulator.effects());
+                }
+                Ok(StepOutcome::Exited(exit_code)) => {
+                    coverage.record(index, None, &[]);
+                    break Outcome::Exited(exit_code);
+                }
+                Err(error) => break Outcome::Crashed(format!("{error:#}")),
+            }
+
+            for effect in emulator.effects() {
+                if let Effect::Comparison { a, b } = *effect {
+                    self.add_to_dictionary(a);
+                    self.add_to_dictionary(b);
+                }
+            }
+        };
+
+        let mut execution = Execution::default();
+
+        let findings = match outcome {
+            Outcome::Exited(exit_code) => vec![Finding::Exit(exit_code)],
+            Outcome::Crashed(message) => vec![Finding::Crash(message)],
+            Outcome::Hung => {
+                self.hangs += 1;
+                vec![]
+            }
+        };
+        let syscalls = syscalls.lock().expect("the log is never poisoned").clone();
+
+        for finding in syscalls.into_iter().map(Finding::Syscall).chain(findings) {
+            if !self.seen.contains(&finding) {
+                self.seen.push(finding.clone());
+                execution.findings.push(finding);
+            }
+        }
+
+        for index in 0..=u8::MAX {
+            let hits = coverage.hits(index);
+            if hits > 0 {
+                // Buckets of 1, 2, 3, 4-7, 8-15, and so on.
+                let bucket = match hits {
+                    1..=3 => hits as u8,
+                    _ => 2 + hits.ilog2() as u8,
+                };
+                execution.new_coverage |= self.features.insert((index, bucket));
+            }
+
+            if let Some(branch) = coverage.branch(index) {
+                if branch.taken > 0 {
+                    execution.new_coverage |= self.features.insert((index, u8::MAX));
+                }
+                if branch.not_taken > 0 {
+                    execution.new_coverage |= self.features.insert((index, u8::MAX - 1));
+                }
+            }
+        }
+
+        if execution.new_coverage {
+            self.corpus.push(input);
+        }
+
+        execution
+    }
+
+    /// Generates an input by applying a few random mutations to a corpus entry.
+    pub fn mutate(&mut self) -> Vec<u8> {
+        let mut input = match self.corpus.len() {
+            0 => vec![],
+            n => self.corpus[self.rng.below(n)].clone(),
+        };
+
+        for _ in 0..1 << self.rng.below(4) {
+            self.mutate_once(&mut input);
+        }
+        input.truncate(self.options.max_len);
+
+        input
+    }
+
+    /// Applies a single random mutation to `input`.
+    fn mutate_once(&mut self, input: &mut Vec<u8>) {
+        let rng = &mut self.rng;
+
+        if input.is_empty() {
+            input.push(rng.byte());
+            return;
+        }
+
+        let position = rng.below(input.len());
+        match rng.below(8) {
+            0 => input[position] ^= 1 << rng.below(8),
+            1 => input[position] = rng.byte(),
+            2 => input[position] = INTERESTING[rng.below(INTERESTING.len())],
+            3 => {
+                let delta = rng.below(16) as u8 + 1;
+                input[position] = match rng.below(2) {
+                    0 => input[position].wrapping_add(delta),
+                    _ => input[position].wrapping_sub(delta),
+                };
+            }
+            4 => input.insert(position, rng.byte()),
+            5 => {
+                input.remove(position);
+            }
+            6 => match self.dictionary.len() {
+                0 => input[position] = rng.byte(),
+                n => input[position] = self.dictionary[rng.below(n)],
+            },
+            _ if self.corpus.is_empty() => input[position] = rng.byte(),
+            _ => {
+                // Splice in the tail of another corpus entry.
+                let other = &self.corpus[rng.below(self.corpus.len())];
+                let start = rng.below(other.len() + 1);
+                input.truncate(position);
+                input.extend_from_slice(&other[start..]);
+            }
+        }
+    }
+
+    /// Adds `value` to the dictionary unless it's already there.
+    fn add_to_dictionary(&mut self, value: u8) {
+        if !self.dictionary.contains(&value) {
+            self.dictionary.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yan85::{
+        constants::Constants, flags::Flags, instruction::Instruction, memory::Memory,
+    };
+
+    #[test]
+    fn test_rng_state_never_zero() {
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+
+        assert_ne!(rng.next(), 0);
+        assert!((0..16).map(|_| rng.below(256)).any(|n| n != 0));
+    }
+
+    #[test]
+    fn test_finds_open() {
+        let consts = Constants::default();
+
+        // Opens a file if the first input byte is 'f', and exits with 1 otherwise.
+        let emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 1),
+                Instruction::SYS(consts.syscall.READ_MEMORY, None),
+                Instruction::LDM(Register::A, Register::B),
+                Instruction::IMM(Register::C, b'f'),
+                Instruction::IMM(Register::D, 11),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::JMP(
+                    Flags {
+                        equal: true,
+                        ..Default::default()
+                    },
+                    Register::D,
+                ),
+                Instruction::IMM(Register::A, 1),
+                Instruction::SYS(consts.syscall.EXIT, None),
+                Instruction::IMM(Register::A, 0x20),
+                Instruction::SYS(consts.syscall.OPEN, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        let mut fuzzer = Fuzzer::new(emulator, FuzzOptions::default());
+        let mut findings = fuzzer.execute(b"a".to_vec()).findings;
+        assert_eq!(
+            findings,
+            [
+                Finding::Syscall(Syscall::ReadMemory),
+                Finding::Syscall(Syscall::Exit),
+                Finding::Exit(1)
+            ]
+        );
+
+        while fuzzer.runs() < 10_000 && !findings.contains(&Finding::Syscall(Syscall::Open)) {
+            let input = fuzzer.mutate();
+            findings.extend(fuzzer.execute(input).findings);
+        }
+
+        assert!(findings.contains(&Finding::Syscall(Syscall::Open)));
+        assert!(findings.iter().any(
+            |finding| matches!(finding, Finding::Crash(message) if message.contains("No such file"))
+        ));
+        assert_eq!(fuzzer.corpus().len(), 2);
+    }
+}
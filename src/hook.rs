@@ -0,0 +1,179 @@
+use crate::{
+    emulator::Emulator,
+    syscall_handler::SyscallHandler,
+    yan85::{instruction::Instruction, syscall::Syscall},
+};
+
+/// A hook's decision on whether an operation should go ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict<T = ()> {
+    /// Perform the operation as usual.
+    Proceed,
+    /// Skip the operation, using the given result in its place.
+    Veto(T),
+}
+
+/// Callbacks that observe and steer an [`Emulator`] as it runs.
+///
+/// Every callback receives the emulator, so it can inspect and modify its state. Callbacks that
+/// return a [`Verdict`] can veto the operation they're called for. When several hooks are added,
+/// they're called in the order they were added until one vetoes.
+///
+/// Changes that hooks make to the machine state aren't recorded as effects, so they aren't undone
+/// by [`Emulator::step_back`] and don't trigger watchpoints.
+#[allow(unused_variables)]
+pub trait Hook<H: SyscallHandler> {
+    /// Called before the instruction `instruction` at index `index` is executed, before register I
+    /// is advanced past it. Vetoing skips the instruction.
+    fn before_instruction(
+        &mut self,
+        emulator: &mut Emulator<H>,
+        index: u8,
+        instruction: &Instruction,
+    ) -> Verdict {
+        Verdict::Proceed
+    }
+
+    /// Called after the instruction `instruction` at index `index` was executed, unless it was
+    /// vetoed or failed.
+    fn after_instruction(
+        &mut self,
+        emulator: &mut Emulator<H>,
+        index: u8,
+        instruction: &Instruction,
+    ) {
+    }
+
+    /// Called before memory at `address` is read, including by system calls. Vetoing reads the
+    /// given value instead.
+    fn on_memory_read(&mut self, emulator: &mut Emulator<H>, address: u8) -> Verdict<u8> {
+        Verdict::Proceed
+    }
+
+    /// Called before `value` is written to memory at `address`, including by system calls.
+    /// Vetoing leaves memory unchanged.
+    fn on_memory_write(&mut self, emulator: &mut Emulator<H>, address: u8, value: u8) -> Verdict {
+        Verdict::Proceed
+    }
+
+    /// Called before `syscall` is performed with the arguments in registers A, B, and C, which the
+    /// hook may change. Vetoing skips the call and returns the given value from it instead.
+    fn on_syscall(&mut self, emulator: &mut Emulator<H>, syscall: Syscall) -> Verdict<u8> {
+        Verdict::Proceed
+    }
+
+    /// Called after `syscall` was performed or vetoed with `arguments`, returning `return_value`
+    /// unless it doesn't return.
+    fn after_syscall(
+        &mut self,
+        emulator: &mut Emulator<H>,
+        syscall: Syscall,
+        arguments: [u8; 3],
+        return_value: Option<u8>,
+    ) {
+    }
+}
+
+/// The hooks added to an emulator.
+///
+/// Hooks aren't part of the machine state, so cloning yields no hooks.
+pub(crate) struct Hooks<H>(pub(crate) Vec<Box<dyn Hook<H> + Send>>);
+
+impl<H> Default for Hooks<H> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<H> Clone for Hooks<H> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, memory::Memory, register::Register},
+    };
+
+    /// Counts instructions, lies about memory at 0x10, protects memory at 0x20, and keeps the
+    /// program from exiting.
+    #[derive(Default)]
+    struct Meddler {
+        /// The number of instructions executed.
+        executed: usize,
+    }
+
+    impl<H: SyscallHandler> Hook<H> for Meddler {
+        fn before_instruction(
+            &mut self,
+            _emulator: &mut Emulator<H>,
+            _index: u8,
+            instruction: &Instruction,
+        ) -> Verdict {
+            match instruction {
+                Instruction::IMM(Register::D, _) => Verdict::Veto(()),
+                _ => Verdict::Proceed,
+            }
+        }
+
+        fn after_instruction(&mut self, _: &mut Emulator<H>, _: u8, _: &Instruction) {
+            self.executed += 1;
+        }
+
+        fn on_memory_read(&mut self, _emulator: &mut Emulator<H>, address: u8) -> Verdict<u8> {
+            match address {
+                0x10 => Verdict::Veto(0x42),
+                _ => Verdict::Proceed,
+            }
+        }
+
+        fn on_memory_write(&mut self, _: &mut Emulator<H>, address: u8, _: u8) -> Verdict {
+            match address {
+                0x20 => Verdict::Veto(()),
+                _ => Verdict::Proceed,
+            }
+        }
+
+        fn on_syscall(&mut self, emulator: &mut Emulator<H>, syscall: Syscall) -> Verdict<u8> {
+            match syscall {
+                Syscall::Exit => Verdict::Veto(emulator.registers()[Register::A] + 1),
+                _ => Verdict::Proceed,
+            }
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let consts = Constants::default();
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0x10),
+                Instruction::LDM(Register::B, Register::A),
+                Instruction::IMM(Register::A, 0x20),
+                Instruction::STM(Register::A, Register::A),
+                Instruction::IMM(Register::D, 0x7),
+                Instruction::SYS(consts.syscall.EXIT, Some(Register::C)),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        emulator.add_hook(Meddler::default());
+
+        for _ in 0..6 {
+            emulator.step().unwrap();
+        }
+
+        assert_eq!(emulator.registers()[Register::B], 0x42);
+        assert_eq!(emulator.memory()[0x20], 0);
+        assert_eq!(emulator.registers()[Register::D], 0);
+        assert_eq!(emulator.registers()[Register::C], 0x21);
+        assert_eq!(emulator.exit_code(), None);
+    }
+}
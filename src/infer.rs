@@ -0,0 +1,614 @@
+use std::io::Write;
+
+use crate::{
+    disasm::disassemble_instruction,
+    fuzz::Rng,
+    yan85::{
+        constants::{
+            ByteOrderConstants, Constants, Decodable, Encodable, FlagConstants, JumpSemantics,
+            OpcodeConstants, RegisterConstants, SemanticsConstants, SyscallConstants,
+        },
+        instruction::Instruction,
+        opcode::Opcode,
+        register::Register,
+        syscall::Syscall,
+    },
+};
+
+/// The values that constants take: each is a single bit.
+const BITS: [u8; 8] = [0x1, 0x2, 0x4, 0x8, 0x10, 0x20, 0x40, 0x80];
+
+/// Settings for a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InferOptions {
+    /// The number of random starting points to improve from for each plausible byte order.
+    pub restarts: usize,
+    /// The number of candidates to return.
+    pub candidates: usize,
+    /// The seed for choosing starting points.
+    pub seed: u64,
+}
+
+impl Default for InferOptions {
+    fn default() -> Self {
+        Self {
+            restarts: 32,
+            candidates: 3,
+            seed: 0,
+        }
+    }
+}
+
+/// A set of constants and how plausible the code looks when decoded with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    /// The constants.
+    pub constants: Constants,
+    /// The plausibility score. Higher is better.
+    pub score: i64,
+    /// The number of instructions that decode.
+    pub decoded: usize,
+}
+
+/// An assignment of bits to constants, with each group listing its constants' bits in order
+/// followed by the bits that no constant of the group uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Assignment {
+    /// The byte order.
+    byte_order: ByteOrderConstants,
+    /// The bits of the opcodes, in the order of [`Opcode::ALL`](crate::yan85::opcode::Opcode::ALL).
+    opcodes: [u8; 8],
+    /// The bits of the registers, in the order of [`Register::ALL`].
+    registers: [u8; 8],
+    /// The bits of the syscalls, in the order of [`Syscall::ALL`].
+    syscalls: [u8; 8],
+    /// The bits of the L, G, E, N, and Z flags.
+    flags: [u8; 8],
+}
+
+impl Assignment {
+    /// Builds the constants that the assignment describes.
+    #[allow(non_snake_case)]
+    fn constants(&self) -> Constants {
+        let [IMM, ADD, STK, STM, LDM, CMP, JMP, SYS] = self.opcodes;
+        let [A, B, C, D, S, I, F, _] = self.registers;
+        let [OPEN, READ_CODE, READ_MEMORY, WRITE, SLEEP, EXIT, ..] = self.syscalls;
+        let [L, G, E, N, Z, ..] = self.flags;
+
+        Constants {
+            byte_order: self.byte_order,
+            opcode: OpcodeConstants {
+                IMM,
+                ADD,
+                STK,
+                STM,
+                LDM,
+                CMP,
+                JMP,
+                SYS,
+                ..Default::default()
+            },
+            register: RegisterConstants {
+                A,
+                B,
+                C,
+                D,
+                S,
+                I,
+                F,
+            },
+            syscall: SyscallConstants {
+                OPEN,
+                READ_CODE,
+                READ_MEMORY,
+                WRITE,
+                SLEEP,
+                EXIT,
+                ..Default::default()
+            },
+            flag: FlagConstants { L, G, E, N, Z },
+            // Levels with single-bit constants run on the reference VM.
+            semantics: SemanticsConstants {
+                jump: JumpSemantics::Reference,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The groups of bits, which searches permute independently.
+    fn groups_mut(&mut self) -> [&mut [u8; 8]; 4] {
+        [
+            &mut self.opcodes,
+            &mut self.registers,
+            &mut self.syscalls,
+            &mut self.flags,
+        ]
+    }
+}
+
+/// Searches for the constants that the level `code` was encoded with.
+///
+/// Assumes that every constant is a single bit, as in the reference VM. Byte orders are ranked by
+/// how many opcode bytes are single bits, and for the most plausible ones, bit assignments are
+/// improved by swapping pairs of bits from random starting points. Candidates are scored by how
+/// many instructions decode, and by heuristics: jumps whose targets were just loaded should land
+/// inside the code, `CMP` should be followed by `JMP`, the registers a `SYS` uses should have just
+/// been loaded, with `WRITE` to fd 1 and `READ_MEMORY` from fd 0, and the code should end with an
+/// `EXIT`. Flags that are only distinguished by the values compared, such as L and G, can come out
+/// swapped.
+///
+/// Returns up to [`InferOptions::candidates`] distinct candidates, best first.
+pub fn infer_constants(code: &[u8], options: InferOptions) -> Vec<Candidate> {
+    // Trailing zeroes are padding rather than instructions.
+    let length = code
+        .chunks_exact(3)
+        .rposition(|bytes| bytes != [0, 0, 0])
+        .map_or(0, |last| last + 1);
+    let instructions: Vec<[u8; 3]> = code
+        .chunks_exact(3)
+        .take(length)
+        .map(|bytes| [bytes[0], bytes[1], bytes[2]])
+        .collect();
+
+    let byte_orders = plausible_byte_orders(&instructions);
+    let mut rng = Rng::new(options.seed);
+    let mut candidates: Vec<Candidate> = vec![];
+
+    for byte_order in byte_orders {
+        for _ in 0..options.restarts {
+            let mut assignment = Assignment {
+                byte_order,
+                opcodes: BITS,
+                registers: BITS,
+                syscalls: BITS,
+                flags: BITS,
+            };
+            for group in assignment.groups_mut() {
+                shuffle(group, &mut rng);
+            }
+
+            let (assignment, score) = climb(&instructions, assignment);
+            let constants = assignment.constants();
+
+            if candidates
+                .iter()
+                .all(|candidate| candidate.constants != constants)
+            {
+                candidates.push(Candidate {
+                    constants,
+                    score,
+                    decoded: decoded(&instructions, constants),
+                });
+            }
+        }
+    }
+
+    candidates.sort_by_key(|candidate| -candidate.score);
+    candidates.truncate(options.candidates);
+
+    candidates
+}
+
+/// The byte orders whose opcode byte is a single bit most often, and whose first operand is a
+/// register, flags, or syscall bits, rather than an immediate, most often.
+fn plausible_byte_orders(instructions: &[[u8; 3]]) -> Vec<ByteOrderConstants> {
+    let single_bits = |position: u8| -> usize {
+        instructions
+            .iter()
+            .filter(|bytes| bytes[position as usize].count_ones() <= 1)
+            .count()
+    };
+
+    let orders = [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ]
+    .map(|[op, a, b]| ByteOrderConstants { op, a, b });
+    let shape = |order: &ByteOrderConstants| (single_bits(order.op), single_bits(order.a));
+
+    let best = orders
+        .iter()
+        .map(shape)
+        .max()
+        .expect("there are byte orders");
+    orders
+        .into_iter()
+        .filter(|order| shape(order) == best)
+        .collect()
+}
+
+/// Shuffles `group` in place.
+fn shuffle(group: &mut [u8; 8], rng: &mut Rng) {
+    for i in (1..group.len()).rev() {
+        group.swap(i, rng.below(i + 1));
+    }
+}
+
+/// Improves `assignment` by swapping pairs of bits within a group until no swap helps, returning
+/// the result and its score.
+fn climb(instructions: &[[u8; 3]], mut assignment: Assignment) -> (Assignment, i64) {
+    let mut best = score(instructions, assignment.constants());
+
+    loop {
+        let mut improved = None;
+
+        for group in 0..4 {
+            for i in 0..8 {
+                for j in i + 1..8 {
+                    let mut neighbor = assignment;
+                    neighbor.groups_mut()[group].swap(i, j);
+
+                    let score = score(instructions, neighbor.constants());
+                    if score > best {
+                        best = score;
+                        improved = Some(neighbor);
+                    }
+                }
+            }
+        }
+
+        match improved {
+            Some(neighbor) => assignment = neighbor,
+            None => return (assignment, best),
+        }
+    }
+}
+
+/// The number of instructions that decode with `constants`.
+fn decoded(instructions: &[[u8; 3]], constants: Constants) -> usize {
+    instructions
+        .iter()
+        .filter(|&&bytes| decode(bytes, constants).is_some())
+        .count()
+}
+
+/// Decodes `bytes` with `constants`.
+///
+/// Most candidate constants leave many instructions undecodable, so invalid opcodes and registers
+/// are ruled out before decoding: building the error is slow when errors capture backtraces.
+fn decode(bytes: [u8; 3], constants: Constants) -> Option<Instruction> {
+    let byte_order = constants.byte_order;
+    let op = bytes[byte_order.op as usize];
+    let a = bytes[byte_order.a as usize];
+    let b = bytes[byte_order.b as usize];
+
+    let is_register = |value: u8| {
+        Register::ALL
+            .iter()
+            .any(|register| register.encode(constants) == value)
+    };
+    let mut covered = 0;
+
+    for opcode in Opcode::ALL {
+        let bit = opcode.encode(constants);
+        if op & bit == 0 {
+            continue;
+        }
+        covered |= bit;
+
+        let valid = match opcode {
+            Opcode::Imm => is_register(a),
+            Opcode::Add | Opcode::Stm | Opcode::Ldm | Opcode::Cmp => {
+                is_register(a) && is_register(b)
+            }
+            Opcode::Stk => (a == 0 || is_register(a)) && (b == 0 || is_register(b)),
+            Opcode::Jmp => is_register(b),
+            Opcode::Sys => b == 0 || is_register(b),
+        };
+        if !valid {
+            return None;
+        }
+    }
+
+    if op == 0 || covered != op {
+        return None;
+    }
+
+    disassemble_instruction(bytes, constants).ok()
+}
+
+/// Scores how plausible the code looks when decoded with `constants`.
+fn score(instructions: &[[u8; 3]], constants: Constants) -> i64 {
+    let decoded: Vec<Option<Instruction>> = instructions
+        .iter()
+        .map(|&bytes| decode(bytes, constants))
+        .collect();
+
+    let mut score = 0;
+    // The values that IMM loaded into each register since it was last overwritten otherwise.
+    let mut loaded: [Option<u8>; 7] = [None; 7];
+
+    for (index, (instruction, bytes)) in decoded.iter().zip(instructions).enumerate() {
+        let Some(instruction) = instruction else {
+            score -= 10;
+            loaded = [None; 7];
+            continue;
+        };
+        score += 10;
+
+        let a = bytes[constants.byte_order.a as usize];
+        for operation in instruction.operations() {
+            score += score_operation(operation, a, &mut loaded, &decoded, index, constants);
+        }
+    }
+
+    let ends_with_exit = matches!(
+        decoded.last(),
+        Some(Some(Instruction::SYS(syscalls, _))) if syscalls & constants.syscall.EXIT != 0
+    );
+    if ends_with_exit {
+        score += 5;
+    }
+
+    score
+}
+
+/// Scores a single operation of the instruction at `index`, whose first operand byte is `a`,
+/// updating the values known to be `loaded`.
+fn score_operation(
+    operation: &Instruction,
+    a: u8,
+    loaded: &mut [Option<u8>; 7],
+    decoded: &[Option<Instruction>],
+    index: usize,
+    constants: Constants,
+) -> i64 {
+    let is_loaded = |loaded: &[Option<u8>; 7], register: Register| loaded[register as usize];
+    let uses_special = |a: Register, b: Register| {
+        [a, b]
+            .iter()
+            .filter(|register| matches!(register, Register::I | Register::F))
+            .count() as i64
+    };
+
+    match *operation {
+        Instruction::IMM(register, value) => {
+            loaded[register as usize] = Some(value);
+            match register {
+                Register::I | Register::F => -2,
+                Register::S => -1,
+                _ => 0,
+            }
+        }
+        Instruction::ADD(a, b) => {
+            // Programs add constants to computed values, but rarely add two constants or double a
+            // register.
+            let score = i64::from(is_loaded(loaded, b).is_some())
+                - i64::from(is_loaded(loaded, a).is_some() && is_loaded(loaded, b).is_some())
+                - i64::from(a == b)
+                - uses_special(a, b);
+            loaded[a as usize] = None;
+            score
+        }
+        Instruction::STK(pop, _) => {
+            if let Some(register) = pop {
+                loaded[register as usize] = None;
+            }
+            0
+        }
+        Instruction::STM(a, b) => {
+            i64::from(is_loaded(loaded, a).is_some()) + i64::from(is_loaded(loaded, b).is_some())
+                - uses_special(a, b)
+        }
+        Instruction::LDM(a, b) => {
+            let score = i64::from(is_loaded(loaded, b).is_some())
+                + i64::from(is_loaded(loaded, a).is_none())
+                - uses_special(a, b);
+            loaded[a as usize] = None;
+            score
+        }
+        Instruction::CMP(a, b) => {
+            let jumps = decoded[index + 1..]
+                .iter()
+                .take(2)
+                .flatten()
+                .flat_map(Instruction::operations)
+                .any(|operation| matches!(operation, Instruction::JMP(..)));
+            2 * i64::from(jumps) - uses_special(a, b)
+        }
+        Instruction::JMP(condition, register) => {
+            let mut score = match is_loaded(loaded, register) {
+                Some(target) if (target as usize) < decoded.len() => 3,
+                Some(_) => -3,
+                None => 0,
+            };
+
+            // Decoding ignores bits that aren't flags.
+            if condition.encode(constants) != a {
+                score -= 5;
+            }
+            if [constants.flag.E, constants.flag.N].contains(&a) {
+                score += 1;
+            }
+
+            score
+        }
+        Instruction::SYS(bits, return_register) => {
+            let syscalls = Vec::<Syscall>::decode(bits, constants).unwrap_or_default();
+            let encoded = syscalls
+                .iter()
+                .fold(0, |encoded, syscall| encoded | syscall.encode(constants));
+
+            let mut score = match (encoded == bits, syscalls.len()) {
+                (false, _) | (_, 0) => -5,
+                (true, 1) => 2,
+                (true, _) => 0,
+            };
+
+            for syscall in syscalls {
+                let fd = is_loaded(loaded, Register::A);
+                // Reads and writes take a buffer address in B and a usually smaller length in C.
+                let transfer = match (
+                    is_loaded(loaded, Register::B),
+                    is_loaded(loaded, Register::C),
+                ) {
+                    (Some(buffer), Some(length)) => 2 + i64::from(length < buffer),
+                    (Some(_), None) | (None, Some(_)) => 1,
+                    (None, None) => 0,
+                };
+
+                score += match syscall {
+                    Syscall::Exit => 2 * i64::from(fd.is_some()),
+                    Syscall::Write => 3 * i64::from(fd == Some(1)) + transfer,
+                    Syscall::ReadMemory => 3 * i64::from(fd == Some(0)) + transfer,
+                    Syscall::ReadCode => i64::from(fd == Some(0)),
+                    Syscall::Open => i64::from(fd.is_some()),
+                    Syscall::Sleep => 0,
+                };
+            }
+
+            if let Some(register) = return_register {
+                loaded[register as usize] = None;
+            }
+
+            score
+        }
+        Instruction::Compound(_) => unreachable!("operations aren't compound"),
+    }
+}
+
+/// Writes `constants` as a constants file.
+pub fn write_constants(constants: &Constants, writer: impl Write) -> serde_yaml::Result<()> {
+    serde_yaml::to_writer(writer, constants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::{assemble, parse_asm_file},
+        yan85::constants::{CmpSemantics, FlagUpdate, Layout, StackLayout, ZeroFlag},
+    };
+
+    /// Prints a prompt, reads a key, compares it with a stored one, and exits.
+    const PROGRAM: &str = "
+            IMM a = 0x50
+            IMM b = 0x4b
+            STM *a = b
+            IMM a = 0x51
+            IMM b = 0x45
+            STM *a = b
+            IMM a = 0x52
+            IMM b = 0x59
+            STM *a = b
+            IMM a = 0x53
+            IMM b = 0x3a
+            STM *a = b
+            IMM a = 0x1
+            IMM b = 0x50
+            IMM c = 0x4
+            SYS 0x4 d
+            IMM a = 0x0
+            IMM b = 0x30
+            IMM c = 0x8
+            SYS 0x10 d
+            IMM a = 0x30
+            IMM b = 0x70
+            IMM c = 0x0
+            IMM d = 0x1
+            LDM a = *a
+            ADD a d
+            LDM b = *b
+            STK NONE d
+            IMM d = 0x2c
+            CMP a b
+            JMP N d
+            STK d NONE
+            IMM b = 0x40
+            ADD c d
+            IMM a = 0x8
+            CMP c a
+            IMM b = 0x29
+            JMP L b
+            IMM a = 0x60
+            IMM b = 0x43
+            STM *a = b
+            IMM a = 0x1
+            IMM b = 0x60
+            IMM c = 0x1
+            SYS 0x4 d
+            IMM a = 0x0
+            SYS 0x2 d
+            IMM a = 0x1
+            SYS 0x2 d
+    ";
+
+    #[test]
+    fn test_infer_constants() {
+        let consts = Constants {
+            byte_order: ByteOrderConstants { op: 2, a: 0, b: 1 },
+            opcode: OpcodeConstants {
+                IMM: 0x20,
+                ADD: 0x4,
+                STK: 0x80,
+                STM: 0x1,
+                LDM: 0x40,
+                CMP: 0x10,
+                JMP: 0x8,
+                SYS: 0x2,
+                ..Default::default()
+            },
+            register: RegisterConstants {
+                A: 0x8,
+                B: 0x40,
+                C: 0x2,
+                D: 0x1,
+                S: 0x80,
+                I: 0x10,
+                F: 0x4,
+            },
+            syscall: SyscallConstants {
+                OPEN: 0x40,
+                READ_CODE: 0x1,
+                READ_MEMORY: 0x10,
+                WRITE: 0x4,
+                SLEEP: 0x8,
+                EXIT: 0x2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let instructions = parse_asm_file(PROGRAM.to_string()).unwrap();
+        let mut code = assemble(&instructions, consts).unwrap();
+        code.resize(0x300, 0);
+
+        let candidates = infer_constants(&code, InferOptions::default());
+        let best = candidates[0];
+
+        assert_eq!(best.decoded, instructions.len());
+        assert_eq!(best.constants.byte_order, consts.byte_order);
+        assert_eq!(best.constants.opcode, consts.opcode);
+        // S, I, and F aren't used, so nothing distinguishes their bits.
+        let registers = |c: Constants| [c.register.A, c.register.B, c.register.C, c.register.D];
+        assert_eq!(registers(best.constants), registers(consts));
+        assert_eq!(best.constants.syscall.READ_MEMORY, consts.syscall.READ_MEMORY);
+        assert_eq!(best.constants.syscall.WRITE, consts.syscall.WRITE);
+        assert_eq!(best.constants.syscall.EXIT, consts.syscall.EXIT);
+    }
+
+    #[test]
+    fn test_write_constants() {
+        let mut consts = Constants::default();
+        consts.opcode.order.reverse();
+        consts.syscall.order.rotate_left(2);
+        consts.semantics = SemanticsConstants {
+            stack: StackLayout::Memory,
+            jump: JumpSemantics::Reference,
+            cmp: CmpSemantics {
+                zero_flag: ZeroFlag::Either,
+                flags: FlagUpdate::Accumulate,
+            },
+            layout: Layout::Flat,
+        };
+
+        let mut yaml = vec![];
+        write_constants(&consts, &mut yaml).unwrap();
+
+        let parsed: Constants = serde_yaml::from_slice(&yaml).unwrap();
+        assert_eq!(parsed, consts);
+    }
+}
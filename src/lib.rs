@@ -4,11 +4,41 @@
 
 /// Yan85 assembler. Converts Yan85 assembly to machine code.
 pub mod asm;
+/// Simulated time for the `SLEEP` syscall.
+pub mod clock;
+/// Instruction and branch coverage of Yan85 programs.
+pub mod coverage;
+/// Byte-by-byte brute forcing of Yan85 input checks.
+pub mod crack;
+/// Interactive Yan85 debugger.
+pub mod debugger;
 /// Yan85 disassembler. Converts Yan85 machine code to assembly.
 pub mod disasm;
 /// Yan85 emulator wrapper that steps through instructions indefinitely.
 pub mod emu;
 /// Yan85 emulator.
 pub mod emulator;
+/// Extraction of Yan85 code, memory, and constants from level binaries.
+pub mod extract;
+/// Coverage-guided fuzzing of Yan85 programs.
+pub mod fuzz;
+/// Callbacks for observing and steering the emulator.
+pub mod hook;
+/// Inference of level constants from unknown Yan85 code.
+pub mod infer;
+/// Execution statistics of Yan85 programs.
+pub mod profile;
+/// Limits and bookkeeping for running Yan85 programs.
+pub mod run;
+/// Symbolic execution that finds input for Yan85 programs.
+pub mod solve;
+/// Backends that service Yan85 system calls.
+pub mod syscall_handler;
+/// Tracking of input bytes through Yan85 programs.
+pub mod taint;
+/// Structured execution traces.
+pub mod trace;
+/// Memory, stack, and register watchpoints.
+pub mod watch;
 /// Yan85 architecture representation structures.
 pub mod yan85;
@@ -1,16 +1,33 @@
 //! Command-line interface to the assembler, disassembler, and emulator.
 
-use std::{fs, path::PathBuf};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{Parser, Subcommand};
 
 use gyan85::{
-    asm::{assemble, parse_asm_file},
+    asm::{assemble, instruction_lines, parse_asm_file},
+    clock::SleepMode,
+    crack::{crack, CrackOptions},
+    debugger::{Debugger, DebuggerOptions},
     disasm::disassemble,
-    emu::emulate,
+    emu::emulate_until,
     emulator::Emulator,
-    yan85::memory::Memory,
+    extract::extract,
+    fuzz::{Finding, FuzzOptions, Fuzzer},
+    infer::{infer_constants, write_constants, InferOptions},
+    run::{RunMonitor, RunOptions, StopReason},
+    solve::{solve, Goal, SolveOptions},
+    syscall_handler::{Script, ScriptedSyscallHandler, VirtualSyscallHandler},
+    trace::Tracer,
+    watch::{Watch, WatchAction, Watchpoint},
+    yan85::{constants::Constants, memory::Memory, register::Register},
 };
 
 /// Supported arguments.
@@ -26,6 +43,8 @@ struct Args {
 }
 
 /// Supported subcommands.
+// Parsed once, so the size of the larger variants doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug, Clone)]
 enum Command {
     /// Assemble Yan85 assembly to machine code.
@@ -48,24 +67,350 @@ enum Command {
     #[clap(alias = "emu", alias = "run")]
     Emulate {
         /// Path of the machine code file to emulate.
-        path: PathBuf,
+        #[clap(required_unless_present = "load_state_path")]
+        path: Option<PathBuf>,
 
         /// Whether to output the disassembly of each instruction emulated.
         #[clap(short = 'd', long)]
         show_disassembly: bool,
 
+        /// Path to an initial Yan85 memory image.
+        #[clap(short = 'm', long = "memory-image", conflicts_with = "load_state_path")]
+        memory_image_path: Option<PathBuf>,
+
+        /// Path to write a JSON Lines trace of every executed instruction to.
+        #[clap(long = "trace")]
+        trace_path: Option<PathBuf>,
+
+        /// Path of a saved machine state to resume instead of starting a fresh program.
+        #[clap(long = "load-state", conflicts_with = "path")]
+        load_state_path: Option<PathBuf>,
+
+        /// Path to save the machine state to when execution reaches `--save-at`.
+        #[clap(long = "save-state", requires = "save_at")]
+        save_state_path: Option<PathBuf>,
+
+        /// Instruction index at which to save the machine state, the first time it's reached.
+        #[clap(long, requires = "save_state_path", value_parser = parse_index)]
+        save_at: Option<u8>,
+
+        /// Stop after executing this many instructions.
+        #[clap(long)]
+        max_steps: Option<u64>,
+
+        /// Stop after this many seconds.
+        #[clap(long, value_parser = parse_seconds)]
+        timeout: Option<Duration>,
+
+        /// Stop and report the loop if the machine state repeats exactly.
+        #[clap(long)]
+        detect_loops: bool,
+
+        /// Stop when a watchpoint is triggered: `memory:<address>[-<end>][:r|w|rw]`,
+        /// `stack:<address>`, or `register:<register>=<value>`. May be repeated.
+        #[clap(long = "watch")]
+        watchpoints: Vec<Watchpoint>,
+
+        /// Report on standard error whenever a watchpoint is triggered, without stopping. Accepts
+        /// the same forms as `--watch`. May be repeated.
+        #[clap(long = "log-watch")]
+        log_watchpoints: Vec<Watchpoint>,
+
+        /// Make `SLEEP` return immediately, only advancing simulated time.
+        #[clap(long, conflicts_with_all = ["sleep_scale", "max_sleep"])]
+        virtual_clock: bool,
+
+        /// Multiply the real time that `SLEEP` takes by this factor.
+        #[clap(long, value_name = "FACTOR")]
+        sleep_scale: Option<f64>,
+
+        /// Limit the real time that each `SLEEP` takes to this many seconds.
+        #[clap(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        max_sleep: Option<Duration>,
+
+        /// Path of a file to use as standard input.
+        #[clap(long, conflicts_with_all = ["input", "script_path"])]
+        stdin_file: Option<PathBuf>,
+
+        /// String to use as standard input.
+        #[clap(long, conflicts_with = "script_path")]
+        input: Option<String>,
+
+        /// Path of an expect/send script that drives standard input and output.
+        #[clap(long = "script")]
+        script_path: Option<PathBuf>,
+
+        /// Path to write a disassembly listing annotated with execution and branch counts to.
+        #[clap(long = "coverage")]
+        coverage_path: Option<PathBuf>,
+
+        /// Path to write an lcov tracefile of execution and branch counts to.
+        #[clap(long = "lcov", requires = "asm_source")]
+        lcov_path: Option<PathBuf>,
+
+        /// Path of the assembly file the program was assembled from, for `--lcov`.
+        #[clap(long, requires = "lcov_path")]
+        asm_source: Option<PathBuf>,
+
+        /// Print instruction, loop, and syscall statistics on standard error once the run ends.
+        #[clap(long)]
+        profile: bool,
+
+        /// Track bytes read from standard input and report on standard error which of them each
+        /// `CMP` compares.
+        #[clap(long)]
+        taint: bool,
+    },
+
+    /// Interactively debug the supplied Yan85 machine code.
+    #[clap(alias = "dbg")]
+    Debug {
+        /// Path of the machine code file to debug.
+        #[clap(required_unless_present = "load_state_path")]
+        path: Option<PathBuf>,
+
+        /// Path to an initial Yan85 memory image.
+        #[clap(short = 'm', long = "memory-image", conflicts_with = "load_state_path")]
+        memory_image_path: Option<PathBuf>,
+
+        /// Path of a saved machine state to resume instead of starting a fresh program.
+        #[clap(long = "load-state", conflicts_with = "path")]
+        load_state_path: Option<PathBuf>,
+
+        /// Number of most recent steps that can be undone.
+        #[clap(long, default_value_t = DebuggerOptions::default().history_limit)]
+        history_limit: usize,
+
+        /// Stop `continue` and `next` after executing this many instructions.
+        #[clap(long, default_value_t = DebuggerOptions::default().max_steps)]
+        max_steps: u64,
+    },
+
+    /// Recover the input that the supplied Yan85 machine code checks for, one byte at a time,
+    /// guided by the comparisons it makes.
+    Crack {
+        /// Path of the machine code file to crack.
+        path: PathBuf,
+
         /// Path to an initial Yan85 memory image.
         #[clap(short = 'm', long = "memory-image")]
         memory_image_path: Option<PathBuf>,
+
+        /// Number of input bytes to recover. Defaults to as many as the program reads.
+        #[clap(long)]
+        length: Option<usize>,
+
+        /// Stop each run after executing this many instructions.
+        #[clap(long, default_value_t = CrackOptions::default().max_steps)]
+        max_steps: u64,
+
+        /// Give up after this many passes over the input bytes.
+        #[clap(long, default_value_t = CrackOptions::default().max_rounds)]
+        max_rounds: usize,
+
+        /// Path to write the recovered input to.
+        #[clap(short, long = "output")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Fuzz the standard input of the supplied Yan85 machine code, guided by coverage.
+    Fuzz {
+        /// Path of the machine code file to fuzz.
+        path: PathBuf,
+
+        /// Path to an initial Yan85 memory image.
+        #[clap(short = 'm', long = "memory-image")]
+        memory_image_path: Option<PathBuf>,
+
+        /// Directory of seed inputs, where inputs that cover something new are also saved.
+        #[clap(long = "corpus")]
+        corpus_path: PathBuf,
+
+        /// Directory to save the first input to crash, exit with each exit code, or attempt each
+        /// syscall to.
+        #[clap(long = "findings", default_value = "findings")]
+        findings_path: PathBuf,
+
+        /// Stop after this many runs.
+        #[clap(long)]
+        runs: Option<u64>,
+
+        /// Stop after this many seconds.
+        #[clap(long, value_parser = parse_seconds)]
+        timeout: Option<Duration>,
+
+        /// Count runs that execute this many instructions as hanging.
+        #[clap(long, default_value_t = FuzzOptions::default().max_steps)]
+        max_steps: u64,
+
+        /// Maximum length of generated inputs.
+        #[clap(long, default_value_t = FuzzOptions::default().max_len)]
+        max_len: usize,
+
+        /// Seed for generating inputs. Defaults to one based on the current time.
+        #[clap(long)]
+        seed: Option<u64>,
+    },
+
+    /// Infer the level constants that the supplied Yan85 machine code was encoded with.
+    InferConstants {
+        /// Path of the machine code file to analyze.
+        path: PathBuf,
+
+        /// Path to write the best candidate's constants file to. Runners-up are written next to
+        /// it, numbered from 2.
+        // Not the `--constants-file` default, which would overwrite a level's constants file.
+        #[clap(short, long = "output", default_value = "inferred.yml")]
+        output_path: PathBuf,
+
+        /// Number of candidates to write.
+        #[clap(long, default_value_t = InferOptions::default().candidates)]
+        candidates: usize,
+
+        /// Number of random starting points to search from for each plausible byte order.
+        #[clap(long, default_value_t = InferOptions::default().restarts)]
+        restarts: usize,
+
+        /// Seed for choosing starting points.
+        #[clap(long, default_value_t = InferOptions::default().seed)]
+        seed: u64,
+    },
+
+    /// Extract the Yan85 machine code, initial memory, and constants from a level binary.
+    Extract {
+        /// Path of the level binary.
+        path: PathBuf,
+
+        /// Path prefix of the files to write: the machine code to `<OUTPUT>.bin`, the memory
+        /// image to `<OUTPUT>.mem`, and the constants file to `<OUTPUT>.yml`. Defaults to the
+        /// path of the level binary.
+        #[clap(short, long = "output")]
+        output_path: Option<PathBuf>,
+    },
+
+    /// Find standard input that makes the supplied Yan85 machine code reach an instruction or
+    /// write a string, by executing it symbolically.
+    Solve {
+        /// Path of the machine code file to solve.
+        path: PathBuf,
+
+        /// Path to an initial Yan85 memory image.
+        #[clap(short = 'm', long = "memory-image")]
+        memory_image_path: Option<PathBuf>,
+
+        /// Instruction index to reach.
+        #[clap(long, value_parser = parse_index, required_unless_present = "write", conflicts_with = "write")]
+        reach: Option<u8>,
+
+        /// String to write to standard output.
+        #[clap(long)]
+        write: Option<String>,
+
+        /// Give up after executing this many instructions, across all paths.
+        #[clap(long, default_value_t = SolveOptions::default().max_steps)]
+        max_steps: u64,
+
+        /// Give up after exploring this many paths.
+        #[clap(long, default_value_t = SolveOptions::default().max_paths)]
+        max_paths: usize,
+
+        /// Path to write the input found to.
+        #[clap(short, long = "output")]
+        output_path: Option<PathBuf>,
     },
 }
 
+/// Parses a decimal or `0x`-prefixed hexadecimal instruction index.
+fn parse_index(value: &str) -> Result<u8> {
+    let index = match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16)?,
+        None => value.parse()?,
+    };
+
+    Ok(index)
+}
+
+/// Pairs each of `watchpoints` with `action`.
+fn watches(watchpoints: Vec<Watchpoint>, action: WatchAction) -> impl Iterator<Item = Watch> {
+    watchpoints
+        .into_iter()
+        .map(move |watchpoint| Watch { watchpoint, action })
+}
+
+/// Parses a possibly fractional number of seconds.
+fn parse_seconds(value: &str) -> Result<Duration> {
+    Ok(Duration::try_from_secs_f64(value.parse()?)?)
+}
+
+/// Reads a 256-byte Yan85 memory image, or returns zeroed memory if no path is given.
+fn read_memory_image(path: Option<&Path>) -> Result<Memory> {
+    match path {
+        Some(path) => {
+            let image: [u8; 256] = fs::read(path)?
+                .try_into()
+                .map_err(|_| anyhow!("Memory image of wrong size"))?;
+
+            Ok(Memory::from(image))
+        }
+        None => Ok(Memory::default()),
+    }
+}
+
+/// Saves `input` to `findings_path` for each of `findings`, numbering crashes from `crashes`.
+fn save_findings(
+    findings_path: &Path,
+    findings: &[Finding],
+    input: &[u8],
+    crashes: &mut usize,
+) -> Result<()> {
+    for finding in findings {
+        let name = match finding {
+            Finding::Crash(_) => {
+                *crashes += 1;
+                format!("crash-{crashes}")
+            }
+            Finding::Exit(exit_code) => format!("exit-{exit_code}"),
+            Finding::Syscall(syscall) => format!("syscall-{}", syscall.to_string().to_lowercase()),
+        };
+
+        let path = findings_path.join(name);
+        fs::write(&path, input)?;
+        eprintln!("Found {finding}: saved to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Constructs an emulator that resumes the state saved at `load_state_path`, or, failing that,
+/// one that starts the program at `path` with the memory image at `memory_image_path`.
+fn load_emulator(
+    consts: Constants,
+    path: Option<&Path>,
+    memory_image_path: Option<&Path>,
+    load_state_path: Option<&Path>,
+) -> Result<Emulator> {
+    if let Some(state_path) = load_state_path {
+        return Emulator::load_state(BufReader::new(File::open(state_path)?));
+    }
+
+    let path = path.expect("clap requires a path unless loading a state");
+    let bytes = fs::read(path)?;
+    let memory = read_memory_image(memory_image_path)?;
+
+    Ok(Emulator::new(consts, bytes.try_into()?, memory))
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    let constants_file = args.constants_file;
-    let yaml = fs::read_to_string(constants_file)?;
-    let consts = serde_yaml::from_str(&yaml).context("Unable to parse constants file")?;
+    let consts = match args.command {
+        // The constants are what's being inferred or extracted.
+        Command::InferConstants { .. } | Command::Extract { .. } => Constants::default(),
+        _ => {
+            let yaml = fs::read_to_string(args.constants_file)?;
+            serde_yaml::from_str(&yaml).context("Unable to parse constants file")?
+        }
+    };
 
     match args.command {
         Command::Assemble {
@@ -75,7 +420,7 @@ fn main() -> Result<()> {
             let asm = fs::read_to_string(input_path)?;
             let instructions = parse_asm_file(asm)?;
 
-            let bytes = assemble(&instructions, consts);
+            let bytes = assemble(&instructions, consts)?;
             fs::write(output_path, bytes)?;
 
             Ok(())
@@ -85,7 +430,7 @@ fn main() -> Result<()> {
             let instructions = disassemble(bytes, consts)?;
 
             for instruction in instructions {
-                println!("{instruction}");
+                println!("{}", instruction.listing(consts.semantics.jump));
             }
 
             Ok(())
@@ -94,22 +439,385 @@ fn main() -> Result<()> {
             path,
             show_disassembly,
             memory_image_path,
+            trace_path,
+            load_state_path,
+            save_state_path,
+            save_at,
+            max_steps,
+            timeout,
+            detect_loops,
+            watchpoints,
+            log_watchpoints,
+            virtual_clock,
+            sleep_scale,
+            max_sleep,
+            stdin_file,
+            input,
+            script_path,
+            coverage_path,
+            lcov_path,
+            asm_source,
+            profile,
+            taint,
         } => {
-            let bytes = fs::read(path)?;
+            let script = match (stdin_file, input, script_path) {
+                (Some(path), _, _) => Script::send(fs::read(path)?),
+                (_, Some(input), _) => Script::send(input),
+                (_, _, Some(path)) => fs::read_to_string(path)?.parse()?,
+                (None, None, None) => Script::default(),
+            };
+
+            let mut emulator = load_emulator(
+                consts,
+                path.as_deref(),
+                memory_image_path.as_deref(),
+                load_state_path.as_deref(),
+            )?
+            .map_handler(|handler| ScriptedSyscallHandler::new(handler, script));
+
+            let sleep_mode = match (virtual_clock, sleep_scale, max_sleep) {
+                (true, _, _) => SleepMode::Virtual,
+                (false, None, None) => SleepMode::Real,
+                (false, factor, max) => SleepMode::Scaled {
+                    factor: factor.unwrap_or(1.0),
+                    max,
+                },
+            };
+            emulator.clock_mut().set_mode(sleep_mode);
+
+            let mut tracer = match trace_path {
+                Some(path) => Some(Tracer::new(BufWriter::new(File::create(path)?))),
+                None => None,
+            };
+
+            let mut monitor = RunMonitor::new(RunOptions {
+                max_steps,
+                timeout,
+                detect_loops,
+                watchpoints: watches(watchpoints, WatchAction::Break)
+                    .chain(watches(log_watchpoints, WatchAction::Log))
+                    .collect(),
+                coverage: coverage_path.is_some() || lcov_path.is_some(),
+                profile,
+                taint,
+            });
+
+            let mut stop_reason = None;
+
+            if let (Some(state_path), Some(index)) = (save_state_path, save_at) {
+                stop_reason = emulate_until(
+                    &mut emulator,
+                    show_disassembly,
+                    tracer.as_mut(),
+                    &mut monitor,
+                    |e| e.registers()[Register::I] == index,
+                )?;
+
+                match stop_reason {
+                    Some(_) => eprintln!(
+                        "The state wasn't saved: instruction {index:#04x} was never reached"
+                    ),
+                    None => emulator.save_state(BufWriter::new(File::create(state_path)?))?,
+                }
+            }
+
+            let stop_reason = match stop_reason {
+                Some(reason) => reason,
+                None => emulate_until(
+                    &mut emulator,
+                    show_disassembly,
+                    tracer.as_mut(),
+                    &mut monitor,
+                    |_| false,
+                )?
+                .expect("emulation only stops early if asked to"),
+            };
+
+            let clock = emulator.clock();
+            if clock.sleeps() > 0 {
+                eprintln!(
+                    "Slept for {:?} of simulated time in {} SLEEP call{}",
+                    clock.elapsed(),
+                    clock.sleeps(),
+                    if clock.sleeps() == 1 { "" } else { "s" }
+                );
+            }
+
+            if let Some(profile) = monitor.profile() {
+                eprint!("{profile}");
+            }
+
+            if let Some(coverage) = monitor.coverage() {
+                // The reports are files, so they shouldn't contain terminal colors.
+                colored::control::set_override(false);
+
+                if let Some(path) = coverage_path {
+                    let writer = BufWriter::new(File::create(path)?);
+                    coverage.write_listing(emulator.code(), emulator.constants(), writer)?;
+                }
+
+                if let (Some(path), Some(source)) = (lcov_path, asm_source) {
+                    let lines = instruction_lines(&fs::read_to_string(&source)?);
+                    coverage.write_lcov(&source, &lines, BufWriter::new(File::create(path)?))?;
+                }
+            }
+
+            match stop_reason {
+                StopReason::Exited(exit_code) => process::exit(exit_code.into()),
+                reason => bail!("The program {reason} after {} steps", monitor.steps()),
+            }
+        }
+        Command::Debug {
+            path,
+            memory_image_path,
+            load_state_path,
+            history_limit,
+            max_steps,
+        } => {
+            let emulator = load_emulator(
+                consts,
+                path.as_deref(),
+                memory_image_path.as_deref(),
+                load_state_path.as_deref(),
+            )?;
+            // Commands are read a byte at a time without holding the lock on standard input, so
+            // that the program's reads from standard input take whatever follows them.
+            let commands = BufReader::with_capacity(1, io::stdin());
+            let options = DebuggerOptions {
+                history_limit,
+                max_steps,
+            };
+            Debugger::new(emulator, options).repl(commands, io::stdout())
+        }
+        Command::Crack {
+            path,
+            memory_image_path,
+            length,
+            max_steps,
+            max_rounds,
+            output_path,
+        } => {
+            let emulator = load_emulator(consts, Some(&path), memory_image_path.as_deref(), None)?
+                .map_handler(|_| VirtualSyscallHandler::default());
+
+            let report = crack(
+                &emulator,
+                CrackOptions {
+                    length,
+                    max_steps,
+                    max_rounds,
+                },
+            );
+            eprintln!(
+                "{} matching comparisons after {} runs; {}",
+                report.progress.matches,
+                report.runs,
+                match report.exit_code {
+                    Some(code) => format!("the program exited with code {code}"),
+                    None => "the program didn't exit".to_string(),
+                }
+            );
+
+            println!("{}", report.key.escape_ascii());
+            if let Some(path) = output_path {
+                fs::write(path, report.key)?;
+            }
+
+            Ok(())
+        }
+        Command::Fuzz {
+            path,
+            memory_image_path,
+            corpus_path,
+            findings_path,
+            runs,
+            timeout,
+            max_steps,
+            max_len,
+            seed,
+        } => {
+            let emulator = load_emulator(consts, Some(&path), memory_image_path.as_deref(), None)?
+                .map_handler(|_| VirtualSyscallHandler::default());
+            let seed = seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+            });
+
+            fs::create_dir_all(&corpus_path)?;
+            fs::create_dir_all(&findings_path)?;
+
+            let mut fuzzer = Fuzzer::new(
+                emulator,
+                FuzzOptions {
+                    max_steps,
+                    max_len,
+                    seed,
+                },
+            );
+            let mut crashes = 0;
+
+            let mut seeds = fs::read_dir(&corpus_path)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            seeds.sort();
+
+            for seed_path in seeds {
+                let input = fs::read(seed_path)?;
+                let execution = fuzzer.execute(input.clone());
+                save_findings(&findings_path, &execution.findings, &input, &mut crashes)?;
+            }
 
-            let memory = match memory_image_path {
-                Some(path) => {
-                    let image: [u8; 256] = fs::read(path)?
-                        .try_into()
-                        .map_err(|_| anyhow!("Memory image of wrong size"))?;
+            let start = Instant::now();
+            let mut last_status = start;
+            let status = |fuzzer: &Fuzzer| {
+                eprintln!(
+                    "{} runs, {} corpus entries, {} coverage features, {} hangs",
+                    fuzzer.runs(),
+                    fuzzer.corpus().len(),
+                    fuzzer.features(),
+                    fuzzer.hangs()
+                )
+            };
+
+            while runs.is_none_or(|runs| fuzzer.runs() < runs)
+                && timeout.is_none_or(|timeout| start.elapsed() < timeout)
+            {
+                let input = fuzzer.mutate();
+                let execution = fuzzer.execute(input.clone());
+
+                if execution.new_coverage {
+                    let name = format!("id-{:06}", fuzzer.runs());
+                    fs::write(corpus_path.join(name), &input)?;
+                }
+                save_findings(&findings_path, &execution.findings, &input, &mut crashes)?;
 
-                    Memory::from(image)
+                if last_status.elapsed() >= Duration::from_secs(5) {
+                    status(&fuzzer);
+                    last_status = Instant::now();
                 }
-                None => Memory::default(),
+            }
+
+            status(&fuzzer);
+            Ok(())
+        }
+        Command::InferConstants {
+            path,
+            output_path,
+            candidates,
+            restarts,
+            seed,
+        } => {
+            let code = fs::read(path)?;
+            let candidates = infer_constants(
+                &code,
+                InferOptions {
+                    restarts,
+                    candidates,
+                    seed,
+                },
+            );
+
+            for (rank, candidate) in (1..).zip(candidates) {
+                let path = match rank {
+                    1 => output_path.clone(),
+                    _ => output_path.with_extension(format!("{rank}.yml")),
+                };
+                write_constants(&candidate.constants, BufWriter::new(File::create(&path)?))?;
+
+                println!(
+                    "{rank}. {}: score {}, {} instructions decode",
+                    path.display(),
+                    candidate.score,
+                    candidate.decoded
+                );
+            }
+
+            Ok(())
+        }
+        Command::Extract { path, output_path } => {
+            let extraction = extract(&fs::read(&path)?)?;
+
+            let output_path = output_path.unwrap_or(path);
+            let with_suffix = |suffix: &str| {
+                let mut path = output_path.clone().into_os_string();
+                path.push(suffix);
+                PathBuf::from(path)
             };
+            let code_path = with_suffix(".bin");
+            let memory_path = with_suffix(".mem");
+            let constants_path = with_suffix(".yml");
 
-            let mut emulator = Emulator::new(consts, bytes.try_into()?, memory);
-            emulate(&mut emulator, show_disassembly)?;
+            let memory = extraction.memory.unwrap_or_else(|| {
+                eprintln!("No vm_mem symbol; the memory image is empty");
+                Memory::default()
+            });
+            for group in &extraction.inferred {
+                eprintln!(
+                    "Couldn't recover the {group} constants from the interpreter; inferred them \
+                     from the code instead"
+                );
+            }
+
+            fs::write(&code_path, &extraction.code)?;
+            fs::write(&memory_path, &memory[0..])?;
+            write_constants(
+                &extraction.constants,
+                BufWriter::new(File::create(&constants_path)?),
+            )?;
+
+            println!(
+                "Wrote {}, {}, and {}",
+                code_path.display(),
+                memory_path.display(),
+                constants_path.display()
+            );
+
+            Ok(())
+        }
+        Command::Solve {
+            path,
+            memory_image_path,
+            reach,
+            write,
+            max_steps,
+            max_paths,
+            output_path,
+        } => {
+            let emulator = load_emulator(consts, Some(&path), memory_image_path.as_deref(), None)?;
+            let goal = match (reach, write) {
+                (Some(index), _) => Goal::Reach(index),
+                (None, Some(string)) => Goal::Write(string.into_bytes()),
+                (None, None) => unreachable!("clap requires a goal"),
+            };
+
+            let report = solve(
+                &emulator,
+                &goal,
+                SolveOptions {
+                    max_steps,
+                    max_paths,
+                    ..Default::default()
+                },
+            );
+            eprintln!(
+                "Explored {} path{} in {} steps",
+                report.paths,
+                if report.paths == 1 { "" } else { "s" },
+                report.steps
+            );
+
+            let Some(input) = report.input else {
+                match report.incomplete {
+                    0 => bail!("No input achieves the goal"),
+                    n => bail!("No input found; {n} paths were cut short by limits, the solver giving up, or unsupported operations"),
+                }
+            };
+
+            println!("{}", input.escape_ascii());
+            if let Some(path) = output_path {
+                fs::write(path, input)?;
+            }
 
             Ok(())
         }
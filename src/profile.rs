@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    emulator::Effect,
+    yan85::{instruction::Instruction, opcode::Opcode, syscall::Syscall},
+};
+
+/// The number of entries shown in each ranking of a profile report.
+const TOP: usize = 10;
+
+/// How often a system call was made and how much data it moved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallStats {
+    /// The number of calls.
+    pub calls: u64,
+    /// The total number of bytes read or written, for the calls that transfer data.
+    pub bytes: u64,
+}
+
+/// Execution statistics for finding where a program spends its time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The number of instructions executed.
+    steps: u64,
+    /// The number of operations executed for each opcode, in the order of [`Opcode::ALL`].
+    /// Compound instructions count once for each of their operations.
+    opcodes: [u64; 8],
+    /// The number of times each instruction index was executed.
+    hits: [u64; 256],
+    /// The instruction most recently executed at each index.
+    instructions: BTreeMap<u8, Instruction>,
+    /// The number of backward jumps taken, by the jump target and the index of the jump.
+    loops: BTreeMap<(u8, u8), u64>,
+    /// Statistics for each system call, in the order of [`Syscall::ALL`].
+    syscalls: [SyscallStats; 6],
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            steps: 0,
+            opcodes: [0; 8],
+            hits: [0; 256],
+            instructions: BTreeMap::new(),
+            loops: BTreeMap::new(),
+            syscalls: [SyscallStats::default(); 6],
+        }
+    }
+}
+
+impl Profile {
+    /// Records that `instruction` was executed at `index` with the side effects `effects`, after
+    /// which execution continued at `next_index`.
+    pub fn record(
+        &mut self,
+        index: u8,
+        instruction: Option<&Instruction>,
+        next_index: u8,
+        effects: &[Effect],
+    ) {
+        self.steps += 1;
+        self.hits[index as usize] += 1;
+
+        for effect in effects {
+            if let Effect::Syscall {
+                syscall,
+                return_value,
+                ..
+            } = *effect
+            {
+                let stats = &mut self.syscalls[position(&Syscall::ALL, syscall)];
+                stats.calls += 1;
+
+                if matches!(
+                    syscall,
+                    Syscall::ReadCode | Syscall::ReadMemory | Syscall::Write
+                ) {
+                    stats.bytes += u64::from(return_value.unwrap_or(0));
+                }
+            }
+        }
+
+        let Some(instruction) = instruction else {
+            return;
+        };
+
+        for operation in instruction.operations() {
+            if let Some(opcode) = operation.opcode() {
+                self.opcodes[position(&Opcode::ALL, opcode)] += 1;
+            }
+
+            // A jump that wasn't taken continues at the next index, which wraps to 0 at the end of
+            // the code.
+            if matches!(operation, Instruction::JMP(..))
+                && next_index != index.wrapping_add(1)
+                && next_index <= index
+            {
+                *self.loops.entry((next_index, index)).or_default() += 1;
+            }
+        }
+
+        self.instructions.insert(index, instruction.clone());
+    }
+
+    /// The number of instructions executed.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The number of operations executed with `opcode`.
+    pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+        self.opcodes[position(&Opcode::ALL, opcode)]
+    }
+
+    /// The number of times the instruction at `index` was executed.
+    pub fn hits(&self, index: u8) -> u64 {
+        self.hits[index as usize]
+    }
+
+    /// The statistics for `syscall`.
+    pub fn syscall(&self, syscall: Syscall) -> SyscallStats {
+        self.syscalls[position(&Syscall::ALL, syscall)]
+    }
+
+    /// The loops that were run, as the range of instruction indices from the target of a backward
+    /// jump to the jump itself, with the number of times the jump was taken. The most frequent
+    /// loops come first.
+    pub fn loops(&self) -> Vec<((u8, u8), u64)> {
+        let mut loops: Vec<_> = self
+            .loops
+            .iter()
+            .map(|(&range, &count)| (range, count))
+            .collect();
+        loops.sort_by(|(_, a), (_, b)| b.cmp(a));
+        loops
+    }
+
+    /// The executed instruction indices with their execution counts, most frequent first.
+    pub fn hottest(&self) -> Vec<(u8, u64)> {
+        let mut hottest: Vec<_> = (0..=u8::MAX)
+            .map(|index| (index, self.hits(index)))
+            .filter(|&(_, hits)| hits > 0)
+            .collect();
+        hottest.sort_by(|(_, a), (_, b)| b.cmp(a));
+        hottest
+    }
+
+    /// The percentage of executed instructions that `count` represents.
+    fn percentage(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.steps.max(1) as f64
+    }
+}
+
+/// The position of `item` in `all`, which must contain it.
+fn position<T: PartialEq>(all: &[T], item: T) -> usize {
+    all.iter()
+        .position(|candidate| *candidate == item)
+        .expect("every variant is listed")
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Executed {} steps", self.steps)?;
+
+        writeln!(f, "\nInstruction mix:")?;
+        for opcode in Opcode::ALL {
+            let count = self.opcode_count(opcode);
+            if count > 0 {
+                writeln!(
+                    f,
+                    "  {opcode:<5} {count:>12}  {:5.1}%",
+                    self.percentage(count)
+                )?;
+            }
+        }
+
+        writeln!(f, "\nHottest instructions:")?;
+        for (index, hits) in self.hottest().into_iter().take(TOP) {
+            write!(
+                f,
+                "  {index:#04x}  {hits:>12}  {:5.1}%",
+                self.percentage(hits)
+            )?;
+            if let Some(instruction) = self.instructions.get(&index) {
+                write!(f, "  {instruction}")?;
+            }
+            writeln!(f)?;
+        }
+
+        let loops = self.loops();
+        if !loops.is_empty() {
+            writeln!(f, "\nHottest loops:")?;
+            for ((start, end), jumps) in loops.into_iter().take(TOP) {
+                writeln!(f, "  {start:#04x}-{end:#04x}  {jumps:>12} jumps back")?;
+            }
+        }
+
+        if self.syscalls.iter().any(|stats| stats.calls > 0) {
+            writeln!(f, "\nSystem calls:")?;
+            for syscall in Syscall::ALL {
+                let SyscallStats { calls, bytes } = self.syscall(syscall);
+                if calls == 0 {
+                    continue;
+                }
+
+                write!(f, "  {syscall:<11} {calls:>12} calls")?;
+                if matches!(
+                    syscall,
+                    Syscall::ReadCode | Syscall::ReadMemory | Syscall::Write
+                ) {
+                    write!(f, "  {bytes} bytes")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yan85::{flags::Flags, register::Register};
+
+    #[test]
+    fn test_record() {
+        let jump = Instruction::JMP(Flags::default(), Register::D);
+        let write = Effect::Syscall {
+            syscall: Syscall::Write,
+            arguments: [1, 0, 4],
+            return_value: Some(4),
+        };
+
+        let mut profile = Profile::default();
+        profile.record(0, Some(&Instruction::IMM(Register::D, 0)), 1, &[]);
+        profile.record(1, Some(&jump), 0, &[]);
+        profile.record(0, Some(&Instruction::IMM(Register::D, 2)), 1, &[]);
+        profile.record(1, Some(&jump), 2, &[]);
+        profile.record(2, Some(&Instruction::SYS(0, None)), 3, &[write]);
+
+        assert_eq!(profile.steps(), 5);
+        assert_eq!(profile.opcode_count(Opcode::Imm), 2);
+        assert_eq!(profile.opcode_count(Opcode::Jmp), 2);
+        assert_eq!(profile.hottest(), vec![(0, 2), (1, 2), (2, 1)]);
+        assert_eq!(profile.loops(), vec![((0, 1), 1)]);
+        assert_eq!(
+            profile.syscall(Syscall::Write),
+            SyscallStats { calls: 1, bytes: 4 }
+        );
+        assert_eq!(profile.syscall(Syscall::Open), SyscallStats::default());
+    }
+
+    #[test]
+    fn test_report() {
+        let mut profile = Profile::default();
+        profile.record(0, Some(&Instruction::IMM(Register::A, 1)), 1, &[]);
+
+        let report = profile.to_string();
+
+        assert!(report.starts_with("Executed 1 steps\n"));
+        assert!(report.contains("  IMM              1  100.0%"));
+        assert!(!report.contains("Hottest loops"));
+        assert!(!report.contains("System calls"));
+    }
+
+    #[test]
+    fn test_not_taken_jump_at_end_is_not_loop() {
+        let mut profile = Profile::default();
+        profile.record(
+            0xff,
+            Some(&Instruction::JMP(Flags::default(), Register::D)),
+            0,
+            &[],
+        );
+
+        assert_eq!(profile.loops(), vec![]);
+    }
+}
@@ -0,0 +1,582 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    coverage::Coverage,
+    emulator::{Effect, Emulator, StepOutcome},
+    profile::Profile,
+    syscall_handler::SyscallHandler,
+    taint::{Comparison, TaintTracker},
+    watch::{self, Watch, WatchAction, WatchHit},
+    yan85::{
+        code::Code, instruction::Instruction, memory::Memory, register::Register,
+        registers::Registers, stack::Stack,
+    },
+};
+
+/// Limits on how long a program may run, and conditions to watch for while it does.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RunOptions {
+    /// Stop after executing this many instructions.
+    pub max_steps: Option<u64>,
+    /// Stop once this much wall-clock time has passed.
+    pub timeout: Option<Duration>,
+    /// Stop once the complete machine state repeats exactly, which proves that the program loops
+    /// forever.
+    ///
+    /// Loops that perform system calls are never reported, since the outside world may break them.
+    pub detect_loops: bool,
+    /// Memory, stack, and register conditions to break on or log.
+    pub watchpoints: Vec<Watch>,
+    /// Collect per-instruction execution and branch counts.
+    pub coverage: bool,
+    /// Collect execution statistics.
+    pub profile: bool,
+    /// Track bytes read from standard input and log the comparisons they reach.
+    pub taint: bool,
+}
+
+/// The reason a run stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The program terminated via the `EXIT` syscall with the given exit code.
+    Exited(u8),
+    /// The run reached [`RunOptions::max_steps`].
+    StepLimit,
+    /// The run reached [`RunOptions::timeout`].
+    Timeout,
+    /// The machine state repeated exactly, so the program loops forever.
+    InfiniteLoop {
+        /// The lowest instruction index executed within the loop.
+        start: u8,
+        /// The highest instruction index executed within the loop.
+        end: u8,
+    },
+    /// An instruction triggered a watchpoint with [`WatchAction::Break`].
+    Watchpoint(WatchHit),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Exited(exit_code) => write!(f, "exited with code {exit_code}"),
+            StopReason::StepLimit => write!(f, "reached the step limit"),
+            StopReason::Timeout => write!(f, "timed out"),
+            StopReason::InfiniteLoop { start, end } => write!(
+                f,
+                "loops forever between instructions {start:#04x} and {end:#04x}"
+            ),
+            StopReason::Watchpoint(hit) => write!(f, "stopped at {hit}"),
+        }
+    }
+}
+
+/// The parts of the machine state that determine future execution, absent system calls.
+#[derive(Debug, Clone)]
+struct MachineState {
+    /// The registers.
+    registers: Registers,
+    /// The memory.
+    memory: Memory,
+    /// The stack.
+    stack: Stack,
+    /// The code.
+    code: Code,
+}
+
+impl MachineState {
+    /// Captures the machine state of `emulator`.
+    fn capture<H: SyscallHandler>(emulator: &Emulator<H>) -> Self {
+        Self {
+            registers: *emulator.registers(),
+            memory: emulator.memory().clone(),
+            stack: emulator.stack().clone(),
+            code: emulator.code().clone(),
+        }
+    }
+
+    /// Checks whether `emulator` is in exactly this state.
+    fn matches<H: SyscallHandler>(&self, emulator: &Emulator<H>) -> bool {
+        self.registers == *emulator.registers()
+            && self.memory == *emulator.memory()
+            && self.stack == *emulator.stack()
+            && self.code == *emulator.code()
+    }
+}
+
+/// Detects exactly repeating machine states with Brent's cycle detection algorithm, which needs
+/// only one saved state at a time.
+#[derive(Debug, Default)]
+struct LoopDetector {
+    /// The state that later states are compared against.
+    saved: Option<MachineState>,
+    /// The number of steps after which the saved state is replaced.
+    power: u64,
+    /// The instruction indices reached since the saved state.
+    indices: Vec<u8>,
+}
+
+impl LoopDetector {
+    /// Forgets the saved state, starting detection afresh.
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Records the state after a step, returning the range of instruction indices in the loop if
+    /// the state has been seen before.
+    fn record<H: SyscallHandler>(&mut self, emulator: &Emulator<H>) -> Option<(u8, u8)> {
+        let Some(saved) = &self.saved else {
+            self.saved = Some(MachineState::capture(emulator));
+            self.power = 1;
+            return None;
+        };
+
+        self.indices.push(emulator.registers()[Register::I]);
+
+        if saved.matches(emulator) {
+            let start = *self.indices.iter().min().expect("a step was just recorded");
+            let end = *self.indices.iter().max().expect("a step was just recorded");
+            return Some((start, end));
+        }
+
+        if self.indices.len() as u64 == self.power {
+            self.saved = Some(MachineState::capture(emulator));
+            self.power *= 2;
+            self.indices.clear();
+        }
+
+        None
+    }
+}
+
+/// Tracks the progress of a run against its [`RunOptions`].
+#[derive(Debug)]
+pub struct RunMonitor {
+    /// The limits being enforced.
+    options: RunOptions,
+    /// When the run started.
+    start: Instant,
+    /// The number of instructions executed.
+    steps: u64,
+    /// The loop detector, if loop detection is enabled.
+    loop_detector: Option<LoopDetector>,
+    /// The registers and decoded instruction before the instruction being executed, if there are
+    /// watchpoints to check, coverage or a profile to collect, or taint to track.
+    before: Option<(Registers, Option<Instruction>)>,
+    /// Hits of watchpoints with [`WatchAction::Log`] that haven't been taken yet.
+    logged: Vec<WatchHit>,
+    /// The execution counts, if coverage collection is enabled.
+    coverage: Option<Coverage>,
+    /// The execution statistics, if profiling is enabled.
+    profile: Option<Profile>,
+    /// The taint tracker, if taint tracking is enabled.
+    taint: Option<TaintTracker>,
+}
+
+impl RunMonitor {
+    /// Starts monitoring a run with the given limits.
+    pub fn new(options: RunOptions) -> Self {
+        Self {
+            loop_detector: options.detect_loops.then(LoopDetector::default),
+            start: Instant::now(),
+            steps: 0,
+            before: None,
+            logged: Vec::new(),
+            coverage: options.coverage.then(Coverage::default),
+            profile: options.profile.then(Profile::default),
+            taint: options.taint.then(TaintTracker::default),
+            options,
+        }
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Takes the watchpoint hits logged since the last call, in the order they occurred.
+    pub fn take_logged(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.logged)
+    }
+
+    /// The execution counts collected so far, if coverage collection is enabled.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Takes the execution counts collected so far, if coverage collection is enabled.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// The execution statistics collected so far, if profiling is enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Takes the execution statistics collected so far, if profiling is enabled.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Takes the comparisons of values derived from input logged since the last call, in the order
+    /// they occurred.
+    pub fn take_comparisons(&mut self) -> Vec<Comparison> {
+        self.taint
+            .as_mut()
+            .map_or_else(Vec::new, TaintTracker::take_comparisons)
+    }
+
+    /// Checks whether a limit has been reached before `emulator` executes its next instruction,
+    /// and notes the state that watchpoints, coverage, the profile, and taint are checked against.
+    pub fn before_step<H: SyscallHandler>(&mut self, emulator: &Emulator<H>) -> Option<StopReason> {
+        if self.options.max_steps.is_some_and(|max| self.steps >= max) {
+            return Some(StopReason::StepLimit);
+        }
+
+        if self
+            .options
+            .timeout
+            .is_some_and(|timeout| self.start.elapsed() >= timeout)
+        {
+            return Some(StopReason::Timeout);
+        }
+
+        if !self.options.watchpoints.is_empty()
+            || self.coverage.is_some()
+            || self.profile.is_some()
+            || self.taint.is_some()
+        {
+            self.before = Some((*emulator.registers(), emulator.next_instruction().ok()));
+        }
+
+        None
+    }
+
+    /// Records an executed instruction, returning why the run should stop, if it should.
+    pub fn record<H: SyscallHandler>(
+        &mut self,
+        emulator: &Emulator<H>,
+        outcome: StepOutcome,
+    ) -> Option<StopReason> {
+        self.steps += 1;
+
+        let before = self.before.take();
+
+        if let (Some(coverage), Some((registers, instruction))) = (&mut self.coverage, &before) {
+            coverage.record(
+                registers[Register::I],
+                instruction.as_ref(),
+                emulator.effects(),
+            );
+        }
+
+        if let (Some(profile), Some((registers, instruction))) = (&mut self.profile, &before) {
+            profile.record(
+                registers[Register::I],
+                instruction.as_ref(),
+                emulator.registers()[Register::I],
+                emulator.effects(),
+            );
+        }
+
+        if let (Some(taint), Some((registers, Some(instruction)))) = (&mut self.taint, &before) {
+            taint.record(
+                registers[Register::I],
+                instruction,
+                registers,
+                emulator.effects(),
+                emulator.constants(),
+            );
+        }
+
+        if let StepOutcome::Exited(exit_code) = outcome {
+            return Some(StopReason::Exited(exit_code));
+        }
+
+        if let Some(hit) = before.and_then(|before| self.check_watchpoints(emulator, before)) {
+            return Some(StopReason::Watchpoint(hit));
+        }
+
+        let detector = self.loop_detector.as_mut()?;
+
+        if emulator
+            .effects()
+            .iter()
+            .any(|effect| matches!(effect, Effect::Syscall { .. }))
+        {
+            detector.reset();
+            return None;
+        }
+
+        detector
+            .record(emulator)
+            .map(|(start, end)| StopReason::InfiniteLoop { start, end })
+    }
+
+    /// Checks the instruction that was just executed from the registers and instruction `before`
+    /// against the watchpoints, logging hits and returning the first one that should stop the run.
+    fn check_watchpoints<H: SyscallHandler>(
+        &mut self,
+        emulator: &Emulator<H>,
+        (before, instruction): (Registers, Option<Instruction>),
+    ) -> Option<WatchHit> {
+        let hits = watch::check_all(
+            self.options
+                .watchpoints
+                .iter()
+                .map(|watch| &watch.watchpoint),
+            before[Register::I],
+            instruction,
+            &before,
+            emulator.registers(),
+            emulator.effects(),
+        );
+
+        let mut stop = None;
+        for hit in hits {
+            match self.options.watchpoints[hit.watchpoint].action {
+                WatchAction::Break => {
+                    stop.get_or_insert(hit);
+                }
+                WatchAction::Log => self.logged.push(hit),
+            }
+        }
+
+        stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        coverage::BranchCounts,
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, flags::Flags, instruction::Instruction},
+    };
+
+    /// Runs `instructions` with the given options.
+    fn run(instructions: Vec<Instruction>, options: RunOptions) -> StopReason {
+        let mut emulator = Emulator::from_instructions_with_handler(
+            Constants::default(),
+            instructions,
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        emulator.run_with(options).unwrap().stop_reason
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let options = RunOptions {
+            max_steps: Some(10),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run(vec![Instruction::IMM(Register::I, 0)], options),
+            StopReason::StepLimit
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let options = RunOptions {
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run(vec![Instruction::IMM(Register::I, 0)], options),
+            StopReason::Timeout
+        );
+    }
+
+    #[test]
+    fn test_detect_loop() {
+        let options = RunOptions {
+            max_steps: Some(10_000),
+            detect_loops: true,
+            ..Default::default()
+        };
+
+        let instructions = vec![
+            Instruction::IMM(Register::A, 5),
+            Instruction::IMM(Register::B, 2),
+            Instruction::IMM(Register::C, 1),
+            Instruction::ADD(Register::D, Register::C),
+            Instruction::IMM(Register::I, 2),
+        ];
+
+        // d keeps changing, but wraps around after 256 iterations
+        assert_eq!(
+            run(instructions, options),
+            StopReason::InfiniteLoop { start: 2, end: 4 }
+        );
+    }
+
+    #[test]
+    fn test_no_loop_through_syscalls() {
+        let consts = Constants::default();
+        let options = RunOptions {
+            max_steps: Some(100),
+            detect_loops: true,
+            ..Default::default()
+        };
+
+        let instructions = vec![
+            Instruction::IMM(Register::C, 1),
+            Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::D)),
+            Instruction::IMM(Register::I, 0),
+        ];
+
+        assert_eq!(run(instructions, options), StopReason::StepLimit);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let options = RunOptions {
+            watchpoints: vec![
+                Watch {
+                    watchpoint: "mem:0x20-0x2f:r".parse().unwrap(),
+                    action: WatchAction::Log,
+                },
+                Watch {
+                    watchpoint: "mem:0x20:w".parse().unwrap(),
+                    action: WatchAction::Break,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let instructions = vec![
+            Instruction::IMM(Register::A, 0x20),
+            Instruction::LDM(Register::B, Register::A),
+            Instruction::IMM(Register::B, 7),
+            Instruction::STM(Register::A, Register::B),
+            Instruction::IMM(Register::I, 0),
+        ];
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            Constants::default(),
+            instructions,
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        let result = emulator.run_with(options).unwrap();
+
+        let StopReason::Watchpoint(hit) = result.stop_reason else {
+            panic!("expected a watchpoint hit, got {:?}", result.stop_reason);
+        };
+        assert_eq!(hit.watchpoint, 1);
+        assert_eq!(hit.index, 3);
+        assert_eq!(
+            hit.instruction,
+            Some(Instruction::STM(Register::A, Register::B))
+        );
+
+        assert_eq!(result.watch_log.len(), 1);
+        assert_eq!(result.watch_log[0].index, 1);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let consts = Constants::default();
+        let options = RunOptions {
+            coverage: true,
+            ..Default::default()
+        };
+
+        // for (a = 0; a != 3; a++) {}
+        let instructions = vec![
+            Instruction::IMM(Register::B, 1),
+            Instruction::IMM(Register::C, 3),
+            Instruction::IMM(Register::D, 3),
+            Instruction::ADD(Register::A, Register::B),
+            Instruction::CMP(Register::A, Register::C),
+            Instruction::JMP(
+                Flags {
+                    not_equal: true,
+                    ..Default::default()
+                },
+                Register::D,
+            ),
+            Instruction::SYS(consts.syscall.EXIT, None),
+        ];
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            instructions,
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        let coverage = emulator.run_with(options).unwrap().coverage.unwrap();
+
+        assert_eq!(coverage.hits(0), 1);
+        assert_eq!(coverage.hits(3), 3);
+        assert_eq!(coverage.hits(6), 1);
+        assert_eq!(coverage.covered(), 7);
+        assert_eq!(
+            coverage.branch(5),
+            Some(BranchCounts {
+                taken: 2,
+                not_taken: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_coverage_jump_to_next() {
+        let consts = Constants::default();
+        let options = RunOptions {
+            coverage: true,
+            ..Default::default()
+        };
+
+        let instructions = vec![
+            Instruction::IMM(Register::D, 3),
+            Instruction::CMP(Register::A, Register::A),
+            Instruction::JMP("E".try_into().unwrap(), Register::D),
+            Instruction::SYS(consts.syscall.EXIT, None),
+        ];
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            instructions,
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+        let coverage = emulator.run_with(options).unwrap().coverage.unwrap();
+
+        assert_eq!(
+            coverage.branch(2),
+            Some(BranchCounts {
+                taken: 1,
+                not_taken: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_exit() {
+        let consts = Constants::default();
+        let options = RunOptions {
+            detect_loops: true,
+            ..Default::default()
+        };
+
+        let instructions = vec![
+            Instruction::IMM(Register::A, 7),
+            Instruction::SYS(consts.syscall.EXIT, None),
+        ];
+        assert_eq!(run(instructions, options), StopReason::Exited(7));
+    }
+}
@@ -0,0 +1,224 @@
+use std::{collections::BTreeSet, rc::Rc};
+
+use crate::yan85::{
+    constants::{Constants, Decodable, Encodable},
+    flags::Flags,
+};
+
+/// A shared symbolic byte.
+pub type Value = Rc<Expr>;
+
+/// A byte computed from input bytes.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Expr {
+    /// A known byte.
+    Const(u8),
+    /// The input byte at the given offset.
+    Input(usize),
+    /// The wrapping sum of two bytes.
+    Add(Value, Value),
+    /// The encoded flags that comparing `a` with `b` produces, on top of the flags in `previous`
+    /// if comparisons accumulate.
+    Cmp {
+        /// The flags that the comparison adds to, if any.
+        previous: Option<Value>,
+        /// The first operand.
+        a: Value,
+        /// The second operand.
+        b: Value,
+    },
+}
+
+impl Expr {
+    /// A known byte.
+    pub fn constant(value: u8) -> Value {
+        Rc::new(Expr::Const(value))
+    }
+
+    /// The input byte at `offset`.
+    pub fn input(offset: usize) -> Value {
+        Rc::new(Expr::Input(offset))
+    }
+
+    /// The byte's value, if it doesn't depend on input.
+    pub fn as_const(&self) -> Option<u8> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The wrapping sum of `a` and `b`, folding constants where possible.
+    pub fn add(a: &Value, b: &Value) -> Value {
+        match (a.as_ref(), b.as_ref()) {
+            (&Expr::Const(a), &Expr::Const(b)) => Expr::constant(a.wrapping_add(b)),
+            (_, Expr::Const(0)) => a.clone(),
+            (Expr::Const(0), _) => b.clone(),
+            (Expr::Add(x, c1), &Expr::Const(c2)) | (&Expr::Const(c2), Expr::Add(x, c1)) => {
+                match c1.as_const() {
+                    Some(c1) => Expr::add(x, &Expr::constant(c1.wrapping_add(c2))),
+                    None => Rc::new(Expr::Add(a.clone(), b.clone())),
+                }
+            }
+            _ => Rc::new(Expr::Add(a.clone(), b.clone())),
+        }
+    }
+
+    /// The encoded flags from comparing `a` with `b` on top of `previous`, folding constants where
+    /// possible.
+    pub fn cmp(previous: Option<&Value>, a: &Value, b: &Value, constants: Constants) -> Value {
+        let expr = Expr::Cmp {
+            previous: previous.cloned(),
+            a: a.clone(),
+            b: b.clone(),
+        };
+
+        let known = previous.is_none_or(|previous| previous.as_const().is_some())
+            && a.as_const().is_some()
+            && b.as_const().is_some();
+
+        match known {
+            true => Expr::constant(expr.eval(&[], constants)),
+            false => Rc::new(expr),
+        }
+    }
+
+    /// Evaluates the byte for the input bytes `input`, which must cover every offset it uses.
+    pub fn eval(&self, input: &[u8], constants: Constants) -> u8 {
+        match self {
+            Expr::Const(value) => *value,
+            Expr::Input(offset) => input[*offset],
+            Expr::Add(a, b) => a
+                .eval(input, constants)
+                .wrapping_add(b.eval(input, constants)),
+            Expr::Cmp { previous, a, b } => {
+                let mut flags = previous
+                    .as_ref()
+                    .and_then(|previous| {
+                        Flags::decode(previous.eval(input, constants), constants).ok()
+                    })
+                    .unwrap_or_default();
+
+                flags.record_comparison(
+                    a.eval(input, constants),
+                    b.eval(input, constants),
+                    constants.semantics.cmp.zero_flag,
+                );
+
+                flags.encode(constants)
+            }
+        }
+    }
+
+    /// Adds the offsets of the input bytes the byte depends on to `offsets`.
+    pub fn collect_inputs(&self, offsets: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Input(offset) => {
+                offsets.insert(*offset);
+            }
+            Expr::Add(a, b) => {
+                a.collect_inputs(offsets);
+                b.collect_inputs(offsets);
+            }
+            Expr::Cmp { previous, a, b } => {
+                if let Some(previous) = previous {
+                    previous.collect_inputs(offsets);
+                }
+                a.collect_inputs(offsets);
+                b.collect_inputs(offsets);
+            }
+        }
+    }
+}
+
+/// A condition that the input must satisfy for execution to follow a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// A `JMP` with `condition` was taken, or not, after the comparisons in `flags`.
+    Jump {
+        /// The value of the flags register.
+        flags: Value,
+        /// The jump condition.
+        condition: Flags,
+        /// Whether the jump was taken.
+        taken: bool,
+    },
+    /// A byte has a particular value.
+    Equal {
+        /// The byte.
+        value: Value,
+        /// The value it must have.
+        expected: u8,
+    },
+}
+
+impl Constraint {
+    /// Checks whether the constraint holds for the input bytes `input`.
+    pub fn holds(&self, input: &[u8], constants: Constants) -> bool {
+        match self {
+            Constraint::Jump {
+                flags,
+                condition,
+                taken,
+            } => Flags::decode(flags.eval(input, constants), constants)
+                .is_ok_and(|flags| flags.does_match(condition) == *taken),
+            Constraint::Equal { value, expected } => value.eval(input, constants) == *expected,
+        }
+    }
+
+    /// The offsets of the input bytes the constraint depends on.
+    pub fn inputs(&self) -> BTreeSet<usize> {
+        let mut offsets = BTreeSet::new();
+
+        match self {
+            Constraint::Jump { flags, .. } => flags.collect_inputs(&mut offsets),
+            Constraint::Equal { value, .. } => value.collect_inputs(&mut offsets),
+        }
+
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding() {
+        let consts = Constants::default();
+        let x = Expr::input(0);
+
+        assert_eq!(
+            Expr::add(&Expr::constant(0xff), &Expr::constant(2)).as_const(),
+            Some(1)
+        );
+        assert!(Rc::ptr_eq(&Expr::add(&x, &Expr::constant(0)), &x));
+
+        let sum = Expr::add(&Expr::add(&x, &Expr::constant(3)), &Expr::constant(4));
+        assert_eq!(*sum, Expr::Add(x.clone(), Expr::constant(7)));
+        assert_eq!(sum.eval(&[0xfa], consts), 1);
+
+        let flags = Expr::cmp(None, &Expr::constant(1), &Expr::constant(2), consts);
+        assert_eq!(flags.as_const(), Some(consts.flag.L | consts.flag.N));
+    }
+
+    #[test]
+    fn test_constraints() {
+        let consts = Constants::default();
+        let flags = Expr::cmp(None, &Expr::input(1), &Expr::constant(b'x'), consts);
+
+        let equal = Constraint::Jump {
+            flags: flags.clone(),
+            condition: Flags {
+                equal: true,
+                ..Default::default()
+            },
+            taken: true,
+        };
+
+        assert!(equal.holds(b"ax", consts));
+        assert!(!equal.holds(b"ay", consts));
+        assert_eq!(equal.inputs(), BTreeSet::from([1]));
+    }
+}
@@ -0,0 +1,695 @@
+/// Symbolic bytes and the constraints on them.
+mod expr;
+/// A constraint solver over input bytes.
+mod solver;
+
+use crate::{
+    disasm::disassemble_instruction,
+    emulator::Emulator,
+    syscall_handler::{SyscallHandler, STDIN, STDOUT},
+    yan85::{
+        code::Code,
+        constants::{Constants, Decodable, FlagUpdate, JumpSemantics, Layout, StackLayout},
+        flags::Flags,
+        instruction::Instruction,
+        register::Register,
+        syscall::Syscall,
+    },
+};
+
+pub use expr::{Constraint, Expr, Value};
+use solver::Solution;
+
+pub(crate) use solver::{default_byte, preferred_bytes};
+
+/// What to find input for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Goal {
+    /// Reach the instruction at this index.
+    Reach(u8),
+    /// Write these bytes to standard output.
+    Write(Vec<u8>),
+}
+
+/// Limits on a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolveOptions {
+    /// The number of instructions to execute, across all paths.
+    pub max_steps: u64,
+    /// The number of paths to explore.
+    pub max_paths: usize,
+    /// The number of constraint evaluations that each call to the solver may perform.
+    pub solver_budget: u64,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            max_steps: 1_000_000,
+            max_paths: 10_000,
+            solver_budget: 10_000_000,
+        }
+    }
+}
+
+/// The outcome of a search.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolveReport {
+    /// Standard input that achieves the goal, if any was found.
+    pub input: Option<Vec<u8>>,
+    /// The number of paths explored.
+    pub paths: usize,
+    /// The number of instructions executed, across all paths.
+    pub steps: u64,
+    /// The number of paths that were cut short by the limits, by the solver running out of budget,
+    /// or by unsupported operations, so finding no input doesn't prove that none exists unless
+    /// this is zero.
+    pub incomplete: usize,
+}
+
+/// The symbolic machine state along one path.
+#[derive(Debug, Clone)]
+struct State {
+    /// The registers, in the order of [`Register::ALL`].
+    registers: [Value; 7],
+    /// The memory.
+    memory: Vec<Value>,
+    /// The stack.
+    stack: Vec<Value>,
+    /// The conditions on the input for execution to follow this path.
+    constraints: Vec<Constraint>,
+    /// Input that satisfies the constraints, with one byte for every byte read so far.
+    model: Vec<u8>,
+    /// Everything written to standard output.
+    output: Vec<Value>,
+    /// The file descriptor that the next `OPEN` returns.
+    next_fd: u8,
+}
+
+impl State {
+    /// The value of `register`.
+    fn get(&self, register: Register) -> Value {
+        self.registers[register as usize].clone()
+    }
+
+    /// Sets the value of `register`.
+    fn set(&mut self, register: Register, value: Value) {
+        self.registers[register as usize] = value;
+    }
+
+    /// The value of `register` for the current model, constraining it to keep that value.
+    fn concretize(&mut self, register: Register, constants: Constants) -> u8 {
+        let value = self.get(register);
+        if let Some(value) = value.as_const() {
+            return value;
+        }
+
+        let expected = value.eval(&self.model, constants);
+        self.constraints.push(Constraint::Equal { value, expected });
+
+        expected
+    }
+
+    /// Adds `constraint`, returning whether the path is still feasible, or `None` if the solver
+    /// ran out of budget before finding out.
+    fn constrain(
+        &mut self,
+        constraint: Constraint,
+        constants: Constants,
+        budget: u64,
+    ) -> Option<bool> {
+        if !constraint.holds(&self.model, constants) {
+            self.constraints.push(constraint.clone());
+
+            match solver::solve(&self.constraints, self.model.len(), constants, budget) {
+                Solution::Sat(model) => self.model = model,
+                Solution::Unsat => return Some(false),
+                Solution::OutOfBudget => return None,
+            }
+        } else {
+            self.constraints.push(constraint);
+        }
+
+        Some(true)
+    }
+}
+
+/// What executing an operation led to.
+enum Flow {
+    /// Execution continues.
+    Next,
+    /// Execution continues, and also along the given other path.
+    Fork(State),
+    /// The path ended.
+    End,
+    /// The path can't be followed any further.
+    Abandon,
+    /// The goal was reached with the given input.
+    Found(Vec<u8>),
+}
+
+/// Explores the paths through a program.
+struct Explorer<'a> {
+    /// The code, which is concrete.
+    code: &'a Code,
+    /// Encoding constants.
+    constants: Constants,
+    /// What to find input for.
+    goal: &'a Goal,
+    /// Limits on the search.
+    options: SolveOptions,
+    /// The progress so far.
+    report: SolveReport,
+}
+
+/// Searches for standard input that makes the program loaded in `emulator` achieve `goal`, by
+/// executing it symbolically from its current state.
+///
+/// Bytes read from standard input become symbolic, and each `JMP` that depends on them forks the
+/// path. Registers that must be known—addresses, jump targets, and syscall arguments—are fixed to
+/// one feasible value. Other files are treated as empty, and reading code from standard input
+/// isn't supported.
+pub fn solve<H: SyscallHandler>(
+    emulator: &Emulator<H>,
+    goal: &Goal,
+    options: SolveOptions,
+) -> SolveReport {
+    let registers = emulator.registers();
+    let state = State {
+        registers: Register::ALL.map(|register| Expr::constant(registers[register])),
+        memory: (0..=u8::MAX)
+            .map(|address| Expr::constant(emulator.memory()[address]))
+            .collect(),
+        stack: (0..=u8::MAX)
+            .map(|address| Expr::constant(emulator.stack()[address]))
+            .collect(),
+        constraints: vec![],
+        model: vec![],
+        output: vec![],
+        next_fd: 3,
+    };
+
+    let mut explorer = Explorer {
+        code: emulator.code(),
+        constants: emulator.constants(),
+        goal,
+        options,
+        report: SolveReport::default(),
+    };
+    explorer.explore(state);
+
+    explorer.report
+}
+
+impl Explorer<'_> {
+    /// Explores paths depth-first from `state` until the goal is reached or the limits are.
+    fn explore(&mut self, state: State) {
+        let mut paths = vec![state];
+
+        while let Some(mut state) = paths.pop() {
+            self.report.paths += 1;
+
+            loop {
+                if self.report.steps >= self.options.max_steps {
+                    self.report.incomplete += paths.len() + 1;
+                    return;
+                }
+
+                let index = state.concretize(Register::I, self.constants);
+                if *self.goal == Goal::Reach(index) {
+                    self.report.input = Some(state.model);
+                    return;
+                }
+
+                let bytes = self.code.get_instruction(index);
+                let Ok(instruction) = disassemble_instruction(bytes, self.constants) else {
+                    break;
+                };
+
+                state.set(Register::I, Expr::constant(index.wrapping_add(1)));
+                self.report.steps += 1;
+
+                let mut successors = match self.execute(state, &instruction) {
+                    Ok(successors) => successors,
+                    Err(input) => {
+                        self.report.input = Some(input);
+                        return;
+                    }
+                };
+
+                let Some(next) = successors.pop() else {
+                    break;
+                };
+                state = next;
+
+                for successor in successors {
+                    if self.report.paths + paths.len() < self.options.max_paths {
+                        paths.push(successor);
+                    } else {
+                        self.report.incomplete += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Executes `instruction` from `state`, returning the states it can lead to, or the input that
+    /// reaches the goal.
+    fn execute(&mut self, state: State, instruction: &Instruction) -> Result<Vec<State>, Vec<u8>> {
+        let operations = instruction.operations();
+        let mut pending = vec![(state, 0)];
+        let mut successors = vec![];
+
+        'paths: while let Some((mut state, start)) = pending.pop() {
+            for (i, operation) in operations.iter().enumerate().skip(start) {
+                match self.execute_operation(&mut state, operation) {
+                    Flow::Next => {}
+                    Flow::Fork(other) => pending.push((other, i + 1)),
+                    Flow::End => continue 'paths,
+                    Flow::Abandon => {
+                        self.report.incomplete += 1;
+                        continue 'paths;
+                    }
+                    Flow::Found(input) => return Err(input),
+                }
+            }
+
+            successors.push(state);
+        }
+
+        Ok(successors)
+    }
+
+    /// Executes a single operation, mirroring the emulator's semantics.
+    fn execute_operation(&mut self, state: &mut State, operation: &Instruction) -> Flow {
+        let constants = self.constants;
+
+        match *operation {
+            Instruction::IMM(register, value) => state.set(register, Expr::constant(value)),
+            Instruction::ADD(a, b) => state.set(a, Expr::add(&state.get(a), &state.get(b))),
+            Instruction::STK(pop, push) => match constants.semantics.stack {
+                StackLayout::Separate => {
+                    if let Some(src) = push {
+                        let s = state.concretize(Register::S, constants);
+                        state.stack[s as usize] = state.get(src);
+                        state.set(Register::S, Expr::constant(s.wrapping_add(1)));
+                    }
+
+                    if let Some(dest) = pop {
+                        let s = state.concretize(Register::S, constants).wrapping_sub(1);
+                        state.set(Register::S, Expr::constant(s));
+                        state.set(dest, state.stack[s as usize].clone());
+                    }
+                }
+                StackLayout::Memory => {
+                    if let Some(src) = push {
+                        let s = state.concretize(Register::S, constants).wrapping_add(1);
+                        state.set(Register::S, Expr::constant(s));
+                        state.memory[s as usize] = state.get(src);
+                    }
+
+                    if let Some(dest) = pop {
+                        let s = state.concretize(Register::S, constants);
+                        state.set(dest, state.memory[s as usize].clone());
+                        state.set(Register::S, Expr::constant(s.wrapping_sub(1)));
+                    }
+                }
+            },
+            Instruction::STM(a, b) => {
+                let address = state.concretize(a, constants);
+                state.memory[address as usize] = state.get(b);
+            }
+            Instruction::LDM(a, b) => {
+                let address = state.concretize(b, constants);
+                state.set(a, state.memory[address as usize].clone());
+            }
+            Instruction::CMP(a, b) => {
+                let previous = match constants.semantics.cmp.flags {
+                    FlagUpdate::Replace => None,
+                    FlagUpdate::Accumulate => Some(state.get(Register::F)),
+                };
+                let flags = Expr::cmp(previous.as_ref(), &state.get(a), &state.get(b), constants);
+                state.set(Register::F, flags);
+            }
+            Instruction::JMP(condition, register) => {
+                return self.execute_jmp(state, condition, register)
+            }
+            Instruction::SYS(syscalls, return_register) => {
+                return self.execute_sys(state, syscalls, return_register)
+            }
+            Instruction::Compound(_) => unreachable!("operations aren't compound"),
+        }
+
+        Flow::Next
+    }
+
+    /// Executes a `JMP`, forking if whether it's taken depends on the input.
+    fn execute_jmp(&mut self, state: &mut State, condition: Flags, register: Register) -> Flow {
+        let constants = self.constants;
+        let target = state.get(register);
+
+        let unconditional = match constants.semantics.jump {
+            JumpSemantics::Reference => condition.is_empty(),
+            JumpSemantics::Strict => false,
+        };
+        if unconditional {
+            state.set(Register::I, target);
+            return Flow::Next;
+        }
+
+        let flags = state.get(Register::F);
+        if let Some(flags) = flags.as_const() {
+            let Ok(flags) = Flags::decode(flags, constants) else {
+                return Flow::End;
+            };
+
+            if flags.does_match(&condition) {
+                state.set(Register::I, target);
+            }
+            return Flow::Next;
+        }
+
+        let budget = self.options.solver_budget;
+        let mut taken = state.clone();
+        let taken_feasibility = taken.constrain(
+            Constraint::Jump {
+                flags: flags.clone(),
+                condition,
+                taken: true,
+            },
+            constants,
+            budget,
+        );
+        taken.set(Register::I, target);
+
+        let not_taken_feasibility = state.constrain(
+            Constraint::Jump {
+                flags,
+                condition,
+                taken: false,
+            },
+            constants,
+            budget,
+        );
+
+        // A direction the solver gave up on is dropped, but the search is then incomplete.
+        let mut feasible = |feasibility: Option<bool>| {
+            feasibility.unwrap_or_else(|| {
+                self.report.incomplete += 1;
+                false
+            })
+        };
+        let taken_feasible = feasible(taken_feasibility);
+        let not_taken_feasible = feasible(not_taken_feasibility);
+
+        match (taken_feasible, not_taken_feasible) {
+            (true, true) => Flow::Fork(taken),
+            (true, false) => {
+                *state = taken;
+                Flow::Next
+            }
+            (false, true) => Flow::Next,
+            (false, false) => Flow::End,
+        }
+    }
+
+    /// Executes a `SYS`, performing system calls in the constants' syscall order.
+    fn execute_sys(
+        &mut self,
+        state: &mut State,
+        syscalls: u8,
+        return_register: Option<Register>,
+    ) -> Flow {
+        let constants = self.constants;
+        let Ok(syscalls) = Vec::<Syscall>::decode(syscalls, constants) else {
+            return Flow::End;
+        };
+
+        for syscall in syscalls {
+            let return_value = match syscall {
+                Syscall::Open => {
+                    let fd = state.next_fd;
+                    state.next_fd = state.next_fd.wrapping_add(1);
+                    fd
+                }
+                Syscall::ReadCode => match state.concretize(Register::A, constants) {
+                    STDIN => return Flow::Abandon,
+                    _ => 0,
+                },
+                Syscall::ReadMemory => {
+                    let fd = state.concretize(Register::A, constants);
+                    let Some((start, length)) = self.transfer(state) else {
+                        return Flow::Abandon;
+                    };
+
+                    match fd {
+                        STDIN => {
+                            for address in start..start + length {
+                                let offset = state.model.len();
+                                state.model.push(solver::default_byte());
+                                state.memory[address] = Expr::input(offset);
+                            }
+                            length as u8
+                        }
+                        _ => 0,
+                    }
+                }
+                Syscall::Write => {
+                    let fd = state.concretize(Register::A, constants);
+                    let Some((start, length)) = self.transfer(state) else {
+                        return Flow::Abandon;
+                    };
+
+                    if fd == STDOUT {
+                        let written = state.memory[start..start + length].to_vec();
+                        state.output.extend(written);
+
+                        if let Some(input) = self.check_output(state, length) {
+                            return Flow::Found(input);
+                        }
+                    }
+                    length as u8
+                }
+                Syscall::Sleep => 0,
+                Syscall::Exit => return Flow::End,
+            };
+
+            if let Some(register) = return_register {
+                state.set(register, Expr::constant(return_value));
+            }
+        }
+
+        Flow::Next
+    }
+
+    /// The memory range that a transfer with the start address in B and the length in C covers,
+    /// unless it would spill out of memory in the flat layout.
+    fn transfer(&self, state: &mut State) -> Option<(usize, usize)> {
+        let start = state.concretize(Register::B, self.constants) as usize;
+        let length = state.concretize(Register::C, self.constants) as usize;
+
+        let available = 256 - start;
+        if length > available && self.constants.semantics.layout == Layout::Flat {
+            return None;
+        }
+
+        Some((start, length.min(available)))
+    }
+
+    /// Checks whether the `new` bytes just written to standard output can complete the goal
+    /// string, returning the input that does so. Positions the solver gives up on make the search
+    /// incomplete.
+    fn check_output(&mut self, state: &State, new: usize) -> Option<Vec<u8>> {
+        let Goal::Write(target) = self.goal else {
+            return None;
+        };
+
+        let output = &state.output;
+        if output.len() < target.len() {
+            return None;
+        }
+
+        let first = (output.len() - new).saturating_sub(target.len().saturating_sub(1));
+        for position in first..=output.len() - target.len() {
+            let window = &output[position..position + target.len()];
+
+            if window
+                .iter()
+                .zip(target)
+                .any(|(byte, &expected)| byte.as_const().is_some_and(|byte| byte != expected))
+            {
+                continue;
+            }
+
+            let mut constraints = state.constraints.clone();
+            constraints.extend(window.iter().zip(target).map(|(byte, &expected)| {
+                Constraint::Equal {
+                    value: byte.clone(),
+                    expected,
+                }
+            }));
+
+            if constraints
+                .iter()
+                .all(|constraint| constraint.holds(&state.model, self.constants))
+            {
+                return Some(state.model.clone());
+            }
+
+            match solver::solve(
+                &constraints,
+                state.model.len(),
+                self.constants,
+                self.options.solver_budget,
+            ) {
+                Solution::Sat(input) => return Some(input),
+                Solution::Unsat => {}
+                Solution::OutOfBudget => self.report.incomplete += 1,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    /// A program that prints "yes" if its input is "ok" and "no" otherwise.
+    fn checker() -> Emulator<VirtualSyscallHandler> {
+        let consts = Constants::default();
+        let not_equal = Flags {
+            not_equal: true,
+            ..Default::default()
+        };
+
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // read_memory(0, 0x10, 2)
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+                Instruction::IMM(Register::D, 21),
+                // input[0] == 'o'
+                Instruction::LDM(Register::A, Register::B),
+                Instruction::IMM(Register::C, b'o'),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::JMP(not_equal, Register::D),
+                // input[1] + 1 == 'l'
+                Instruction::IMM(Register::B, 0x11),
+                Instruction::LDM(Register::A, Register::B),
+                Instruction::IMM(Register::C, 1),
+                Instruction::ADD(Register::A, Register::C),
+                Instruction::IMM(Register::C, b'l'),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::JMP(not_equal, Register::D),
+                // write(1, "yes")
+                Instruction::IMM(Register::A, 1),
+                Instruction::IMM(Register::B, 0x30),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.WRITE, None),
+                Instruction::SYS(consts.syscall.EXIT, None),
+                // write(1, "no")
+                Instruction::IMM(Register::A, 1),
+                Instruction::IMM(Register::B, 0x33),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.WRITE, None),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        for (address, &byte) in (0x30..).zip(b"yesno") {
+            emulator.memory_mut()[address] = byte;
+        }
+
+        emulator
+    }
+
+    #[test]
+    fn test_reach() {
+        let report = solve(&checker(), &Goal::Reach(16), SolveOptions::default());
+
+        assert_eq!(report.input, Some(b"ok".to_vec()));
+        assert_eq!(report.incomplete, 0);
+    }
+
+    #[test]
+    fn test_write() {
+        let emulator = checker();
+        let options = SolveOptions::default();
+
+        let yes = solve(&emulator, &Goal::Write(b"yes".to_vec()), options);
+        assert_eq!(yes.input, Some(b"ok".to_vec()));
+
+        let no = solve(&emulator, &Goal::Write(b"no".to_vec()), options);
+        assert_eq!(no.input, Some(b"aa".to_vec()));
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let report = solve(
+            &checker(),
+            &Goal::Write(b"maybe".to_vec()),
+            SolveOptions::default(),
+        );
+
+        assert_eq!(report.input, None);
+        assert_eq!(report.paths, 3);
+        assert_eq!(report.incomplete, 0);
+    }
+
+    #[test]
+    fn test_solver_out_of_budget() {
+        let consts = Constants::default();
+        let not_equal = Flags {
+            not_equal: true,
+            ..Default::default()
+        };
+
+        // Reaches the last instruction if input[0] + input[1] == '0' + '9'.
+        let emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // read_memory(0, 0x10, 2)
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 2),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+                Instruction::LDM(Register::A, Register::B),
+                Instruction::IMM(Register::B, 0x11),
+                Instruction::LDM(Register::B, Register::B),
+                Instruction::ADD(Register::A, Register::B),
+                Instruction::IMM(Register::C, b'0' + b'9'),
+                Instruction::CMP(Register::A, Register::C),
+                Instruction::IMM(Register::D, 13),
+                Instruction::JMP(not_equal, Register::D),
+                Instruction::IMM(Register::A, 0),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        let report = solve(&emulator, &Goal::Reach(12), SolveOptions::default());
+        assert!(report.input.is_some());
+
+        let options = SolveOptions {
+            solver_budget: 1,
+            ..Default::default()
+        };
+        let report = solve(&emulator, &Goal::Reach(12), options);
+        assert_eq!(report.input, None);
+        assert!(report.incomplete > 0);
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::OnceLock;
+
+use crate::yan85::constants::Constants;
+
+use super::expr::Constraint;
+
+/// Every byte value, in the order they're tried: alphanumerics first, then other printable
+/// characters, then everything else, so that solutions are readable where possible.
+pub fn preferred_bytes() -> &'static [u8; 256] {
+    static ORDER: OnceLock<[u8; 256]> = OnceLock::new();
+
+    ORDER.get_or_init(|| {
+        let mut order: Vec<u8> = (b'a'..=b'z')
+            .chain(b'A'..=b'Z')
+            .chain(b'0'..=b'9')
+            .collect();
+        order.extend((0x20..0x7f).filter(|byte: &u8| !byte.is_ascii_alphanumeric()));
+        order.extend((0..0x20).chain(0x7f..=0xff));
+
+        order.try_into().expect("every byte is listed once")
+    })
+}
+
+/// The byte that unconstrained input bytes start out as.
+pub fn default_byte() -> u8 {
+    preferred_bytes()[0]
+}
+
+/// The result of a search for input that satisfies some constraints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Solution {
+    /// Input that satisfies every constraint.
+    Sat(Vec<u8>),
+    /// No input satisfies the constraints.
+    Unsat,
+    /// The budget ran out before input was found or ruled out.
+    OutOfBudget,
+}
+
+/// Finds `length` input bytes that satisfy every constraint in `constraints`, trying at most
+/// `budget` constraint evaluations.
+///
+/// Each input byte's candidates are first narrowed by the constraints on it alone. The remaining
+/// constraints, which relate several bytes, are then checked by backtracking search as soon as
+/// all of their bytes have been chosen.
+pub fn solve(
+    constraints: &[Constraint],
+    length: usize,
+    constants: Constants,
+    mut budget: u64,
+) -> Solution {
+    let mut candidates: Vec<Vec<u8>> = vec![preferred_bytes().to_vec(); length];
+    let mut input = vec![default_byte(); length];
+
+    // Constraints that relate several bytes, keyed by the last of those bytes.
+    let mut pending: Vec<Vec<&Constraint>> = vec![vec![]; length];
+    let mut related = vec![false; length];
+
+    for constraint in constraints {
+        let offsets = constraint.inputs();
+
+        match offsets.len() {
+            0 => {
+                if !constraint.holds(&input, constants) {
+                    return Solution::Unsat;
+                }
+            }
+            1 => {
+                let offset = *offsets.first().expect("there's one offset");
+                let mut trial = input.clone();
+
+                candidates[offset].retain(|&byte| {
+                    trial[offset] = byte;
+                    constraint.holds(&trial, constants)
+                });
+                budget = budget.saturating_sub(256);
+
+                if candidates[offset].is_empty() {
+                    return Solution::Unsat;
+                }
+            }
+            _ => {
+                let last = *offsets.last().expect("there are several offsets");
+                pending[last].push(constraint);
+
+                for offset in offsets {
+                    related[offset] = true;
+                }
+            }
+        }
+    }
+
+    // Bytes that no other byte depends on can take their first candidate.
+    for (offset, byte) in input.iter_mut().enumerate() {
+        *byte = candidates[offset][0];
+    }
+
+    let related: Vec<usize> = (0..length).filter(|&offset| related[offset]).collect();
+    let mut search = Search {
+        input: &mut input,
+        candidates: &candidates,
+        pending: &pending,
+        constants,
+        budget,
+        exhausted: false,
+    };
+
+    match search.choose(&related) {
+        true => Solution::Sat(input),
+        false if search.exhausted => Solution::OutOfBudget,
+        false => Solution::Unsat,
+    }
+}
+
+/// The state of a backtracking search over related input bytes.
+struct Search<'a> {
+    /// The input chosen so far.
+    input: &'a mut [u8],
+    /// The candidates for each input byte.
+    candidates: &'a [Vec<u8>],
+    /// The constraints to check once each input byte is chosen.
+    pending: &'a [Vec<&'a Constraint>],
+    /// Encoding constants.
+    constants: Constants,
+    /// The number of constraint evaluations left.
+    budget: u64,
+    /// Whether the search gave up because the budget ran out.
+    exhausted: bool,
+}
+
+impl Search<'_> {
+    /// Chooses the bytes at `offsets` in turn, returning whether every constraint was satisfied.
+    fn choose(&mut self, offsets: &[usize]) -> bool {
+        let Some((&offset, rest)) = offsets.split_first() else {
+            return true;
+        };
+
+        for &byte in &self.candidates[offset] {
+            if self.budget == 0 {
+                self.exhausted = true;
+                return false;
+            }
+
+            self.input[offset] = byte;
+            self.budget = self
+                .budget
+                .saturating_sub(self.pending[offset].len() as u64 + 1);
+
+            if self.pending[offset]
+                .iter()
+                .all(|constraint| constraint.holds(self.input, self.constants))
+                && self.choose(rest)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solve::expr::Expr;
+
+    #[test]
+    fn test_independent_bytes() {
+        let consts = Constants::default();
+        let constraints: Vec<Constraint> = b"yan"
+            .iter()
+            .enumerate()
+            .map(|(offset, &expected)| Constraint::Equal {
+                value: Expr::add(&Expr::input(offset), &Expr::constant(1)),
+                expected: expected + 1,
+            })
+            .collect();
+
+        assert_eq!(
+            solve(&constraints, 4, consts, u64::MAX),
+            Solution::Sat(b"yana".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_related_bytes() {
+        let consts = Constants::default();
+        let sum = Expr::add(&Expr::input(0), &Expr::input(1));
+        let constraints = vec![
+            Constraint::Equal {
+                value: Expr::add(&Expr::input(0), &Expr::constant(0)),
+                expected: b'0',
+            },
+            Constraint::Equal {
+                value: sum,
+                expected: b'0' + b'9',
+            },
+        ];
+
+        assert_eq!(
+            solve(&constraints, 2, consts, u64::MAX),
+            Solution::Sat(b"09".to_vec())
+        );
+        assert_eq!(solve(&constraints, 2, consts, 1), Solution::OutOfBudget);
+    }
+
+    #[test]
+    fn test_unsatisfiable() {
+        let consts = Constants::default();
+        let constraints = vec![
+            Constraint::Equal {
+                value: Expr::input(0),
+                expected: 1,
+            },
+            Constraint::Equal {
+                value: Expr::input(0),
+                expected: 2,
+            },
+        ];
+
+        assert_eq!(solve(&constraints, 1, consts, u64::MAX), Solution::Unsat);
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{SyscallHandler, STDERR, STDIN, STDOUT};
+
+/// A file opened by the Yan85 program.
+#[derive(Debug)]
+struct OpenFile {
+    /// The path the file was opened with.
+    path: PathBuf,
+    /// The open file.
+    file: File,
+}
+
+/// The serialized form of an [`OpenFile`].
+#[derive(Serialize, Deserialize)]
+struct SavedFile {
+    /// The path the file was opened with.
+    path: PathBuf,
+    /// The offset of the next byte to read.
+    position: u64,
+}
+
+/// Services system calls using the host's standard streams and filesystem.
+///
+/// Descriptors 0, 1, and 2 refer to the host's standard input, output, and error. Files opened
+/// with `OPEN` are assigned the lowest free descriptor from 3 onwards, like a fresh process would.
+///
+/// When serialized, open files are saved as their paths and read positions, and are reopened on
+/// deserialization. The position in the host's standard input is not saved.
+#[derive(Debug, Default)]
+pub struct HostSyscallHandler {
+    /// Files opened by the Yan85 program, keyed by their Yan85 file descriptor.
+    files: BTreeMap<u8, OpenFile>,
+}
+
+impl HostSyscallHandler {
+    /// Finds the lowest file descriptor that isn't in use.
+    fn next_fd(&self) -> Result<u8> {
+        (STDERR + 1..=u8::MAX)
+            .find(|fd| !self.files.contains_key(fd))
+            .ok_or_else(|| anyhow!("Too many open files"))
+    }
+}
+
+impl SyscallHandler for HostSyscallHandler {
+    fn open(&mut self, path: &[u8]) -> Result<u8> {
+        let fd = self.next_fd()?;
+        let path = PathBuf::from(OsStr::from_bytes(path));
+        let file = File::open(&path)?;
+
+        self.files.insert(fd, OpenFile { path, file });
+
+        Ok(fd)
+    }
+
+    fn read(&mut self, fd: u8, buffer: &mut [u8]) -> Result<usize> {
+        let n = match fd {
+            STDIN => io::stdin().read(buffer)?,
+            _ => match self.files.get_mut(&fd) {
+                Some(open_file) => open_file.file.read(buffer)?,
+                None => bail!("Bad file descriptor for reading: {fd}"),
+            },
+        };
+
+        Ok(n)
+    }
+
+    fn write(&mut self, fd: u8, buffer: &[u8]) -> Result<usize> {
+        let n = match fd {
+            STDOUT => {
+                let mut stdout = io::stdout();
+                let n = stdout.write(buffer)?;
+                stdout.flush()?;
+
+                n
+            }
+            STDERR => io::stderr().write(buffer)?,
+            _ => bail!("Bad file descriptor for writing: {fd}"),
+        };
+
+        Ok(n)
+    }
+}
+
+impl Serialize for HostSyscallHandler {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut saved = BTreeMap::new();
+        for (&fd, open_file) in &self.files {
+            let position = (&open_file.file)
+                .stream_position()
+                .map_err(serde::ser::Error::custom)?;
+
+            saved.insert(
+                fd,
+                SavedFile {
+                    path: open_file.path.clone(),
+                    position,
+                },
+            );
+        }
+
+        saved.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HostSyscallHandler {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = BTreeMap::<u8, SavedFile>::deserialize(deserializer)?;
+
+        let mut files = BTreeMap::new();
+        for (fd, SavedFile { path, position }) in saved {
+            let reopen = || -> io::Result<File> {
+                let mut file = File::open(&path)?;
+                file.seek(SeekFrom::Start(position))?;
+                Ok(file)
+            };
+            let file = reopen().map_err(|e| {
+                serde::de::Error::custom(format!("Unable to reopen {}: {e}", path.display()))
+            })?;
+
+            files.insert(fd, OpenFile { path, file });
+        }
+
+        Ok(Self { files })
+    }
+}
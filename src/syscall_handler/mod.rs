@@ -0,0 +1,41 @@
+use anyhow::Result;
+
+/// A syscall handler backed by the host operating system.
+mod host;
+/// A syscall handler that drives standard input and output with a script.
+mod scripted;
+/// A fully in-memory syscall handler.
+mod virtual_fs;
+
+pub use host::HostSyscallHandler;
+pub use scripted::{Script, ScriptStep, ScriptedSyscallHandler};
+pub use virtual_fs::VirtualSyscallHandler;
+
+/// The file descriptor of standard input.
+pub const STDIN: u8 = 0;
+/// The file descriptor of standard output.
+pub const STDOUT: u8 = 1;
+/// The file descriptor of standard error.
+pub const STDERR: u8 = 2;
+
+/// A backend that services the file-related Yan85 system calls.
+///
+/// File descriptors are Yan85-level values: each backend keeps its own descriptor table, so they
+/// never correspond to descriptors of the host process.
+pub trait SyscallHandler {
+    /// Opens the file at `path`, returning a new file descriptor for it.
+    fn open(&mut self, path: &[u8]) -> Result<u8>;
+
+    /// Reads up to `buffer.len()` bytes from the file with file descriptor `fd` into `buffer`,
+    /// returning the number of bytes read.
+    fn read(&mut self, fd: u8, buffer: &mut [u8]) -> Result<usize>;
+
+    /// Writes up to `buffer.len()` bytes from `buffer` to the file with file descriptor `fd`,
+    /// returning the number of bytes written.
+    fn write(&mut self, fd: u8, buffer: &[u8]) -> Result<usize>;
+
+    /// Everything written to standard output so far, if this backend captures it.
+    fn captured_output(&self) -> Option<&[u8]> {
+        None
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Serialize, Serializer};
+
+use super::{SyscallHandler, STDIN, STDOUT};
+
+/// A step of an interaction [`Script`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptStep {
+    /// Wait until the program writes these bytes to standard output.
+    Expect(Vec<u8>),
+    /// Make these bytes available on standard input.
+    Send(Vec<u8>),
+}
+
+/// A sequence of bytes to expect on standard output and to send to standard input in turn.
+///
+/// Scripts are written one step per line, as `expect` or `send` followed by a double-quoted
+/// string that may contain the escapes `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, and `\xNN`. Blank
+/// lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// expect "Password: "
+/// send "hunter2\n"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// The steps that haven't completed yet.
+    steps: VecDeque<ScriptStep>,
+    /// Whether standard input ends once every step has completed, rather than being read from the
+    /// wrapped handler.
+    closes_input: bool,
+}
+
+impl Script {
+    /// Constructs a script that performs `steps` in order.
+    pub fn new(steps: impl IntoIterator<Item = ScriptStep>) -> Self {
+        Self {
+            steps: steps.into_iter().collect(),
+            closes_input: false,
+        }
+    }
+
+    /// Constructs a script that sends `input` without waiting for anything, after which standard
+    /// input ends.
+    pub fn send(input: impl Into<Vec<u8>>) -> Self {
+        Self {
+            closes_input: true,
+            ..Self::new([ScriptStep::Send(input.into())])
+        }
+    }
+
+    /// Checks whether every step has completed.
+    pub fn is_finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl FromStr for Script {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let steps = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                parse_step(line).with_context(|| format!("Invalid script line {}", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::new(steps))
+    }
+}
+
+/// Parses a single `expect` or `send` line.
+fn parse_step(line: &str) -> Result<ScriptStep> {
+    let (command, argument) = line
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("Expected a command and a quoted string"))?;
+
+    let bytes = parse_quoted(argument.trim())?;
+
+    match command {
+        "expect" => Ok(ScriptStep::Expect(bytes)),
+        "send" => Ok(ScriptStep::Send(bytes)),
+        _ => bail!("Unknown command: {command:?}"),
+    }
+}
+
+/// Parses a double-quoted string with escapes into bytes.
+fn parse_quoted(quoted: &str) -> Result<Vec<u8>> {
+    let inner = quoted
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|_| quoted.len() >= 2)
+        .ok_or_else(|| anyhow!("Expected a double-quoted string, got {quoted}"))?;
+
+    let mut bytes = vec![];
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        let byte = match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => b'\0',
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| anyhow!("Invalid escape: \\x{hex}"))?
+            }
+            Some(c) => bail!("Invalid escape: \\{c}"),
+            None => bail!("Unterminated escape"),
+        };
+        bytes.push(byte);
+    }
+
+    Ok(bytes)
+}
+
+/// Drives a program's standard streams with a [`Script`], passing everything else through to
+/// another handler.
+///
+/// Reads from standard input are served from the script's `send` steps, but fail while the script
+/// is waiting for output that hasn't been written yet. Output is still passed on to the wrapped
+/// handler. Once the script has finished, standard input is read from the wrapped handler too,
+/// unless the script ends it.
+#[derive(Debug, Clone)]
+pub struct ScriptedSyscallHandler<H> {
+    /// The handler that services everything the script doesn't.
+    inner: H,
+    /// The remaining steps.
+    script: Script,
+    /// Standard output written since the last `expect` step completed.
+    unmatched: Vec<u8>,
+}
+
+impl<H: SyscallHandler> ScriptedSyscallHandler<H> {
+    /// Wraps `inner`, driving standard input and output with `script`.
+    pub fn new(inner: H, script: Script) -> Self {
+        let mut handler = Self {
+            inner,
+            script,
+            unmatched: vec![],
+        };
+        handler.match_output();
+
+        handler
+    }
+
+    /// The wrapped handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// The steps of the script that haven't completed yet.
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    /// Completes `expect` steps whose bytes have been written. Empty `expect` steps complete
+    /// immediately.
+    fn match_output(&mut self) {
+        while let Some(ScriptStep::Expect(expected)) = self.script.steps.front() {
+            let position = match expected.is_empty() {
+                true => Some(0),
+                false => self
+                    .unmatched
+                    .windows(expected.len())
+                    .position(|window| window == expected.as_slice()),
+            };
+            let Some(position) = position else {
+                return;
+            };
+
+            self.unmatched.drain(..position + expected.len());
+            self.script.steps.pop_front();
+        }
+    }
+}
+
+impl<H: SyscallHandler> SyscallHandler for ScriptedSyscallHandler<H> {
+    fn open(&mut self, path: &[u8]) -> Result<u8> {
+        self.inner.open(path)
+    }
+
+    fn read(&mut self, fd: u8, buffer: &mut [u8]) -> Result<usize> {
+        if fd != STDIN {
+            return self.inner.read(fd, buffer);
+        }
+
+        match self.script.steps.front_mut() {
+            None if self.script.closes_input => Ok(0),
+            None => self.inner.read(fd, buffer),
+            Some(ScriptStep::Expect(expected)) => bail!(
+                "The program read from standard input while the script was waiting for {:?}",
+                String::from_utf8_lossy(expected)
+            ),
+            Some(ScriptStep::Send(input)) => {
+                let length = buffer.len().min(input.len());
+                buffer[..length].copy_from_slice(&input[..length]);
+                input.drain(..length);
+
+                if input.is_empty() {
+                    self.script.steps.pop_front();
+                    self.match_output();
+                }
+
+                Ok(length)
+            }
+        }
+    }
+
+    fn write(&mut self, fd: u8, buffer: &[u8]) -> Result<usize> {
+        let written = self.inner.write(fd, buffer)?;
+
+        if fd == STDOUT {
+            self.unmatched.extend_from_slice(&buffer[..written]);
+            self.match_output();
+        }
+
+        Ok(written)
+    }
+
+    fn captured_output(&self) -> Option<&[u8]> {
+        self.inner.captured_output()
+    }
+}
+
+/// Serializes as the wrapped handler, since the script is input rather than machine state.
+impl<H: Serialize> Serialize for ScriptedSyscallHandler<H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_handler::VirtualSyscallHandler;
+
+    #[test]
+    fn test_parse_script() {
+        let script: Script = "# log in\nexpect \"Password: \"\n\nsend \"hunter2\\n\\x00\"\n"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            script,
+            Script::new([
+                ScriptStep::Expect(b"Password: ".to_vec()),
+                ScriptStep::Send(b"hunter2\n\0".to_vec()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_script() {
+        assert!("send hunter2".parse::<Script>().is_err());
+        assert!("send \"\\x4\"".parse::<Script>().is_err());
+        assert!("type \"hunter2\"".parse::<Script>().is_err());
+    }
+
+    #[test]
+    fn test_expect_send() {
+        let script = Script::new([
+            ScriptStep::Expect(b"name? ".to_vec()),
+            ScriptStep::Send(b"yan".to_vec()),
+            ScriptStep::Send(b"85".to_vec()),
+        ]);
+        let mut handler = ScriptedSyscallHandler::new(VirtualSyscallHandler::new("rest"), script);
+        let mut buffer = [0; 4];
+
+        assert!(handler.read(STDIN, &mut buffer).is_err());
+
+        handler.write(STDOUT, b"your na").unwrap();
+        handler.write(STDOUT, b"me? ").unwrap();
+
+        assert_eq!(handler.read(STDIN, &mut buffer[..2]).unwrap(), 2);
+        assert_eq!(&buffer[..2], b"ya");
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 1);
+        assert_eq!(&buffer[..1], b"n");
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 2);
+        assert!(handler.script().is_finished());
+
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 4);
+        assert_eq!(&buffer, b"rest");
+        assert_eq!(handler.captured_output().unwrap(), b"your name? ");
+    }
+
+    #[test]
+    fn test_send_ends_input() {
+        let script = Script::send("ab");
+        let mut handler = ScriptedSyscallHandler::new(VirtualSyscallHandler::new("rest"), script);
+        let mut buffer = [0; 4];
+
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 2);
+        assert_eq!(&buffer[..2], b"ab");
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 0);
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_empty_expect() {
+        let script: Script = "expect \"\"\nsend \"x\"\nexpect \"\"".parse().unwrap();
+        let mut handler = ScriptedSyscallHandler::new(VirtualSyscallHandler::default(), script);
+        let mut buffer = [0; 1];
+
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 1);
+        assert_eq!(&buffer, b"x");
+        assert!(handler.script().is_finished());
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{SyscallHandler, STDERR, STDIN, STDOUT};
+
+/// An entry in the virtual file descriptor table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Descriptor {
+    /// Standard input.
+    Stdin,
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+    /// A read-only file opened with `OPEN`.
+    File {
+        /// The path the file was opened with.
+        path: Vec<u8>,
+        /// The offset of the next byte to read.
+        position: usize,
+    },
+}
+
+/// Services system calls entirely in memory.
+///
+/// Files live in an in-memory filesystem, standard input is a fixed buffer, and anything written
+/// to standard output or standard error is captured. Runs are deterministic and never touch the
+/// host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualSyscallHandler {
+    /// The virtual filesystem, mapping paths to file contents.
+    #[serde(with = "file_list")]
+    files: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Open file descriptors.
+    descriptors: BTreeMap<u8, Descriptor>,
+    /// Data available on standard input.
+    stdin: Vec<u8>,
+    /// The offset of the next byte to read from standard input.
+    stdin_position: usize,
+    /// Everything written to standard output.
+    stdout: Vec<u8>,
+    /// Everything written to standard error.
+    stderr: Vec<u8>,
+}
+
+impl VirtualSyscallHandler {
+    /// Constructs a handler with an empty filesystem whose standard input contains `stdin`.
+    pub fn new(stdin: impl Into<Vec<u8>>) -> Self {
+        Self {
+            files: BTreeMap::new(),
+            descriptors: BTreeMap::from([
+                (STDIN, Descriptor::Stdin),
+                (STDOUT, Descriptor::Stdout),
+                (STDERR, Descriptor::Stderr),
+            ]),
+            stdin: stdin.into(),
+            stdin_position: 0,
+            stdout: vec![],
+            stderr: vec![],
+        }
+    }
+
+    /// Creates or replaces the file at `path` in the virtual filesystem.
+    pub fn add_file(&mut self, path: impl Into<Vec<u8>>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into());
+    }
+
+    /// Appends `bytes` to the data available on standard input.
+    pub fn feed_stdin(&mut self, bytes: &[u8]) {
+        self.stdin.extend_from_slice(bytes);
+    }
+
+    /// Everything written to standard output so far.
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Everything written to standard error so far.
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Finds the lowest file descriptor that isn't in use.
+    fn next_fd(&self) -> Result<u8> {
+        (0..=u8::MAX)
+            .find(|fd| !self.descriptors.contains_key(fd))
+            .ok_or_else(|| anyhow!("Too many open files"))
+    }
+}
+
+impl Default for VirtualSyscallHandler {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
+}
+
+/// Serde helpers that represent the virtual filesystem as a list of `[path, contents]` pairs,
+/// since formats like JSON only allow string keys.
+mod file_list {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serializes the filesystem as a list of pairs.
+    pub fn serialize<S: Serializer>(
+        files: &BTreeMap<Vec<u8>, Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(files)
+    }
+
+    /// Deserializes the filesystem from a list of pairs.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, D::Error> {
+        let pairs = Vec::<(Vec<u8>, Vec<u8>)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// Copies as much of `source[*position..]` as fits into `buffer`, advancing `position`.
+fn read_at(source: &[u8], position: &mut usize, buffer: &mut [u8]) -> usize {
+    let remaining = source.get(*position..).unwrap_or_default();
+    let n = remaining.len().min(buffer.len());
+
+    buffer[..n].copy_from_slice(&remaining[..n]);
+    *position += n;
+
+    n
+}
+
+impl SyscallHandler for VirtualSyscallHandler {
+    fn open(&mut self, path: &[u8]) -> Result<u8> {
+        if !self.files.contains_key(path) {
+            bail!(
+                "No such file in the virtual filesystem: {}",
+                String::from_utf8_lossy(path)
+            );
+        }
+
+        let fd = self.next_fd()?;
+        self.descriptors.insert(
+            fd,
+            Descriptor::File {
+                path: path.to_vec(),
+                position: 0,
+            },
+        );
+
+        Ok(fd)
+    }
+
+    fn read(&mut self, fd: u8, buffer: &mut [u8]) -> Result<usize> {
+        match self.descriptors.get_mut(&fd) {
+            Some(Descriptor::Stdin) => Ok(read_at(&self.stdin, &mut self.stdin_position, buffer)),
+            Some(Descriptor::File { path, position }) => {
+                let contents = self
+                    .files
+                    .get(path)
+                    .expect("files are never removed from the filesystem");
+
+                Ok(read_at(contents, position, buffer))
+            }
+            _ => bail!("Bad file descriptor for reading: {fd}"),
+        }
+    }
+
+    fn write(&mut self, fd: u8, buffer: &[u8]) -> Result<usize> {
+        match self.descriptors.get(&fd) {
+            Some(Descriptor::Stdout) => self.stdout.extend_from_slice(buffer),
+            Some(Descriptor::Stderr) => self.stderr.extend_from_slice(buffer),
+            _ => bail!("Bad file descriptor for writing: {fd}"),
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn captured_output(&self) -> Option<&[u8]> {
+        Some(&self.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_stdin_in_chunks() {
+        let mut handler = VirtualSyscallHandler::new("hello");
+        let mut buffer = [0; 3];
+
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 3);
+        assert_eq!(&buffer, b"hel");
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 2);
+        assert_eq!(&buffer[..2], b"lo");
+        assert_eq!(handler.read(STDIN, &mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_open_and_read_file() {
+        let mut handler = VirtualSyscallHandler::default();
+        handler.add_file("/flag", "pwn.college{fake}");
+
+        let fd = handler.open(b"/flag").unwrap();
+        assert_eq!(fd, 3);
+
+        let mut buffer = [0; 32];
+        let n = handler.read(fd, &mut buffer).unwrap();
+        assert_eq!(&buffer[..n], b"pwn.college{fake}");
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let mut handler = VirtualSyscallHandler::default();
+        assert!(handler.open(b"/flag").is_err());
+    }
+
+    #[test]
+    fn test_capture_output() {
+        let mut handler = VirtualSyscallHandler::default();
+
+        handler.write(STDOUT, b"out").unwrap();
+        handler.write(STDERR, b"err").unwrap();
+
+        assert_eq!(handler.stdout(), b"out");
+        assert_eq!(handler.stderr(), b"err");
+    }
+
+    #[test]
+    fn test_write_to_stdin() {
+        let mut handler = VirtualSyscallHandler::default();
+        assert!(handler.write(STDIN, b"nope").is_err());
+    }
+}
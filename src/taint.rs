@@ -0,0 +1,390 @@
+use std::{collections::BTreeSet, fmt};
+
+use crate::{
+    emulator::Effect,
+    syscall_handler::STDIN,
+    yan85::{
+        constants::{Constants, Decodable},
+        instruction::Instruction,
+        register::Register,
+        registers::Registers,
+        syscall::Syscall,
+    },
+};
+
+/// The offsets of the input bytes that a value was derived from.
+pub type Taint = BTreeSet<usize>;
+
+/// One side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operand {
+    /// The register compared.
+    pub register: Register,
+    /// The register's value.
+    pub value: u8,
+    /// The input bytes the value was derived from.
+    pub taint: Taint,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {:#04x} ", self.register, self.value)?;
+
+        if self.taint.is_empty() {
+            return write!(f, "(untainted)");
+        }
+
+        let offsets: Vec<String> = self.taint.iter().map(usize::to_string).collect();
+        write!(f, "(input[{}])", offsets.join(", "))
+    }
+}
+
+/// A `CMP` operation with at least one operand derived from input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The index of the instruction that compared.
+    pub index: u8,
+    /// The first operand.
+    pub left: Operand,
+    /// The second operand.
+    pub right: Operand,
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instruction {:#04x} compared {} with {}",
+            self.index, self.left, self.right
+        )
+    }
+}
+
+/// A place that a system call wrote to.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    /// A memory address.
+    Memory(u8),
+    /// A register.
+    Register(Register),
+    /// A byte of code, whose taint isn't tracked.
+    Code,
+}
+
+/// Follows bytes read from standard input through registers, memory, and the stack, and reports
+/// the comparisons they reach.
+///
+/// Bytes that `READ_MEMORY` reads from standard input are labelled with their offsets in the input.
+/// `ADD` merges labels, `IMM` and other system calls clear them, and loads, stores, pushes, and pops
+/// move them along with the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaintTracker {
+    /// The taint of each register, in the order of [`Register::ALL`].
+    registers: [Taint; 7],
+    /// The taint of each memory address.
+    memory: Vec<Taint>,
+    /// The taint of each stack slot.
+    stack: Vec<Taint>,
+    /// The number of bytes read from standard input so far.
+    input_offset: usize,
+    /// Comparisons of tainted values that haven't been taken yet.
+    comparisons: Vec<Comparison>,
+}
+
+impl Default for TaintTracker {
+    fn default() -> Self {
+        Self {
+            registers: Default::default(),
+            memory: vec![Taint::new(); 256],
+            stack: vec![Taint::new(); 256],
+            input_offset: 0,
+            comparisons: Vec::new(),
+        }
+    }
+}
+
+impl TaintTracker {
+    /// The taint of `register`.
+    pub fn register(&self, register: Register) -> &Taint {
+        &self.registers[register as usize]
+    }
+
+    /// The taint of the memory at `address`.
+    pub fn memory(&self, address: u8) -> &Taint {
+        &self.memory[address as usize]
+    }
+
+    /// The number of bytes read from standard input so far.
+    pub fn input_offset(&self) -> usize {
+        self.input_offset
+    }
+
+    /// Takes the comparisons of tainted values recorded since the last call, in the order they
+    /// occurred.
+    pub fn take_comparisons(&mut self) -> Vec<Comparison> {
+        std::mem::take(&mut self.comparisons)
+    }
+
+    /// Propagates taint through `instruction`, which was executed at `index` from the registers
+    /// `before` with the side effects `effects`.
+    pub fn record(
+        &mut self,
+        index: u8,
+        instruction: &Instruction,
+        before: &Registers,
+        effects: &[Effect],
+        constants: Constants,
+    ) {
+        // Register values as of each operation of a compound instruction.
+        let mut values = *before;
+        let mut effects = effects.iter();
+
+        for operation in instruction.operations() {
+            match *operation {
+                Instruction::IMM(register, value) => {
+                    self.set_register(register, Taint::new());
+                    values[register] = value;
+                }
+                Instruction::ADD(a, b) => {
+                    let taint = self.register(b).clone();
+                    self.registers[a as usize].extend(taint);
+                    values[a] = values[a].wrapping_add(values[b]);
+                }
+                Instruction::STK(pop, push) => {
+                    if let Some(src) = push {
+                        let taint = self.register(src).clone();
+                        match effects.find(|effect| {
+                            matches!(
+                                effect,
+                                Effect::StackWrite { .. } | Effect::MemoryWrite { .. }
+                            )
+                        }) {
+                            Some(Effect::StackWrite { address, .. }) => {
+                                self.stack[*address as usize] = taint
+                            }
+                            Some(Effect::MemoryWrite { address, .. }) => {
+                                self.memory[*address as usize] = taint
+                            }
+                            _ => {}
+                        }
+                        values[Register::S] = values[Register::S].wrapping_add(1);
+                    }
+
+                    if let Some(dest) = pop {
+                        match effects.find(|effect| {
+                            matches!(effect, Effect::StackRead { .. } | Effect::MemoryRead { .. })
+                        }) {
+                            Some(&Effect::StackRead { address, value }) => {
+                                self.set_register(dest, self.stack[address as usize].clone());
+                                values[dest] = value;
+                            }
+                            Some(&Effect::MemoryRead { address, value }) => {
+                                self.set_register(dest, self.memory(address).clone());
+                                values[dest] = value;
+                            }
+                            _ => {}
+                        }
+                        values[Register::S] = values[Register::S].wrapping_sub(1);
+                    }
+                }
+                Instruction::STM(_, b) => {
+                    if let Some(&Effect::MemoryWrite { address, .. }) =
+                        effects.find(|effect| matches!(effect, Effect::MemoryWrite { .. }))
+                    {
+                        self.memory[address as usize] = self.register(b).clone();
+                    }
+                }
+                Instruction::LDM(a, _) => {
+                    if let Some(&Effect::MemoryRead { address, value }) =
+                        effects.find(|effect| matches!(effect, Effect::MemoryRead { .. }))
+                    {
+                        self.set_register(a, self.memory(address).clone());
+                        values[a] = value;
+                    }
+                }
+                Instruction::CMP(a, b) => {
+                    if !self.register(a).is_empty() || !self.register(b).is_empty() {
+                        self.comparisons.push(Comparison {
+                            index,
+                            left: self.operand(a, &values),
+                            right: self.operand(b, &values),
+                        });
+                    }
+                }
+                Instruction::JMP(..) => {}
+                Instruction::SYS(syscalls, return_register) => {
+                    let calls = Vec::<Syscall>::decode(syscalls, constants).map_or(0, |s| s.len());
+                    self.record_syscalls(calls, return_register, &mut values, &mut effects);
+                }
+                Instruction::Compound(_) => unreachable!("operations aren't compound"),
+            }
+        }
+    }
+
+    /// Propagates taint through up to `calls` system calls, whose side effects come next in
+    /// `effects`.
+    fn record_syscalls<'a>(
+        &mut self,
+        mut calls: usize,
+        return_register: Option<Register>,
+        values: &mut Registers,
+        effects: &mut impl Iterator<Item = &'a Effect>,
+    ) {
+        let mut written = vec![];
+
+        while calls > 0 {
+            let Some(effect) = effects.next() else {
+                return;
+            };
+
+            match *effect {
+                Effect::MemoryWrite { address, .. } => written.push(Location::Memory(address)),
+                Effect::RegisterWrite { register, new, .. } => {
+                    written.push(Location::Register(register));
+                    values[register] = new;
+                }
+                Effect::CodeWrite { .. } => written.push(Location::Code),
+                Effect::Syscall {
+                    syscall,
+                    arguments: [fd, ..],
+                    return_value,
+                } => {
+                    let from_stdin =
+                        fd == STDIN && matches!(syscall, Syscall::ReadCode | Syscall::ReadMemory);
+
+                    for location in written.drain(..) {
+                        let mut taint = Taint::new();
+                        if from_stdin {
+                            if syscall == Syscall::ReadMemory {
+                                taint.insert(self.input_offset);
+                            }
+                            self.input_offset += 1;
+                        }
+
+                        match location {
+                            Location::Memory(address) => self.memory[address as usize] = taint,
+                            Location::Register(register) => self.set_register(register, taint),
+                            Location::Code => {}
+                        }
+                    }
+
+                    if let (Some(register), Some(value)) = (return_register, return_value) {
+                        self.set_register(register, Taint::new());
+                        values[register] = value;
+                    }
+
+                    calls -= 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces the taint of `register`.
+    fn set_register(&mut self, register: Register, taint: Taint) {
+        self.registers[register as usize] = taint;
+    }
+
+    /// Describes `register` as a comparison operand.
+    fn operand(&self, register: Register, values: &Registers) -> Operand {
+        Operand {
+            register,
+            value: values[register],
+            taint: self.register(register).clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        emulator::Emulator,
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    #[test]
+    fn test_propagation() {
+        let consts = Constants::default();
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                // read_memory(0, 0x10, 3)
+                Instruction::IMM(Register::A, 0),
+                Instruction::IMM(Register::B, 0x10),
+                Instruction::IMM(Register::C, 3),
+                Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+                // c = input[0] + input[2]
+                Instruction::LDM(Register::C, Register::B),
+                Instruction::IMM(Register::B, 0x12),
+                Instruction::LDM(Register::D, Register::B),
+                Instruction::ADD(Register::C, Register::D),
+                // move c to d through the stack and memory
+                Instruction::STK(None, Some(Register::C)),
+                Instruction::STK(Some(Register::A), None),
+                Instruction::STM(Register::B, Register::A),
+                Instruction::LDM(Register::D, Register::B),
+                Instruction::IMM(Register::A, 0x90),
+                Instruction::CMP(Register::D, Register::A),
+                // untainted
+                Instruction::CMP(Register::A, Register::A),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::new("ABC"),
+        )
+        .unwrap();
+
+        let mut tracker = TaintTracker::default();
+        for _ in 0..15 {
+            let index = emulator.registers()[Register::I];
+            let instruction = emulator.next_instruction().unwrap();
+            let before = *emulator.registers();
+
+            emulator.step().unwrap();
+            tracker.record(index, &instruction, &before, emulator.effects(), consts);
+        }
+
+        assert_eq!(tracker.input_offset(), 3);
+        assert_eq!(*tracker.memory(0x11), Taint::from([1]));
+        assert_eq!(*tracker.memory(0x12), Taint::from([0, 2]));
+        assert!(tracker.register(Register::A).is_empty());
+
+        assert_eq!(
+            tracker.take_comparisons(),
+            vec![Comparison {
+                index: 13,
+                left: Operand {
+                    register: Register::D,
+                    value: b'A' + b'C',
+                    taint: Taint::from([0, 2]),
+                },
+                right: Operand {
+                    register: Register::A,
+                    value: 0x90,
+                    taint: Taint::new(),
+                },
+            }]
+        );
+        assert!(tracker.take_comparisons().is_empty());
+    }
+
+    #[test]
+    fn test_compound_values() {
+        let mut tracker = TaintTracker::default();
+        tracker.set_register(Register::A, Taint::from([4]));
+
+        let instruction = Instruction::Compound(vec![
+            Instruction::IMM(Register::B, 0x30),
+            Instruction::CMP(Register::A, Register::B),
+        ]);
+        let mut before = Registers::default();
+        before[Register::A] = 0x31;
+
+        tracker.record(7, &instruction, &before, &[], Constants::default());
+
+        let comparisons = tracker.take_comparisons();
+        assert_eq!(comparisons[0].left.value, 0x31);
+        assert_eq!(comparisons[0].right.value, 0x30);
+    }
+}
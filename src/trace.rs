@@ -0,0 +1,136 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    emulator::{Effect, Emulator, StepOutcome},
+    syscall_handler::SyscallHandler,
+    yan85::{instruction::Instruction, register::Register, registers::Registers},
+};
+
+/// A record of a single executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceRecord {
+    /// The number of instructions executed before this one.
+    pub step: u64,
+    /// The instruction index, i.e. the value of register I before execution.
+    pub index: u8,
+    /// The raw instruction bytes, in encoded order.
+    pub bytes: [u8; 3],
+    /// The decoded instruction.
+    pub instruction: Instruction,
+    /// Register values before execution.
+    pub registers_before: Registers,
+    /// Register values after execution.
+    pub registers_after: Registers,
+    /// Memory, stack, and code accesses and system calls, in the order they occurred.
+    pub effects: Vec<Effect>,
+}
+
+/// Writes an execution trace as JSON Lines, one [`TraceRecord`] per executed instruction.
+pub struct Tracer<W: Write> {
+    /// The trace destination.
+    writer: W,
+    /// The number of instructions traced so far.
+    steps: u64,
+}
+
+impl<W: Write> Tracer<W> {
+    /// Constructs a tracer that writes records to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer, steps: 0 }
+    }
+
+    /// Steps `emulator` through the next instruction, writing a record of it to the trace.
+    pub fn step<H: SyscallHandler>(&mut self, emulator: &mut Emulator<H>) -> Result<StepOutcome> {
+        let index = emulator.registers()[Register::I];
+        let bytes = emulator.code().get_instruction(index);
+        let instruction = emulator.next_instruction()?;
+        let registers_before = *emulator.registers();
+
+        let outcome = emulator.step()?;
+
+        let record = TraceRecord {
+            step: self.steps,
+            index,
+            bytes,
+            instruction,
+            registers_before,
+            registers_after: *emulator.registers(),
+            effects: emulator.effects().to_vec(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &record)?;
+        writeln!(self.writer)?;
+        self.steps += 1;
+
+        Ok(outcome)
+    }
+
+    /// Flushes buffered records to the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        syscall_handler::VirtualSyscallHandler,
+        yan85::{constants::Constants, memory::Memory},
+    };
+
+    #[test]
+    fn test_trace_records() {
+        let consts = Constants::default();
+        let mut emulator = Emulator::from_instructions_with_handler(
+            consts,
+            vec![
+                Instruction::IMM(Register::A, 0x10),
+                Instruction::STM(Register::A, Register::A),
+                Instruction::SYS(consts.syscall.EXIT, None),
+            ],
+            Memory::default(),
+            VirtualSyscallHandler::default(),
+        )
+        .unwrap();
+
+        let mut tracer = Tracer::new(vec![]);
+        for _ in 0..3 {
+            tracer.step(&mut emulator).unwrap();
+        }
+
+        let lines: Vec<Value> = String::from_utf8(tracer.writer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+
+        assert_eq!(lines[0]["index"], 0);
+        assert_eq!(lines[0]["instruction"], json!({"IMM": ["a", 0x10]}));
+        assert_eq!(lines[0]["registers_before"]["a"], 0);
+        assert_eq!(lines[0]["registers_after"]["a"], 0x10);
+
+        assert_eq!(
+            lines[1]["effects"],
+            json!([{"kind": "memory_write", "address": 0x10, "old": 0, "new": 0x10}])
+        );
+
+        assert_eq!(
+            lines[2]["effects"],
+            json!([{
+                "kind": "syscall",
+                "syscall": "EXIT",
+                "arguments": [0x10, 0, 0],
+                "return_value": null,
+            }])
+        );
+    }
+}
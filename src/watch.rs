@@ -0,0 +1,367 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    emulator::Effect,
+    yan85::{instruction::Instruction, register::Register, registers::Registers},
+};
+
+/// The kinds of memory access a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reads, e.g. by `LDM` or `WRITE`.
+    Read,
+    /// Writes, e.g. by `STM` or `READ_MEMORY`.
+    Write,
+    /// Both reads and writes.
+    ReadWrite,
+}
+
+/// A condition to watch for while emulating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// An access to a range of memory addresses.
+    Memory {
+        /// The watched addresses.
+        range: RangeInclusive<u8>,
+        /// The watched kinds of access.
+        access: Access,
+    },
+    /// A change to the value of a stack slot.
+    ///
+    /// When the stack lives in memory, watch its memory addresses instead.
+    Stack {
+        /// The watched stack address.
+        address: u8,
+    },
+    /// A register taking a particular value.
+    Register {
+        /// The watched register.
+        register: Register,
+        /// The value to watch for.
+        value: u8,
+    },
+}
+
+/// What to do when a watchpoint is triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Stop execution.
+    Break,
+    /// Report the hit and keep going.
+    Log,
+}
+
+/// A watchpoint together with what to do when it's triggered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    /// The condition to watch for.
+    pub watchpoint: Watchpoint,
+    /// What to do when the condition holds.
+    pub action: WatchAction,
+}
+
+/// The event that triggered a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// A memory or stack access.
+    Access(Effect),
+    /// A register changed value.
+    Register {
+        /// The register.
+        register: Register,
+        /// The value before the instruction.
+        old: u8,
+        /// The value after the instruction.
+        new: u8,
+    },
+}
+
+/// A triggered watchpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The number of the watchpoint, e.g. its position in
+    /// [`RunOptions::watchpoints`](crate::run::RunOptions::watchpoints).
+    pub watchpoint: usize,
+    /// The index of the instruction that triggered it.
+    pub index: u8,
+    /// The instruction that triggered it, if it could be decoded.
+    pub instruction: Option<Instruction>,
+    /// What happened.
+    pub event: WatchEvent,
+}
+
+impl Watchpoint {
+    /// Finds the first event of an instruction that triggers this watchpoint, given the register
+    /// values around the instruction and its effects.
+    pub fn check(
+        &self,
+        before: &Registers,
+        after: &Registers,
+        effects: &[Effect],
+    ) -> Option<WatchEvent> {
+        match self {
+            Watchpoint::Memory { range, access } => effects
+                .iter()
+                .find(|effect| match (effect, access) {
+                    (Effect::MemoryRead { address, .. }, Access::Read | Access::ReadWrite)
+                    | (Effect::MemoryWrite { address, .. }, Access::Write | Access::ReadWrite) => {
+                        range.contains(address)
+                    }
+                    _ => false,
+                })
+                .map(|&effect| WatchEvent::Access(effect)),
+            Watchpoint::Stack { address: watched } => effects
+                .iter()
+                .find(|effect| {
+                    matches!(effect, Effect::StackWrite { address, old, new }
+                        if address == watched && old != new)
+                })
+                .map(|&effect| WatchEvent::Access(effect)),
+            Watchpoint::Register { register, value } => {
+                let (old, new) = (before[*register], after[*register]);
+                (old != *value && new == *value).then_some(WatchEvent::Register {
+                    register: *register,
+                    old,
+                    new,
+                })
+            }
+        }
+    }
+}
+
+/// Checks an executed instruction against each of `watchpoints`, returning every hit.
+pub fn check_all<'a>(
+    watchpoints: impl IntoIterator<Item = &'a Watchpoint>,
+    index: u8,
+    instruction: Option<Instruction>,
+    before: &Registers,
+    after: &Registers,
+    effects: &[Effect],
+) -> Vec<WatchHit> {
+    watchpoints
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, watchpoint)| {
+            let event = watchpoint.check(before, after, effects)?;
+            Some(WatchHit {
+                watchpoint: i,
+                index,
+                instruction: instruction.clone(),
+                event,
+            })
+        })
+        .collect()
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal byte.
+fn parse_byte(word: &str) -> Result<u8> {
+    match word.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => word.parse(),
+    }
+    .with_context(|| format!("Invalid byte: {word:?}"))
+}
+
+/// Parses watchpoint specifications:
+///
+/// - `memory:<address>[-<end>][:r|w|rw]`, e.g. `memory:0x40-0x4f:w`. Watches reads and writes
+///   unless specified otherwise.
+/// - `stack:<address>`, e.g. `stack:3`.
+/// - `register:<register>=<value>`, e.g. `register:a=0x42`.
+///
+/// `mem`, `stk`, and `reg` are accepted as abbreviations.
+impl FromStr for Watchpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let (kind, rest) = spec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid watchpoint {spec:?}: expected <kind>:<location>"))?;
+
+        let watchpoint = match kind {
+            "memory" | "mem" => {
+                let (range, access) = match rest.split_once(':') {
+                    Some((range, "r")) => (range, Access::Read),
+                    Some((range, "w")) => (range, Access::Write),
+                    Some((range, "rw")) => (range, Access::ReadWrite),
+                    Some((_, access)) => bail!("Invalid memory access kind: {access:?}"),
+                    None => (rest, Access::ReadWrite),
+                };
+
+                let range = match range.split_once('-') {
+                    Some((start, end)) => parse_byte(start)?..=parse_byte(end)?,
+                    None => parse_byte(range)?..=parse_byte(range)?,
+                };
+                if range.is_empty() {
+                    bail!("Empty memory range in watchpoint {spec:?}");
+                }
+
+                Watchpoint::Memory { range, access }
+            }
+            "stack" | "stk" => Watchpoint::Stack {
+                address: parse_byte(rest)?,
+            },
+            "register" | "reg" => {
+                let (register, value) = rest.split_once('=').ok_or_else(|| {
+                    anyhow!("Invalid register watchpoint: expected <reg>=<value>")
+                })?;
+
+                Watchpoint::Register {
+                    register: Register::try_from(register)?,
+                    value: parse_byte(value)?,
+                }
+            }
+            _ => bail!("Invalid watchpoint kind: {kind:?}"),
+        };
+
+        Ok(watchpoint)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watchpoint::Memory { range, access } => {
+                write!(f, "memory[{:#04x}", range.start())?;
+                if range.start() != range.end() {
+                    write!(f, "..={:#04x}", range.end())?;
+                }
+
+                let access = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                    Access::ReadWrite => "read/write",
+                };
+                write!(f, "] ({access})")
+            }
+            Watchpoint::Stack { address } => write!(f, "stack[{address:#04x}]"),
+            Watchpoint::Register { register, value } => write!(f, "{register} == {value:#04x}"),
+        }
+    }
+}
+
+impl fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchEvent::Access(Effect::MemoryRead { address, value }) => {
+                write!(f, "read memory[{address:#04x}] == {value:#04x}")
+            }
+            WatchEvent::Access(Effect::MemoryWrite { address, old, new }) => {
+                write!(f, "wrote memory[{address:#04x}]: {old:#04x} -> {new:#04x}")
+            }
+            WatchEvent::Access(Effect::StackWrite { address, old, new }) => {
+                write!(f, "wrote stack[{address:#04x}]: {old:#04x} -> {new:#04x}")
+            }
+            WatchEvent::Access(effect) => write!(f, "{effect:?}"),
+            WatchEvent::Register { register, old, new } => {
+                write!(f, "{register}: {old:#04x} -> {new:#04x}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "watchpoint {} hit by instruction {:#04x}",
+            self.watchpoint, self.index
+        )?;
+        if let Some(instruction) = &self.instruction {
+            write!(f, " ({instruction})")?;
+        }
+        write!(f, ": {}", self.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "memory:0x40-0x4f:w".parse::<Watchpoint>().unwrap(),
+            Watchpoint::Memory {
+                range: 0x40..=0x4f,
+                access: Access::Write
+            }
+        );
+        assert_eq!(
+            "mem:16".parse::<Watchpoint>().unwrap(),
+            Watchpoint::Memory {
+                range: 16..=16,
+                access: Access::ReadWrite
+            }
+        );
+        assert_eq!(
+            "stack:3".parse::<Watchpoint>().unwrap(),
+            Watchpoint::Stack { address: 3 }
+        );
+        assert_eq!(
+            "reg:a=0x42".parse::<Watchpoint>().unwrap(),
+            Watchpoint::Register {
+                register: Register::A,
+                value: 0x42
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("memory".parse::<Watchpoint>().is_err());
+        assert!("memory:0x20-0x10".parse::<Watchpoint>().is_err());
+        assert!("memory:0x10:x".parse::<Watchpoint>().is_err());
+        assert!("register:a".parse::<Watchpoint>().is_err());
+        assert!("flag:0".parse::<Watchpoint>().is_err());
+    }
+
+    #[test]
+    fn test_memory_access_kinds() {
+        let write = [Effect::MemoryWrite {
+            address: 0x10,
+            old: 0,
+            new: 1,
+        }];
+        let registers = Registers::default();
+
+        let watch_reads: Watchpoint = "mem:0x10:r".parse().unwrap();
+        let watch_writes: Watchpoint = "mem:0x08-0x10:w".parse().unwrap();
+
+        assert_eq!(watch_reads.check(&registers, &registers, &write), None);
+        assert_eq!(
+            watch_writes.check(&registers, &registers, &write),
+            Some(WatchEvent::Access(write[0]))
+        );
+    }
+
+    #[test]
+    fn test_stack_unchanged() {
+        let write = [Effect::StackWrite {
+            address: 3,
+            old: 7,
+            new: 7,
+        }];
+        let registers = Registers::default();
+
+        let watchpoint = Watchpoint::Stack { address: 3 };
+        assert_eq!(watchpoint.check(&registers, &registers, &write), None);
+    }
+
+    #[test]
+    fn test_register_takes_value() {
+        let watchpoint = Watchpoint::Register {
+            register: Register::A,
+            value: 5,
+        };
+
+        let before = Registers::default();
+        let mut after = Registers::default();
+        after[Register::A] = 5;
+
+        assert!(watchpoint.check(&before, &after, &[]).is_some());
+        assert!(watchpoint.check(&after, &after, &[]).is_none());
+    }
+}
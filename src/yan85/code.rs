@@ -1,36 +1,71 @@
 use std::ops::{Index, IndexMut, Range, RangeFrom};
 
 use anyhow::bail;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// The size of the code storage in bytes.
 ///
 /// Can store up to 256 instructions, each occupying 3 bytes.
-const CODE_SIZE: usize = 256 * 3;
+pub const CODE_SIZE: usize = 256 * 3;
 
 /// 768-byte Yan85 code storage.
-#[derive(Debug)]
-pub struct Code([u8; CODE_SIZE]);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Code {
+    /// The raw machine code.
+    bytes: [u8; CODE_SIZE],
+    /// Whether each byte was written at runtime, e.g. by a `READ_CODE` syscall.
+    modified: [bool; CODE_SIZE],
+}
 
 impl Code {
     /// Get the instruction 3-tuple for instruction number `number`.
     pub fn get_instruction(&self, number: u8) -> [u8; 3] {
-        [
-            self[3 * number as usize],
-            self[((3 * number) + 1) as usize],
-            self[((3 * number) + 2) as usize],
-        ]
+        let offset = 3 * number as usize;
+        [self[offset], self[offset + 1], self[offset + 2]]
+    }
+
+    /// Overwrites code starting at byte offset `offset` with `bytes`, marking the affected bytes as
+    /// modified at runtime.
+    ///
+    /// Panics if the write extends past the end of the code storage.
+    pub fn write(&mut self, offset: usize, bytes: &[u8]) {
+        let range = offset..offset + bytes.len();
+
+        self.bytes[range.clone()].copy_from_slice(bytes);
+        self.modified[range].fill(true);
+    }
+
+    /// Checks whether the byte at offset `offset` was written at runtime.
+    pub fn is_byte_modified(&self, offset: usize) -> bool {
+        self.modified[offset]
+    }
+
+    /// Sets the byte at offset `offset` and whether it counts as written at runtime, e.g. to undo
+    /// a [`Code::write`].
+    pub fn restore(&mut self, offset: usize, byte: u8, modified: bool) {
+        self.bytes[offset] = byte;
+        self.modified[offset] = modified;
+    }
+
+    /// Checks whether any byte of instruction number `number` was written at runtime.
+    pub fn is_instruction_modified(&self, number: u8) -> bool {
+        let offset = 3 * number as usize;
+        self.modified[offset..offset + 3].contains(&true)
     }
 }
 
 impl Default for Code {
     fn default() -> Self {
-        Self([0; CODE_SIZE])
+        Self::from([0; CODE_SIZE])
     }
 }
 
 impl From<[u8; CODE_SIZE]> for Code {
     fn from(value: [u8; CODE_SIZE]) -> Self {
-        Self(value)
+        Self {
+            bytes: value,
+            modified: [false; CODE_SIZE],
+        }
     }
 }
 
@@ -46,17 +81,55 @@ impl TryFrom<Vec<u8>> for Code {
 
         code.resize(CODE_SIZE, 0);
 
-        Ok(Self(
-            code.try_into().expect("We resized it to the correct size"),
+        Ok(Self::from(
+            <[u8; CODE_SIZE]>::try_from(code).expect("We resized it to the correct size"),
         ))
     }
 }
 
+/// The serialized form of [`Code`].
+#[derive(Serialize, Deserialize)]
+struct SerializedCode {
+    /// The raw machine code.
+    #[serde(with = "super::hex")]
+    bytes: [u8; CODE_SIZE],
+    /// Offsets of bytes that were written at runtime.
+    modified: Vec<usize>,
+}
+
+impl Serialize for Code {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedCode {
+            bytes: self.bytes,
+            modified: (0..CODE_SIZE).filter(|&i| self.modified[i]).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Code {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedCode::deserialize(deserializer)?;
+
+        let mut code = Code::from(serialized.bytes);
+        for offset in serialized.modified {
+            if offset >= CODE_SIZE {
+                return Err(serde::de::Error::custom(format!(
+                    "modified offset out of range: {offset}"
+                )));
+            }
+            code.modified[offset] = true;
+        }
+
+        Ok(code)
+    }
+}
+
 impl Index<usize> for Code {
     type Output = u8;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+        &self.bytes[index]
     }
 }
 
@@ -64,7 +137,7 @@ impl Index<Range<usize>> for Code {
     type Output = [u8];
 
     fn index(&self, index: Range<usize>) -> &Self::Output {
-        &self.0[index.start..index.end]
+        &self.bytes[index.start..index.end]
     }
 }
 
@@ -72,18 +145,42 @@ impl Index<RangeFrom<usize>> for Code {
     type Output = [u8];
 
     fn index(&self, index: RangeFrom<usize>) -> &Self::Output {
-        &self.0[index.start..]
+        &self.bytes[index.start..]
     }
 }
 
 impl IndexMut<usize> for Code {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+        &mut self.bytes[index]
     }
 }
 
 impl IndexMut<Range<usize>> for Code {
     fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
-        &mut self.0[index.start..index.end]
+        &mut self.bytes[index.start..index.end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_last_instruction() {
+        let mut code = Code::default();
+        code[CODE_SIZE - 1] = 0x42;
+
+        assert_eq!(code.get_instruction(255), [0, 0, 0x42]);
+    }
+
+    #[test]
+    fn test_write_marks_modified() {
+        let mut code = Code::default();
+        code.write(4, &[1, 2]);
+
+        assert_eq!(code.get_instruction(1), [0, 1, 2]);
+        assert!(!code.is_instruction_modified(0));
+        assert!(code.is_instruction_modified(1));
+        assert!(!code.is_instruction_modified(2));
     }
 }
@@ -1,5 +1,7 @@
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+use super::{opcode::Opcode, syscall::Syscall};
 
 /// Instruction encoding specification that varies from level to level.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +16,9 @@ pub struct Constants {
     pub syscall: SyscallConstants,
     /// The flag constants.
     pub flag: FlagConstants,
+    /// How the interpreter behaves where levels differ. Optional in constants files.
+    #[serde(default)]
+    pub semantics: SemanticsConstants,
 }
 
 /// Encodable to a byte with the constants dictionary as context.
@@ -77,6 +82,40 @@ pub struct OpcodeConstants {
     pub JMP: u8,
     /// The constant for the SYS opcode.
     pub SYS: u8,
+    /// The order in which the interpreter tests opcode bits, which is the order that compound
+    /// instructions perform their operations in. Optional in constants files.
+    #[serde(
+        default = "default_opcode_order",
+        deserialize_with = "deserialize_order"
+    )]
+    pub order: [Opcode; 8],
+}
+
+/// The opcode order used when a constants file doesn't specify one.
+fn default_opcode_order() -> [Opcode; 8] {
+    Opcode::ALL
+}
+
+/// Deserializes a dispatch order, which must list each of the `N` possible items exactly once.
+fn deserialize_order<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialEq,
+{
+    let order = Vec::<T>::deserialize(deserializer)?;
+
+    if order
+        .iter()
+        .enumerate()
+        .any(|(i, item)| order[..i].contains(item))
+    {
+        return Err(D::Error::custom("an item is listed more than once"));
+    }
+
+    let length = order.len();
+    order
+        .try_into()
+        .map_err(|_| D::Error::invalid_length(length, &format!("{N} items").as_str()))
 }
 
 impl Default for OpcodeConstants {
@@ -90,6 +129,7 @@ impl Default for OpcodeConstants {
             CMP: 0x20,
             JMP: 0x40,
             SYS: 0x80,
+            order: default_opcode_order(),
         }
     }
 }
@@ -144,6 +184,18 @@ pub struct SyscallConstants {
     pub SLEEP: u8,
     /// The syscall number for the `EXIT` syscall.
     pub EXIT: u8,
+    /// The order in which the interpreter tests syscall bits, which is the order that a `SYS`
+    /// instruction with several bits set performs its syscalls in. Optional in constants files.
+    #[serde(
+        default = "default_syscall_order",
+        deserialize_with = "deserialize_order"
+    )]
+    pub order: [Syscall; 6],
+}
+
+/// The syscall order used when a constants file doesn't specify one.
+fn default_syscall_order() -> [Syscall; 6] {
+    Syscall::ALL
 }
 
 impl Default for SyscallConstants {
@@ -155,6 +207,7 @@ impl Default for SyscallConstants {
             WRITE: 0x8,
             SLEEP: 0x10,
             EXIT: 0x20,
+            order: default_syscall_order(),
         }
     }
 }
@@ -187,6 +240,96 @@ impl Default for FlagConstants {
     }
 }
 
+/// Interpreter behavior that varies between Yan85 implementations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SemanticsConstants {
+    /// Where the stack lives and how pushes and pops update the stack pointer.
+    #[serde(default)]
+    pub stack: StackLayout,
+    /// When `JMP` instructions jump.
+    #[serde(default)]
+    pub jump: JumpSemantics,
+    /// How `CMP` instructions set the flag register.
+    #[serde(default)]
+    pub cmp: CmpSemantics,
+    /// What system calls that transfer data reach when they run past the end of code or memory.
+    #[serde(default)]
+    pub layout: Layout,
+}
+
+/// The location of the stack and the order of stack pointer updates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StackLayout {
+    /// The stack is separate from memory. Pushes store at `s` and then increment it, and pops
+    /// decrement `s` and then load.
+    #[default]
+    Separate,
+    /// The stack lives in memory, as in the reference VM. Pushes increment `s` and then store at
+    /// `memory[s]`, and pops load from `memory[s]` and then decrement it.
+    Memory,
+}
+
+/// The arrangement of code, memory, and registers seen by system calls that transfer data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// Code and memory are separate, and transfers are truncated at the end of either.
+    #[default]
+    Separate,
+    /// Code, memory, and registers `a` through `f` are contiguous, as in the reference VM's
+    /// state struct. Transfers past the end of code continue into memory, and those past the end
+    /// of memory continue into the registers. Transfers are truncated only at the end of the
+    /// registers.
+    Flat,
+}
+
+/// The conditions under which a `JMP` instruction jumps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JumpSemantics {
+    /// Jumps if any flag in the condition is set in the flag register, or unconditionally if the
+    /// condition is empty, as in the reference VM.
+    Reference,
+    /// Jumps only if a flag in the condition is set in the flag register, so a jump with an empty
+    /// condition never jumps. The default, which gyan85 has always followed.
+    #[default]
+    Strict,
+}
+
+/// The way a `CMP` instruction sets the flag register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CmpSemantics {
+    /// When the "zeroes" flag is set.
+    #[serde(default)]
+    pub zero_flag: ZeroFlag,
+    /// Whether the comparison's flags replace or add to the flags already set.
+    #[serde(default)]
+    pub flags: FlagUpdate,
+}
+
+/// The operands for which a `CMP` instruction sets the "zeroes" flag.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZeroFlag {
+    /// Set when both operands are zero, as in the reference VM.
+    #[default]
+    Both,
+    /// Set when either operand is zero.
+    Either,
+}
+
+/// How a `CMP` instruction combines its flags with those already in the flag register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagUpdate {
+    /// The flag register is cleared first, as in the reference VM.
+    #[default]
+    Replace,
+    /// The new flags are ORed into the flag register.
+    Accumulate,
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -200,4 +343,42 @@ mod tests {
 
         assert_eq!(consts, Constants::default())
     }
+
+    #[test]
+    fn test_opcode_order() {
+        let yaml = fs::read_to_string("constants.yml").unwrap();
+
+        let reordered = yaml.replace("[IMM, ADD", "[ADD, IMM");
+        let consts: Constants = serde_yaml::from_str(&reordered).unwrap();
+        assert_eq!(consts.opcode.order[..2], [Opcode::Add, Opcode::Imm]);
+
+        let duplicated = yaml.replace("[IMM, ADD", "[IMM, IMM");
+        assert!(serde_yaml::from_str::<Constants>(&duplicated).is_err());
+
+        let truncated = yaml.replace("[IMM, ADD", "[ADD");
+        assert!(serde_yaml::from_str::<Constants>(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_semantics() {
+        let yaml = fs::read_to_string("constants.yml").unwrap();
+        let yaml = yaml.replace("stack: separate", "stack: memory");
+        let consts: Constants = serde_yaml::from_str(&yaml).unwrap();
+
+        assert_eq!(consts.semantics.stack, StackLayout::Memory);
+        assert_eq!(consts.semantics.jump, JumpSemantics::Strict);
+        assert_eq!(consts.semantics.layout, Layout::Separate);
+    }
+
+    #[test]
+    fn test_partial_semantics() {
+        let yaml = fs::read_to_string("constants.yml").unwrap();
+        let (yaml, _) = yaml.split_once("semantics:").unwrap();
+        let yaml = format!("{yaml}semantics:\n    cmp:\n        flags: accumulate\n");
+        let consts: Constants = serde_yaml::from_str(&yaml).unwrap();
+
+        assert_eq!(consts.semantics.stack, StackLayout::Separate);
+        assert_eq!(consts.semantics.cmp.zero_flag, ZeroFlag::Both);
+        assert_eq!(consts.semantics.cmp.flags, FlagUpdate::Accumulate);
+    }
 }
@@ -1,8 +1,12 @@
-use std::fmt::{self, Display, Write};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Write},
+};
 
 use anyhow::{bail, Result};
+use serde::{Serialize, Serializer};
 
-use super::constants::{Constants, Decodable, Encodable};
+use super::constants::{Constants, Decodable, Encodable, ZeroFlag};
 
 /// Comparison flags.
 ///
@@ -23,6 +27,33 @@ pub struct Flags {
 }
 
 impl Flags {
+    /// Checks whether no flags are enabled.
+    pub fn is_empty(&self) -> bool {
+        *self == Flags::default()
+    }
+
+    /// Enables the flags that describe how `a` compares to `b`, leaving enabled flags enabled.
+    pub fn record_comparison(&mut self, a: u8, b: u8, zero_flag: ZeroFlag) {
+        match a.cmp(&b) {
+            Ordering::Less => {
+                self.less_than = true;
+                self.not_equal = true;
+            }
+            Ordering::Greater => {
+                self.greater_than = true;
+                self.not_equal = true;
+            }
+            Ordering::Equal => {
+                self.equal = true;
+            }
+        }
+
+        self.zeroes |= match zero_flag {
+            ZeroFlag::Both => a == 0 && b == 0,
+            ZeroFlag::Either => a == 0 || b == 0,
+        };
+    }
+
     /// Checks if any of the "enabled" flags in `other` are also enabled in `self`.
     pub fn does_match(&self, other: &Flags) -> bool {
         (self.less_than && other.less_than)
@@ -103,6 +134,13 @@ impl Display for Flags {
     }
 }
 
+/// Serializes as the flag letters, e.g. `"LE"`.
+impl Serialize for Flags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl TryFrom<&str> for Flags {
     type Error = anyhow::Error;
 
@@ -0,0 +1,63 @@
+use std::fmt::Write;
+
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+/// Serializes bytes as a lowercase hexadecimal string.
+pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut hex = String::with_capacity(2 * bytes.len());
+    for byte in bytes {
+        write!(hex, "{byte:02x}").expect("writing to a string can't fail");
+    }
+
+    serializer.serialize_str(&hex)
+}
+
+/// Deserializes a fixed-size byte array from a hexadecimal string.
+pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[u8; N], D::Error> {
+    let hex = String::deserialize(deserializer)?;
+
+    if hex.len() != 2 * N {
+        return Err(D::Error::invalid_length(hex.len() / 2, &"the array size"));
+    }
+
+    let mut bytes = [0; N];
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let digits = std::str::from_utf8(digits).map_err(D::Error::custom)?;
+        *byte = u8::from_str_radix(digits, 16).map_err(D::Error::custom)?;
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    /// A wrapper for exercising the helpers through serde.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Bytes(#[serde(with = "super")] [u8; 3]);
+
+    #[test]
+    fn test_roundtrip() {
+        let json = serde_json::to_string(&Bytes([0x00, 0xab, 0x10])).unwrap();
+        assert_eq!(json, r#""00ab10""#);
+        assert_eq!(
+            serde_json::from_str::<Bytes>(&json).unwrap(),
+            Bytes([0x00, 0xab, 0x10])
+        );
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        assert!(serde_json::from_str::<Bytes>(r#""00ab""#).is_err());
+    }
+
+    #[test]
+    fn test_invalid_digits() {
+        assert!(serde_json::from_str::<Bytes>(r#""00abzz""#).is_err());
+    }
+}
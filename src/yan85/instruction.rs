@@ -1,7 +1,8 @@
 use colored::Colorize;
+use serde::Serialize;
 use std::fmt;
 
-use super::{flags::Flags, register::Register};
+use super::{constants::JumpSemantics, flags::Flags, opcode::Opcode, register::Register};
 
 /// Yan85 syscall number.
 pub type SysCall = u8;
@@ -13,7 +14,7 @@ pub type MutRegister = Register;
 pub type PointerRegister = Register;
 
 /// Yan85 instruction.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum Instruction {
     /// Immediate instruction that assigns an integer to a register.
     IMM(Register, u8),
@@ -37,9 +38,83 @@ pub enum Instruction {
     CMP(Register, Register),
     /// Jumps to the instruction referenced by a register if the specified condition is met by the
     /// value in the "flag" register.
+    ///
+    /// An empty condition, written `JMP <reg>`, never jumps unless the constants select
+    /// [reference](super::constants::JumpSemantics::Reference) jump semantics, under which it
+    /// jumps unconditionally.
     JMP(Flags, Register),
     /// Syscall instruction.
     SYS(SysCall, Option<Register>),
+    /// Several operations encoded in one instruction by setting several opcode bits, performed in
+    /// the opcode order given by the constants.
+    ///
+    /// The operations share the instruction's operand bytes. Written with the operations
+    /// separated by `|`, e.g. `IMM a = 0x02 | ADD a b`.
+    Compound(Vec<Instruction>),
+}
+
+impl Instruction {
+    /// The opcode of the instruction, or `None` if it's compound.
+    pub fn opcode(&self) -> Option<Opcode> {
+        match self {
+            Instruction::IMM(..) => Some(Opcode::Imm),
+            Instruction::ADD(..) => Some(Opcode::Add),
+            Instruction::STK(..) => Some(Opcode::Stk),
+            Instruction::STM(..) => Some(Opcode::Stm),
+            Instruction::LDM(..) => Some(Opcode::Ldm),
+            Instruction::CMP(..) => Some(Opcode::Cmp),
+            Instruction::JMP(..) => Some(Opcode::Jmp),
+            Instruction::SYS(..) => Some(Opcode::Sys),
+            Instruction::Compound(_) => None,
+        }
+    }
+
+    /// The operations the instruction performs, in order. Only compound instructions perform more
+    /// than one.
+    pub fn operations(&self) -> &[Instruction] {
+        match self {
+            Instruction::Compound(operations) => operations,
+            _ => std::slice::from_ref(self),
+        }
+    }
+
+    /// Formats the instruction for a listing of a program that runs with `jump` semantics. See
+    /// [`Listing`].
+    pub fn listing(&self, jump: JumpSemantics) -> Listing<'_> {
+        Listing {
+            instruction: self,
+            jump,
+        }
+    }
+}
+
+/// An instruction formatted like its `Display` implementation, followed by a comment saying
+/// whether a `JMP` with an empty condition is always or never taken, which depends on the jump
+/// semantics.
+pub struct Listing<'a> {
+    /// The instruction.
+    instruction: &'a Instruction,
+    /// The semantics of the program's jumps.
+    jump: JumpSemantics,
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.instruction)?;
+
+        let has_empty_jump = self.instruction.operations().iter().any(
+            |operation| matches!(operation, Instruction::JMP(condition, _) if condition.is_empty()),
+        );
+        if has_empty_jump {
+            let note = match self.jump {
+                JumpSemantics::Reference => "# always taken",
+                JumpSemantics::Strict => "# never taken",
+            };
+            write!(f, "  {}", note.black())?;
+        }
+
+        Ok(())
+    }
 }
 
 impl fmt::Display for Instruction {
@@ -61,6 +136,7 @@ impl fmt::Display for Instruction {
             Instruction::STM(a, b) => write!(f, "{} *{a} = {b}", "STM".green()),
             Instruction::LDM(a, b) => write!(f, "{} {a} = *{b}", "LDM".green()),
             Instruction::CMP(a, b) => write!(f, "{} {a} {b}", "CMP".green()),
+            Instruction::JMP(a, b) if a.is_empty() => write!(f, "{} {b}", "JMP".green()),
             Instruction::JMP(a, b) => {
                 write!(f, "{} {} {b}", "JMP".green(), a.to_string().blue())
             }
@@ -73,6 +149,37 @@ impl fmt::Display for Instruction {
                     b.map(|r| r.to_string().into()).unwrap_or("NONE".black())
                 )
             }
+            Instruction::Compound(operations) => {
+                for (i, operation) in operations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{operation}")?;
+                }
+
+                Ok(())
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listing_empty_jump() {
+        let jump = Instruction::JMP(Flags::default(), Register::D);
+
+        let reference = jump.listing(JumpSemantics::Reference).to_string();
+        let strict = jump.listing(JumpSemantics::Strict).to_string();
+        assert!(reference.ends_with("# always taken"));
+        assert!(strict.ends_with("# never taken"));
+
+        let conditional = Instruction::JMP("L".try_into().unwrap(), Register::D);
+        assert_eq!(
+            conditional.listing(JumpSemantics::Strict).to_string(),
+            conditional.to_string()
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use std::ops::{Index, IndexMut, Range, RangeFrom};
 
+use serde::{Deserialize, Serialize};
+
 /// The 256-byte Yan85 memory.
-#[derive(Debug)]
-pub struct Memory([u8; 256]);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Memory(#[serde(with = "super::hex")] [u8; 256]);
 
 impl Default for Memory {
     fn default() -> Self {
@@ -4,10 +4,14 @@ pub mod code;
 pub mod constants;
 /// Comparison result flags.
 pub mod flags;
+/// Serde helpers that represent byte arrays as hexadecimal strings.
+mod hex;
 /// A representation of Yan85 instructions.
 pub mod instruction;
 /// Yan85 memory.
 pub mod memory;
+/// Yan85 opcodes.
+pub mod opcode;
 /// A Yan85 register.
 pub mod register;
 /// The set of registers available in Yan85.
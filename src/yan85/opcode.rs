@@ -0,0 +1,73 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::constants::{Constants, Encodable};
+
+/// A Yan85 opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Opcode {
+    /// Assigns an integer to a register.
+    Imm,
+    /// Adds two registers.
+    Add,
+    /// Pushes to and pops from the stack.
+    Stk,
+    /// Stores to memory.
+    Stm,
+    /// Loads from memory.
+    Ldm,
+    /// Compares two registers.
+    Cmp,
+    /// Conditionally jumps.
+    Jmp,
+    /// Performs system calls.
+    Sys,
+}
+
+impl Opcode {
+    /// Every opcode, in the order that the reference VM typically tests them.
+    pub const ALL: [Opcode; 8] = [
+        Opcode::Imm,
+        Opcode::Add,
+        Opcode::Stk,
+        Opcode::Stm,
+        Opcode::Ldm,
+        Opcode::Cmp,
+        Opcode::Jmp,
+        Opcode::Sys,
+    ];
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Opcode::Imm => "IMM",
+            Opcode::Add => "ADD",
+            Opcode::Stk => "STK",
+            Opcode::Stm => "STM",
+            Opcode::Ldm => "LDM",
+            Opcode::Cmp => "CMP",
+            Opcode::Jmp => "JMP",
+            Opcode::Sys => "SYS",
+        };
+
+        f.pad(name)
+    }
+}
+
+impl Encodable for Opcode {
+    fn encode(&self, c: Constants) -> u8 {
+        match self {
+            Opcode::Imm => c.opcode.IMM,
+            Opcode::Add => c.opcode.ADD,
+            Opcode::Stk => c.opcode.STK,
+            Opcode::Stm => c.opcode.STM,
+            Opcode::Ldm => c.opcode.LDM,
+            Opcode::Cmp => c.opcode.CMP,
+            Opcode::Jmp => c.opcode.JMP,
+            Opcode::Sys => c.opcode.SYS,
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use anyhow::{anyhow, Result};
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use super::constants::{Constants, Decodable, Encodable};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 /// Yan85 registers.
 pub enum Register {
@@ -25,6 +27,19 @@ pub enum Register {
     F,
 }
 
+impl Register {
+    /// Every register, in encoding order.
+    pub const ALL: [Register; 7] = [
+        Register::A,
+        Register::B,
+        Register::C,
+        Register::D,
+        Register::S,
+        Register::I,
+        Register::F,
+    ];
+}
+
 impl Encodable for Register {
     fn encode(&self, c: Constants) -> u8 {
         match self {
@@ -85,6 +100,23 @@ impl Decodable for Option<Register> {
     }
 }
 
+impl TryFrom<&str> for Register {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "a" => Ok(Register::A),
+            "b" => Ok(Register::B),
+            "c" => Ok(Register::C),
+            "d" => Ok(Register::D),
+            "s" => Ok(Register::S),
+            "i" => Ok(Register::I),
+            "f" => Ok(Register::F),
+            _ => Err(anyhow!("Invalid register name: {value:?}")),
+        }
+    }
+}
+
 impl fmt::Display for Register {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
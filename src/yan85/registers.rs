@@ -1,11 +1,41 @@
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::HashMap,
+    ops::{Index, IndexMut},
+};
+
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::yan85::register::Register;
 
 /// The seven Yan85 registers, including [Register::S], [Register::I], and [Register::F].
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Registers([u8; 7]);
 
+/// Serializes as a map from register names to values, e.g. `{"a": 1, "b": 0, ...}`.
+impl Serialize for Registers {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(Register::ALL.len()))?;
+        for register in Register::ALL {
+            map.serialize_entry(&register, &self[register])?;
+        }
+        map.end()
+    }
+}
+
+/// Deserializes from a map from register names to values. Missing registers are zero.
+impl<'de> Deserialize<'de> for Registers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = HashMap::<Register, u8>::deserialize(deserializer)?;
+
+        let mut registers = Registers::default();
+        for (register, value) in values {
+            registers[register] = value;
+        }
+
+        Ok(registers)
+    }
+}
+
 impl Index<Register> for Registers {
     type Output = u8;
 
@@ -1,7 +1,10 @@
-use std::ops::{Index, IndexMut, Range};
+use std::ops::{Index, IndexMut, Range, RangeFrom};
+
+use serde::{Deserialize, Serialize};
 
 /// The 256-byte Yan85 stack.
-pub struct Stack([u8; 256]);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stack(#[serde(with = "super::hex")] [u8; 256]);
 
 impl Default for Stack {
     fn default() -> Self {
@@ -25,6 +28,14 @@ impl Index<Range<u8>> for Stack {
     }
 }
 
+impl Index<RangeFrom<u8>> for Stack {
+    type Output = [u8];
+
+    fn index(&self, index: RangeFrom<u8>) -> &Self::Output {
+        &self.0[index.start as usize..]
+    }
+}
+
 impl IndexMut<u8> for Stack {
     fn index_mut(&mut self, index: u8) -> &mut Self::Output {
         &mut self.0[index as usize]
@@ -1,11 +1,13 @@
-use std::ops::BitOr;
+use std::{fmt, ops::BitOr};
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 use super::constants::{Constants, Decodable, Encodable};
 
 /// A Yan85 system call.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Syscall {
     /// Opens a file.
     Open,
@@ -21,6 +23,33 @@ pub enum Syscall {
     Exit,
 }
 
+impl Syscall {
+    /// Every system call, in the order that the reference VM typically tests them.
+    pub const ALL: [Syscall; 6] = [
+        Syscall::Open,
+        Syscall::ReadCode,
+        Syscall::ReadMemory,
+        Syscall::Write,
+        Syscall::Sleep,
+        Syscall::Exit,
+    ];
+}
+
+impl fmt::Display for Syscall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Syscall::Open => "OPEN",
+            Syscall::ReadCode => "READ_CODE",
+            Syscall::ReadMemory => "READ_MEMORY",
+            Syscall::Write => "WRITE",
+            Syscall::Sleep => "SLEEP",
+            Syscall::Exit => "EXIT",
+        };
+
+        f.pad(name)
+    }
+}
+
 impl Encodable for Syscall {
     fn encode(&self, c: Constants) -> u8 {
         match self {
@@ -43,33 +72,17 @@ impl Encodable for Vec<Syscall> {
     }
 }
 
+/// Decodes the system calls whose bits are set, in the constants' syscall order.
 impl Decodable for Vec<Syscall> {
     fn decode(value: u8, c: Constants) -> Result<Self>
     where
         Self: std::marker::Sized,
     {
-        let mut syscalls = vec![];
-
-        if value & c.syscall.OPEN != 0 {
-            syscalls.push(Syscall::Open);
-        }
-        if value & c.syscall.READ_CODE != 0 {
-            syscalls.push(Syscall::ReadCode);
-        }
-        if value & c.syscall.READ_MEMORY != 0 {
-            syscalls.push(Syscall::ReadMemory);
-        }
-        if value & c.syscall.WRITE != 0 {
-            syscalls.push(Syscall::Write);
-        }
-        if value & c.syscall.SLEEP != 0 {
-            syscalls.push(Syscall::Sleep);
-        }
-        if value & c.syscall.EXIT != 0 {
-            syscalls.push(Syscall::Exit);
-        }
-
-        Ok(syscalls)
+        Ok(c.syscall
+            .order
+            .into_iter()
+            .filter(|syscall| value & syscall.encode(c) != 0)
+            .collect())
     }
 }
 
@@ -106,4 +119,15 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_decode_in_order() {
+        let mut consts = Constants::default();
+        consts.syscall.order.reverse();
+
+        assert_eq!(
+            Vec::<Syscall>::decode(consts.syscall.OPEN | consts.syscall.EXIT, consts).unwrap(),
+            vec![Syscall::Exit, Syscall::Open]
+        );
+    }
 }
@@ -0,0 +1,69 @@
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use gyan85::{
+    asm::assemble,
+    yan85::{constants::Constants, instruction::Instruction, register::Register},
+};
+
+#[test]
+fn test_debug_program_that_reads_stdin() {
+    let consts = Constants::default();
+    let instructions = vec![
+        // c = read_memory(0, 0x10, 5)
+        Instruction::IMM(Register::A, 0),
+        Instruction::IMM(Register::B, 0x10),
+        Instruction::IMM(Register::C, 5),
+        Instruction::SYS(consts.syscall.READ_MEMORY, Some(Register::C)),
+        // write(1, 0x10, c)
+        Instruction::IMM(Register::A, 1),
+        Instruction::SYS(consts.syscall.WRITE, None),
+        Instruction::SYS(consts.syscall.EXIT, None),
+    ];
+
+    let directory = env::temp_dir().join(format!("gyan85-debug-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let code_path = directory.join("read.bin");
+    let constants_path = directory.join("constants.yml");
+    fs::write(&code_path, assemble(&instructions, consts).unwrap()).unwrap();
+    fs::write(&constants_path, serde_yaml::to_string(&consts).unwrap()).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gyan85"))
+        .arg("-c")
+        .arg(&constants_path)
+        .arg("debug")
+        .arg(&code_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The program reads the line after the command that continues it.
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"c\nabcd\nq\n")
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("The debugger deadlocked on a read from standard input");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let output = child.wait_with_output().unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("abcd\n"));
+}
@@ -1,14 +1,14 @@
 use gyan85::{
     asm::assemble,
     disasm::disassemble,
-    yan85::{constants::Constants, instruction::Instruction, register::Register},
+    yan85::{constants::Constants, flags::Flags, instruction::Instruction, register::Register},
 };
 
 #[test]
 fn test_imm() {
     let consts = Constants::default();
     let instructions = vec![Instruction::IMM(Register::A, 5)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -17,7 +17,7 @@ fn test_imm() {
 fn test_add() {
     let consts = Constants::default();
     let instructions = vec![Instruction::ADD(Register::A, Register::B)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -26,7 +26,7 @@ fn test_add() {
 fn test_stk_push() {
     let consts = Constants::default();
     let instructions = vec![Instruction::STK(None, Some(Register::A))];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -35,7 +35,7 @@ fn test_stk_push() {
 fn test_stk_pop() {
     let consts = Constants::default();
     let instructions = vec![Instruction::STK(Some(Register::A), None)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -44,7 +44,7 @@ fn test_stk_pop() {
 fn test_stk_push_pop() {
     let consts = Constants::default();
     let instructions = vec![Instruction::STK(Some(Register::A), Some(Register::B))];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -53,7 +53,7 @@ fn test_stk_push_pop() {
 fn test_ldm() {
     let consts = Constants::default();
     let instructions = vec![Instruction::LDM(Register::A, Register::B)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -62,7 +62,7 @@ fn test_ldm() {
 fn test_cmp() {
     let consts = Constants::default();
     let instructions = vec![Instruction::CMP(Register::A, Register::B)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -71,7 +71,16 @@ fn test_cmp() {
 fn test_jmp() {
     let consts = Constants::default();
     let instructions = vec![Instruction::JMP("L".try_into().unwrap(), Register::A)];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
+
+    assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
+}
+
+#[test]
+fn test_jmp_unconditional() {
+    let consts = Constants::default();
+    let instructions = vec![Instruction::JMP(Flags::default(), Register::A)];
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }
@@ -80,7 +89,19 @@ fn test_jmp() {
 fn test_sys() {
     let consts = Constants::default();
     let instructions = vec![Instruction::SYS(0x1, Some(Register::D))];
-    let bytes = assemble(&instructions, consts);
+    let bytes = assemble(&instructions, consts).unwrap();
+
+    assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
+}
+
+#[test]
+fn test_compound() {
+    let consts = Constants::default();
+    let instructions = vec![Instruction::Compound(vec![
+        Instruction::STK(Some(Register::A), Some(Register::B)),
+        Instruction::CMP(Register::A, Register::B),
+    ])];
+    let bytes = assemble(&instructions, consts).unwrap();
 
     assert_eq!(disassemble(bytes, consts).unwrap(), instructions);
 }